@@ -0,0 +1,49 @@
+//性能测试
+//cargo run --release --bin benchmark -- [--warmup N] [--runs N] [--only copy,upload,download,compute,fractal]
+//                                        [--sizes BYTES,...] [--output results.json|results.csv]
+use std::env;
+use std::path::Path;
+use my_winit::example::benchmark::{run_benchmarks, write_results, BenchmarkGroup, BenchmarkSettings};
+use my_winit::example::pipeline_cache::save_pipeline_caches;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut settings = BenchmarkSettings::default();
+    let mut output = None;
+
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--warmup" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(warmup) => settings.warmup = warmup,
+                None => println!("--warmup expects a number"),
+            },
+            "--runs" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(runs) if runs > 0 => settings.runs = runs,
+                _ => println!("--runs expects a positive number"),
+            },
+            "--only" => match rest.next().map(|value| value.split(',').map(BenchmarkGroup::from_name).collect::<Option<Vec<_>>>()) {
+                Some(Some(groups)) => settings.groups = groups,
+                _ => println!("--only expects a list of copy, upload, download, compute, fractal"),
+            },
+            "--sizes" => match rest.next().map(|value| value.split(',').map(|size| size.trim().parse().ok()).collect::<Option<Vec<u64>>>()) {
+                Some(Some(sizes)) if sizes.iter().all(|size| *size > 0) => settings.copy_sizes = sizes,
+                _ => println!("--sizes expects a list of byte counts"),
+            },
+            "--output" => match rest.next() {
+                Some(path) => output = Some(path.clone()),
+                None => println!("--output expects a .json or .csv file"),
+            },
+            _ => println!("unknown argument {arg}"),
+        }
+    }
+
+    let (device, results) = run_benchmarks(&settings);
+    save_pipeline_caches();
+    if let Some(path) = output {
+        match write_results(Path::new(&path), &device, &settings, &results) {
+            Ok(()) => println!("saved {path}"),
+            Err(e) => println!("{e}"),
+        }
+    }
+}
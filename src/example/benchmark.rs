@@ -0,0 +1,553 @@
+//性能测试：buffer 复制、主机和设备之间的传输、计算着色器的调度开销、Mandelbrot 渲染
+//每个用例先运行 warmup 次不计时，再运行 runs 次，记录墙钟时间（录制、提交并等待完成）和 GPU 时间（时间戳查询），
+//输出中位数和 p95，结果可以写成 JSON 或 CSV，用来比较不同驱动版本之间的性能
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync::{self, GpuFuture};
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device, create_instance, create_memory_allocator, get_queue};
+use crate::example::fractal::{FractalRenderer, DEFAULT_ZOOM_CENTER};
+use crate::example::glsl::cs;
+use crate::example::pipeline_cache::SharedPipelineCache;
+use crate::example::profiler::{escape_json, GpuProfiler};
+
+//用例的分组，命令行中用名字选择
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BenchmarkGroup {
+    //设备内存之间的 copy_buffer
+    Copy,
+    //主机可见内存复制到设备内存
+    Upload,
+    //设备内存复制到主机可见内存，包括 CPU 读取
+    Download,
+    //operator_computer 的着色器，不同的工作组数
+    Compute,
+    //Mandelbrot 的计算着色器和图像读回
+    Fractal,
+}
+
+impl BenchmarkGroup {
+    pub const ALL: [BenchmarkGroup; 5] = [
+        BenchmarkGroup::Copy,
+        BenchmarkGroup::Upload,
+        BenchmarkGroup::Download,
+        BenchmarkGroup::Compute,
+        BenchmarkGroup::Fractal,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BenchmarkGroup::Copy => "copy",
+            BenchmarkGroup::Upload => "upload",
+            BenchmarkGroup::Download => "download",
+            BenchmarkGroup::Compute => "compute",
+            BenchmarkGroup::Fractal => "fractal",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<BenchmarkGroup> {
+        BenchmarkGroup::ALL.into_iter().find(|group| group.name() == name)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BenchmarkSettings {
+    pub warmup: u32,
+    pub runs: u32,
+    pub groups: Vec<BenchmarkGroup>,
+    //copy、upload 和 download 的字节数
+    pub copy_sizes: Vec<u64>,
+    //compute 的工作组数，每个工作组64个元素
+    pub dispatch_sizes: Vec<u32>,
+    pub fractal_sizes: Vec<[u32; 2]>,
+}
+
+impl Default for BenchmarkSettings {
+    fn default() -> Self {
+        BenchmarkSettings {
+            warmup: 3,
+            runs: 20,
+            groups: BenchmarkGroup::ALL.to_vec(),
+            copy_sizes: vec![64 << 10, 1 << 20, 16 << 20, 64 << 20],
+            dispatch_sizes: vec![1, 1024, 16384],
+            fractal_sizes: vec![[256, 256], [512, 512], [1024, 1024], [1920, 1080]],
+        }
+    }
+}
+
+//一个用例的所有计时，单位毫秒
+#[derive(Clone, Debug)]
+pub struct BenchmarkResult {
+    pub group: BenchmarkGroup,
+    //用例的参数，例如 "16777216 bytes"、"1024x1024"
+    pub case: String,
+    pub wall_ms: Vec<f64>,
+    //设备不支持时间戳查询或者用例不使用 GPU 计时时为空
+    pub gpu_ms: Vec<f64>,
+    //传输的字节数，用来计算带宽
+    pub bytes: Option<u64>,
+}
+
+impl BenchmarkResult {
+    //有 GPU 时间时按 GPU 时间计算，否则按墙钟时间
+    pub fn throughput_gb_per_s(&self) -> Option<f64> {
+        let bytes = self.bytes?;
+        let ms = median(&self.gpu_ms).or_else(|| median(&self.wall_ms))?;
+        (ms > 0.0).then(|| bytes as f64 / (ms / 1000.0) / 1e9)
+    }
+}
+
+//结果文件中的设备信息，驱动更新以后用来区分不同的结果
+#[derive(Clone, Debug)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub driver_version: u32,
+    pub driver_info: Option<String>,
+    pub api_version: String,
+}
+
+pub fn median(samples: &[f64]) -> Option<f64> {
+    percentile(samples, 0.5)
+}
+
+pub fn p95(samples: &[f64]) -> Option<f64> {
+    percentile(samples, 0.95)
+}
+
+//最近秩法：排序后取第 ceil(p * n) 个
+pub fn percentile(samples: &[f64], p: f64) -> Option<f64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = ((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    Some(sorted[rank - 1])
+}
+
+//copy、upload、download 和 compute 共用的设备，每次运行录制一个命令缓冲区，提交后等待完成
+struct BenchmarkContext {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    profiler: GpuProfiler,
+}
+
+impl BenchmarkContext {
+    fn new() -> BenchmarkContext {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+
+        BenchmarkContext {
+            memory_allocator: Arc::new(create_memory_allocator(device.clone())),
+            command_buffer_allocator: create_buffer_allocator(device.clone()),
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+            profiler: GpuProfiler::new(device.clone(), queue_index, 1, 1),
+            device,
+            queue,
+        }
+    }
+
+    fn device_info(&self) -> DeviceInfo {
+        let properties = self.device.physical_device().properties();
+        DeviceInfo {
+            name: properties.device_name.clone(),
+            driver_version: properties.driver_version,
+            driver_info: properties.driver_info.clone(),
+            api_version: properties.api_version.to_string(),
+        }
+    }
+
+    //录制的命令被一个 GPU 区间包围，返回它的毫秒数
+    fn submit(&mut self, record: impl FnOnce(&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>)) -> Option<f64> {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        self.profiler.begin_frame(&mut builder, 0);
+        self.profiler.begin_scope(&mut builder, "run");
+        record(&mut builder);
+        self.profiler.end_scope(&mut builder);
+        self.profiler.end_frame();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        self.profiler
+            .collect(0)
+            .and_then(|timings| timings.first().map(|timing| timing.duration_ms as f64))
+    }
+
+    fn create_buffer(&self, usage: BufferUsage, memory_usage: MemoryUsage, size: u64) -> Subbuffer<[u8]> {
+        Buffer::new_slice::<u8>(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: memory_usage,
+                ..Default::default()
+            },
+            size,
+        )
+        .expect("failed to create benchmark buffer")
+    }
+}
+
+//按设置运行所有选中的用例，每个用例完成后打印一行
+pub fn run_benchmarks(settings: &BenchmarkSettings) -> (DeviceInfo, Vec<BenchmarkResult>) {
+    let mut context = BenchmarkContext::new();
+    let device_info = context.device_info();
+    println!("{} (driver {})", device_info.name, device_info.driver_version);
+
+    let mut results = Vec::new();
+    for &group in &settings.groups {
+        let group_results = match group {
+            BenchmarkGroup::Copy => bench_transfers(&mut context, settings, group, MemoryUsage::DeviceOnly, MemoryUsage::DeviceOnly),
+            BenchmarkGroup::Upload => bench_transfers(&mut context, settings, group, MemoryUsage::Upload, MemoryUsage::DeviceOnly),
+            BenchmarkGroup::Download => bench_transfers(&mut context, settings, group, MemoryUsage::DeviceOnly, MemoryUsage::Download),
+            BenchmarkGroup::Compute => bench_compute(&mut context, settings),
+            BenchmarkGroup::Fractal => bench_fractal(settings),
+        };
+        for result in group_results {
+            println!("{}", format_result(&result));
+            results.push(result);
+        }
+    }
+    (device_info, results)
+}
+
+//先运行 warmup 次，再计时运行 runs 次，run 返回这次运行的 GPU 时间
+fn measure(settings: &BenchmarkSettings, mut run: impl FnMut() -> Option<f64>) -> (Vec<f64>, Vec<f64>) {
+    for _ in 0..settings.warmup {
+        run();
+    }
+    let mut wall_ms = Vec::new();
+    let mut gpu_ms = Vec::new();
+    for _ in 0..settings.runs {
+        let start = Instant::now();
+        let gpu = run();
+        wall_ms.push(start.elapsed().as_secs_f64() * 1000.0);
+        gpu_ms.extend(gpu);
+    }
+    (wall_ms, gpu_ms)
+}
+
+//从 source 内存复制到 destination 内存，下载时墙钟时间包括 CPU 读取复制结果
+fn bench_transfers(
+    context: &mut BenchmarkContext,
+    settings: &BenchmarkSettings,
+    group: BenchmarkGroup,
+    source_usage: MemoryUsage,
+    destination_usage: MemoryUsage,
+) -> Vec<BenchmarkResult> {
+    settings
+        .copy_sizes
+        .iter()
+        .map(|&size| {
+            let source = context.create_buffer(BufferUsage::TRANSFER_SRC, source_usage, size);
+            let destination = context.create_buffer(BufferUsage::TRANSFER_DST, destination_usage, size);
+            let (wall_ms, gpu_ms) = measure(settings, || {
+                let gpu_ms = context.submit(|builder| {
+                    builder
+                        .copy_buffer(CopyBufferInfo::buffers(source.clone(), destination.clone()))
+                        .unwrap();
+                });
+                if destination_usage == MemoryUsage::Download {
+                    let content = destination.read().unwrap();
+                    std::hint::black_box(content.iter().fold(0u8, |sum, value| sum.wrapping_add(*value)));
+                }
+                gpu_ms
+            });
+            BenchmarkResult {
+                group,
+                case: format!("{size} bytes"),
+                wall_ms,
+                gpu_ms,
+                bytes: Some(size),
+            }
+        })
+        .collect()
+}
+
+//operator_computer 的着色器，1个工作组的用例基本上就是一次提交和等待的开销
+fn bench_compute(context: &mut BenchmarkContext, settings: &BenchmarkSettings) -> Vec<BenchmarkResult> {
+    let shader = cs::load(context.device.clone()).expect("failed to create shader module");
+    let pipeline_cache = SharedPipelineCache::new(context.device.clone());
+    let pipeline = ComputePipeline::new(
+        context.device.clone(),
+        shader.entry_point("main").unwrap(),
+        &(),
+        Some(pipeline_cache.cache()),
+        |_| {},
+    )
+    .expect("failed to create compute pipeline");
+
+    settings
+        .dispatch_sizes
+        .iter()
+        .map(|&work_groups| {
+            let elements = work_groups as u64 * 64;
+            let data = context.create_buffer(BufferUsage::STORAGE_BUFFER, MemoryUsage::DeviceOnly, elements * 4);
+            let set = PersistentDescriptorSet::new(
+                &context.descriptor_set_allocator,
+                pipeline.layout().set_layouts()[0].clone(),
+                [WriteDescriptorSet::buffer(0, data)],
+            )
+            .unwrap();
+
+            let (wall_ms, gpu_ms) = measure(settings, || {
+                context.submit(|builder| {
+                    builder
+                        .bind_pipeline_compute(pipeline.clone())
+                        .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline.layout().clone(), 0, set.clone())
+                        .dispatch([work_groups, 1, 1])
+                        .unwrap();
+                })
+            });
+            BenchmarkResult {
+                group: BenchmarkGroup::Compute,
+                case: format!("{work_groups} work groups"),
+                wall_ms,
+                gpu_ms,
+                bytes: None,
+            }
+        })
+        .collect()
+}
+
+//每个分辨率创建一个 FractalRenderer，计时包括计算、复制到缓冲区和等待完成
+fn bench_fractal(settings: &BenchmarkSettings) -> Vec<BenchmarkResult> {
+    settings
+        .fractal_sizes
+        .iter()
+        .map(|&[width, height]| {
+            let renderer = FractalRenderer::new(width, height);
+            let (wall_ms, gpu_ms) = measure(settings, || {
+                std::hint::black_box(renderer.render(DEFAULT_ZOOM_CENTER, 3.0));
+                None
+            });
+            BenchmarkResult {
+                group: BenchmarkGroup::Fractal,
+                case: format!("{width}x{height}"),
+                wall_ms,
+                gpu_ms,
+                bytes: None,
+            }
+        })
+        .collect()
+}
+
+fn format_result(result: &BenchmarkResult) -> String {
+    let mut line = format!(
+        "{:<9} {:<22} median {:>9.3} ms  p95 {:>9.3} ms",
+        result.group.name(),
+        result.case,
+        median(&result.wall_ms).unwrap_or(0.0),
+        p95(&result.wall_ms).unwrap_or(0.0),
+    );
+    if let (Some(gpu_median), Some(gpu_p95)) = (median(&result.gpu_ms), p95(&result.gpu_ms)) {
+        write!(line, "  gpu median {gpu_median:>9.3} ms  p95 {gpu_p95:>9.3} ms").unwrap();
+    }
+    if let Some(throughput) = result.throughput_gb_per_s() {
+        write!(line, "  {throughput:>7.2} GB/s").unwrap();
+    }
+    line
+}
+
+//扩展名为 .csv 时写 CSV，否则写 JSON
+pub fn write_results(path: &Path, device: &DeviceInfo, settings: &BenchmarkSettings, results: &[BenchmarkResult]) -> Result<(), String> {
+    let is_csv = path
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false);
+    let content = if is_csv {
+        results_to_csv(device, results)
+    } else {
+        results_to_json(device, settings, results)
+    };
+    fs::write(path, content).map_err(|e| format!("failed to write {}: {e}", path.display()))
+}
+
+//没有的值留空，每一行都带上设备和驱动版本，方便把多次的结果合并到一张表中
+pub fn results_to_csv(device: &DeviceInfo, results: &[BenchmarkResult]) -> String {
+    let mut csv = String::from(
+        "device,driver_version,group,case,runs,median_ms,p95_ms,min_ms,gpu_median_ms,gpu_p95_ms,bytes,throughput_gb_s\n",
+    );
+    let optional = |value: Option<f64>| value.map(|value| format!("{value:.6}")).unwrap_or_default();
+    for result in results {
+        writeln!(
+            csv,
+            "\"{}\",{},{},\"{}\",{},{},{},{},{},{},{},{}",
+            device.name.replace('"', "\"\""),
+            device.driver_version,
+            result.group.name(),
+            result.case,
+            result.wall_ms.len(),
+            optional(median(&result.wall_ms)),
+            optional(p95(&result.wall_ms)),
+            optional(percentile(&result.wall_ms, 0.0)),
+            optional(median(&result.gpu_ms)),
+            optional(p95(&result.gpu_ms)),
+            result.bytes.map(|bytes| bytes.to_string()).unwrap_or_default(),
+            optional(result.throughput_gb_per_s()),
+        )
+        .unwrap();
+    }
+    csv
+}
+
+//除了统计值以外也保存每次运行的时间，之后可以用别的统计方法重新分析
+pub fn results_to_json(device: &DeviceInfo, settings: &BenchmarkSettings, results: &[BenchmarkResult]) -> String {
+    let optional = |value: Option<f64>| value.map(|value| format!("{value:.6}")).unwrap_or_else(|| "null".to_string());
+    let samples = |samples: &[f64]| {
+        let values: Vec<String> = samples.iter().map(|value| format!("{value:.6}")).collect();
+        format!("[{}]", values.join(","))
+    };
+
+    let mut json = String::from("{\n");
+    writeln!(
+        json,
+        "  \"device\": {{\"name\": \"{}\", \"driver_version\": {}, \"driver_info\": {}, \"api_version\": \"{}\"}},",
+        escape_json(&device.name),
+        device.driver_version,
+        device
+            .driver_info
+            .as_ref()
+            .map(|info| format!("\"{}\"", escape_json(info)))
+            .unwrap_or_else(|| "null".to_string()),
+        device.api_version,
+    )
+    .unwrap();
+    writeln!(json, "  \"warmup\": {},\n  \"runs\": {},\n  \"results\": [", settings.warmup, settings.runs).unwrap();
+    for (i, result) in results.iter().enumerate() {
+        writeln!(
+            json,
+            "    {{\"group\": \"{}\", \"case\": \"{}\", \"median_ms\": {}, \"p95_ms\": {}, \"gpu_median_ms\": {}, \"gpu_p95_ms\": {}, \"bytes\": {}, \"throughput_gb_s\": {}, \"wall_ms\": {}, \"gpu_ms\": {}}}{}",
+            result.group.name(),
+            escape_json(&result.case),
+            optional(median(&result.wall_ms)),
+            optional(p95(&result.wall_ms)),
+            optional(median(&result.gpu_ms)),
+            optional(p95(&result.gpu_ms)),
+            result.bytes.map(|bytes| bytes.to_string()).unwrap_or_else(|| "null".to_string()),
+            optional(result.throughput_gb_per_s()),
+            samples(&result.wall_ms),
+            samples(&result.gpu_ms),
+            if i + 1 < results.len() { "," } else { "" },
+        )
+        .unwrap();
+    }
+    json.push_str("  ]\n}\n");
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device() -> DeviceInfo {
+        DeviceInfo {
+            name: "GPU \"Pro\" \\ 9".to_string(),
+            driver_version: 42,
+            driver_info: Some("line1\nline2".to_string()),
+            api_version: "1.3.250".to_string(),
+        }
+    }
+
+    fn results() -> Vec<BenchmarkResult> {
+        vec![
+            BenchmarkResult {
+                group: BenchmarkGroup::Copy,
+                case: "1000000 bytes".to_string(),
+                wall_ms: vec![4.0, 1.0, 3.0, 2.0],
+                gpu_ms: vec![1.5, 0.5],
+                bytes: Some(1_000_000),
+            },
+            BenchmarkResult {
+                group: BenchmarkGroup::Fractal,
+                case: "1024x1024".to_string(),
+                wall_ms: vec![10.0],
+                gpu_ms: Vec::new(),
+                bytes: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn percentiles_use_nearest_rank() {
+        let samples = [5.0, 1.0, 4.0, 2.0, 3.0];
+        assert_eq!(median(&samples), Some(3.0));
+        assert_eq!(p95(&samples), Some(5.0));
+        assert_eq!(percentile(&samples, 0.0), Some(1.0));
+        assert_eq!(percentile(&samples, 1.0), Some(5.0));
+
+        //偶数个样本时取较小的中间值
+        assert_eq!(median(&[4.0, 1.0, 3.0, 2.0]), Some(2.0));
+
+        let samples: Vec<f64> = (1..=20).map(f64::from).collect();
+        assert_eq!(median(&samples), Some(10.0));
+        assert_eq!(p95(&samples), Some(19.0));
+    }
+
+    #[test]
+    fn empty_samples_have_no_percentile() {
+        assert_eq!(median(&[]), None);
+        assert_eq!(p95(&[]), None);
+        assert_eq!(percentile(&[], 0.0), None);
+    }
+
+    #[test]
+    fn csv_snapshot() {
+        let expected = concat!(
+            "device,driver_version,group,case,runs,median_ms,p95_ms,min_ms,gpu_median_ms,gpu_p95_ms,bytes,throughput_gb_s\n",
+            "\"GPU \"\"Pro\"\" \\ 9\",42,copy,\"1000000 bytes\",4,2.000000,4.000000,1.000000,0.500000,1.500000,1000000,2.000000\n",
+            "\"GPU \"\"Pro\"\" \\ 9\",42,fractal,\"1024x1024\",1,10.000000,10.000000,10.000000,,,,\n",
+        );
+        assert_eq!(results_to_csv(&device(), &results()), expected);
+    }
+
+    #[test]
+    fn json_snapshot() {
+        let settings = BenchmarkSettings {
+            warmup: 1,
+            runs: 4,
+            ..BenchmarkSettings::default()
+        };
+        let expected = concat!(
+            "{\n",
+            "  \"device\": {\"name\": \"GPU \\\"Pro\\\" \\\\ 9\", \"driver_version\": 42, \"driver_info\": \"line1\\nline2\", \"api_version\": \"1.3.250\"},\n",
+            "  \"warmup\": 1,\n",
+            "  \"runs\": 4,\n",
+            "  \"results\": [\n",
+            "    {\"group\": \"copy\", \"case\": \"1000000 bytes\", \"median_ms\": 2.000000, \"p95_ms\": 4.000000, \"gpu_median_ms\": 0.500000, \"gpu_p95_ms\": 1.500000, \"bytes\": 1000000, \"throughput_gb_s\": 2.000000, \"wall_ms\": [4.000000,1.000000,3.000000,2.000000], \"gpu_ms\": [1.500000,0.500000]},\n",
+            "    {\"group\": \"fractal\", \"case\": \"1024x1024\", \"median_ms\": 10.000000, \"p95_ms\": 10.000000, \"gpu_median_ms\": null, \"gpu_p95_ms\": null, \"bytes\": null, \"throughput_gb_s\": null, \"wall_ms\": [10.000000], \"gpu_ms\": []}\n",
+            "  ]\n",
+            "}\n",
+        );
+        assert_eq!(results_to_json(&device(), &settings, &results()), expected);
+    }
+}
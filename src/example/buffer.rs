@@ -5,7 +5,7 @@ use vulkano::{
         allocator::{StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo},
         AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferInfo,
     },
-    device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags},
+    device::{Device, DeviceCreateInfo, Features, QueueCreateInfo, QueueFlags},
     instance::{Instance, InstanceCreateInfo},
     memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator},
     sync::{self, GpuFuture},
@@ -75,6 +75,21 @@ pub fn operator_buffer() {
     println!("Everything succeed!");
 }
 
+//能创建 Vulkan 实例并且至少有一个物理设备时返回第一个设备的名字，也就是 choose_device 选择的设备
+//和 create_instance、choose_device 不同，没有 Vulkan 时返回 Err 而不是 panic，用来决定是否使用软件渲染或跳过测试
+pub fn find_vulkan_device() -> Result<String, String> {
+    let library = VulkanLibrary::new().map_err(|e| format!("no Vulkan library: {e}"))?;
+    let instance = Instance::new(library, InstanceCreateInfo::default())
+        .map_err(|e| format!("failed to create a Vulkan instance: {e}"))?;
+    let device = instance
+        .enumerate_physical_devices()
+        .map_err(|e| format!("failed to enumerate Vulkan devices: {e}"))?
+        .next()
+        .ok_or_else(|| "no Vulkan device (ICD) is available".to_string())?;
+    let name = device.properties().device_name.clone();
+    Ok(name)
+}
+
 //创建一个实例，如果系统不可使用vulkan，报错
 pub fn create_instance() -> Arc<Instance> {
     let library = VulkanLibrary::new().expect("no local Vulkan libray");
@@ -110,6 +125,11 @@ pub fn get_queue(physical_device: Arc<PhysicalDevice>) -> u32 {
 
 //创建设备 创建设备返回两件事：设备本身，以及队列对象列表 稍后将允许我们提交操作。
 pub fn create_device(physical_device: Arc<PhysicalDevice>, queue_family_index: u32) -> (Arc<Device>, impl ExactSizeIterator<Item=Arc<Queue>>) {
+    create_device_with_features(physical_device, queue_family_index, Features::empty())
+}
+
+//创建设备并开启指定的设备特性，特性必须是物理设备支持的
+pub fn create_device_with_features(physical_device: Arc<PhysicalDevice>, queue_family_index: u32, enabled_features: Features) -> (Arc<Device>, impl ExactSizeIterator<Item=Arc<Queue>>) {
     let (device, queues) = Device::new(
         physical_device,
         DeviceCreateInfo {
@@ -117,6 +137,7 @@ pub fn create_device(physical_device: Arc<PhysicalDevice>, queue_family_index: u
                 queue_family_index,
                 ..Default::default()
             }],
+            enabled_features,
             ..Default::default()
         },
     ).expect("failed to create device");
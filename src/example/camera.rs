@@ -0,0 +1,152 @@
+//交互相机
+//轨道相机围绕目标点旋转和缩放，自由相机用键盘移动、用鼠标转向。
+//两种模式共用同一组参数：目标点、距离和朝向，相机位置 = 目标点 + 距离 * 朝向，
+//所以切换模式时画面不会跳动
+use std::f32::consts::FRAC_PI_2;
+use cgmath::{InnerSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3};
+use crate::example::input::{Action, InputBindings, InputState};
+use crate::example::mesh_glsl::vs;
+use crate::example::render_mesh::{get_projection, RenderOptions};
+
+//鼠标移动一个像素旋转的弧度
+const ROTATE_SPEED: f32 = 0.005;
+//俯仰角限制在 ±89° 以内，避免 look_at 在正上方和正下方退化
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.02;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    Orbit,
+    Fly,
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera {
+    pub mode: CameraMode,
+    pub target: Point3<f32>,
+    pub distance: f32,
+    //绕Y轴的角度，0 时相机在目标点的 +Z 方向
+    pub yaw: f32,
+    pub pitch: f32,
+    //自由相机每秒移动的距离
+    pub speed: f32,
+}
+
+impl Camera {
+    //轨道相机，根据包围盒摆放，让整个场景都出现在画面中
+    pub fn framing(bounds: ([f32; 3], [f32; 3])) -> Camera {
+        let (center, radius) = get_bounding_sphere(bounds);
+        Camera {
+            mode: CameraMode::Orbit,
+            target: center,
+            distance: radius * 2.5,
+            yaw: 0.0,
+            pitch: 0.0,
+            speed: radius,
+        }
+    }
+
+    //从目标点指向相机的单位向量
+    pub fn direction(&self) -> Vector3<f32> {
+        Vector3::new(
+            self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            self.pitch.cos() * self.yaw.cos(),
+        )
+    }
+
+    pub fn position(&self) -> Point3<f32> {
+        self.target + self.direction() * self.distance
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_rh(self.position(), self.target, Vector3::unit_y())
+    }
+
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            CameraMode::Orbit => CameraMode::Fly,
+            CameraMode::Fly => CameraMode::Orbit,
+        };
+    }
+
+    //根据这一帧的输入更新相机，delta_time 是距离上一帧的秒数
+    pub fn update(&mut self, input: &InputState, bindings: &InputBindings, delta_time: f32) {
+        let [dx, dy] = input.mouse_delta;
+        let forward = -self.direction();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+
+        //按住 Shift 拖动也是平移
+        let panning = input.is_action_down(bindings, Action::Pan)
+            || (input.modifiers.shift() && input.is_action_down(bindings, Action::Rotate));
+
+        if panning {
+            let scale = self.distance * 0.002;
+            self.target += (-right * dx + up * dy) * scale;
+        } else if input.is_action_down(bindings, Action::Rotate) {
+            let position = self.position();
+            self.yaw -= dx * ROTATE_SPEED;
+            self.pitch = (self.pitch + dy * ROTATE_SPEED).clamp(-MAX_PITCH, MAX_PITCH);
+            //自由相机绕自己的位置转向，轨道相机绕目标点旋转
+            if self.mode == CameraMode::Fly {
+                self.target = position - self.direction() * self.distance;
+            }
+        }
+
+        match self.mode {
+            CameraMode::Orbit => {
+                //滚轮缩放
+                self.distance = (self.distance * 0.9f32.powf(input.wheel_delta)).max(0.001);
+            }
+            CameraMode::Fly => {
+                //滚轮调整移动速度
+                self.speed *= 1.2f32.powf(input.wheel_delta);
+
+                let movement = forward * input.axis(bindings, Action::MoveBackward, Action::MoveForward)
+                    + right * input.axis(bindings, Action::MoveLeft, Action::MoveRight)
+                    + Vector3::unit_y() * input.axis(bindings, Action::MoveDown, Action::MoveUp);
+                if movement.magnitude2() > 0.0 {
+                    let faster = if input.is_action_down(bindings, Action::Faster) { 4.0 } else { 1.0 };
+                    self.target += movement.normalize() * self.speed * faster * delta_time;
+                }
+            }
+        }
+    }
+}
+
+//包围球的中心和半径
+pub fn get_bounding_sphere(bounds: ([f32; 3], [f32; 3])) -> (Point3<f32>, f32) {
+    let (min, max) = bounds;
+    let center = Point3::new(
+        (min[0] + max[0]) / 2.0,
+        (min[1] + max[1]) / 2.0,
+        (min[2] + max[2]) / 2.0,
+    );
+    let radius = (Vector3::new(max[0] - min[0], max[1] - min[1], max[2] - min[2]) / 2.0)
+        .magnitude()
+        .max(0.001);
+    (center, radius)
+}
+
+//使用相机的 uniform 数据，远近平面根据相机到场景的距离计算，自由相机飞远以后场景也不会被裁掉
+//开启 animate 时世界矩阵随时间绕Z轴旋转
+pub fn get_camera_uniforms(camera: &Camera, bounds: ([f32; 3], [f32; 3]), aspect_ratio: f32, elapsed: f32, options: &RenderOptions) -> vs::Data {
+    let (center, radius) = get_bounding_sphere(bounds);
+    let far = (camera.position() - center).magnitude() + radius * 2.0;
+
+    let world = if options.animate {
+        Matrix4::from_angle_z(Rad(elapsed))
+    } else {
+        Matrix4::identity()
+    };
+
+    vs::Data {
+        world: world.into(),
+        view: camera.view_matrix().into(),
+        proj: get_projection(aspect_ratio, radius * 0.01, far.max(radius * 10.0)).into(),
+        time: elapsed,
+        point_size: options.point_size,
+        scale_factor: options.scale_factor,
+        animate: if options.animate { 1.0 } else { 0.0 },
+    }
+}
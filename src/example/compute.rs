@@ -13,6 +13,7 @@ use vulkano::sync;
 use vulkano::sync::GpuFuture;
 
 use crate::example::glsl::*;
+use crate::example::pipeline_cache::SharedPipelineCache;
 
 pub fn operator_computer() {
     let data_iter = 0..65536u32;
@@ -26,11 +27,13 @@ pub fn operator_computer() {
     let data_buffer = create_iter_buffer(Arc::new(memory_allocator), BufferUsage::STORAGE_BUFFER, MemoryUsage::Upload, data_iter);
 
     let shader = cs::load(device.clone()).expect("failed to create shader module");
+    //管线缓存保存着上次运行的编译结果
+    let pipeline_cache = SharedPipelineCache::new(device.clone());
     let compute_pipeline = ComputePipeline::new(
         device.clone(),
         shader.entry_point("main").unwrap(),
         &(),
-        None,
+        Some(pipeline_cache.cache()),
         |_| {}
     ).expect("failed to create compute pipeline");
 
@@ -0,0 +1,88 @@
+//简单的配置文件格式，按键绑定、窗口配置等都使用它
+//每行一个 key = value，# 开头的行是注释，[name] 开始一个分组，分组中的键会加上 "name." 前缀
+use std::fs;
+use std::path::Path;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigEntry {
+    //行号从1开始，用于错误信息
+    pub line: usize,
+    pub key: String,
+    pub value: String,
+}
+
+pub fn load_config(path: &Path) -> Result<Vec<ConfigEntry>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    parse_config(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+pub fn parse_config(text: &str) -> Result<Vec<ConfigEntry>, String> {
+    let mut entries = Vec::new();
+    let mut section = String::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+            section = name.trim().to_string();
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| format!("line {line_number}: expected key = value"))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("line {line_number}: missing key"));
+        }
+
+        entries.push(ConfigEntry {
+            line: line_number,
+            key: if section.is_empty() { key.to_string() } else { format!("{section}.{key}") },
+            value: value.trim().to_string(),
+        });
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(line: usize, key: &str, value: &str) -> ConfigEntry {
+        ConfigEntry { line, key: key.to_string(), value: value.to_string() }
+    }
+
+    #[test]
+    fn parses_sections_comments_and_blank_lines() {
+        let text = "# comment\n\ntitle =  my window \n[ input ]\n  screenshot=F12\n\n  # indented comment\nfilter = gamma = 2.2\n";
+        let entries = parse_config(text).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                entry(3, "title", "my window"),
+                entry(5, "input.screenshot", "F12"),
+                //只按第一个 = 分开，值里可以有 =
+                entry(8, "input.filter", "gamma = 2.2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_value_is_allowed() {
+        assert_eq!(parse_config("icon =").unwrap(), vec![entry(1, "icon", "")]);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert_eq!(parse_config("title = a\nnot a setting\n"), Err(String::from("line 2: expected key = value")));
+        assert_eq!(parse_config("  = value"), Err(String::from("line 1: missing key")));
+        //没有 ] 的分组名不是分组，也不是 key = value
+        assert_eq!(parse_config("[window\nsize = 1x1"), Err(String::from("line 1: expected key = value")));
+    }
+}
@@ -0,0 +1,278 @@
+//立即模式的调试绘制
+//调用方每帧向 DebugDraw 添加世界空间中的线段、包围盒、线框球、箭头、网格和坐标轴，
+//所有线段合并成一个顶点缓冲区，在场景之后用线列表管线一次画完
+//屏幕空间的线段（例如帧时间曲线）以逻辑像素为单位，和世界空间的线段放在同一个顶点缓冲区中
+use std::f32::consts::TAU;
+use std::sync::Arc;
+use cgmath::{InnerSpace, Matrix4, Vector3};
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
+use vulkano::buffer::{BufferContents, BufferUsage};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::DeviceOwned;
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, StateMode};
+use vulkano::render_pass::{RenderPass, Subpass};
+use crate::example::debug_draw_glsl::{fs, vs};
+use crate::example::mesh_glsl;
+
+//线框球每个圆的线段数
+const SPHERE_SEGMENTS: usize = 32;
+
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct DebugVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+//这一帧要画的线段，每两个顶点是一条线段
+#[derive(Clone, Debug, Default)]
+pub struct DebugDraw {
+    vertices: Vec<DebugVertex>,
+    //屏幕空间的线段，z 为0，画在所有东西前面
+    screen_vertices: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw::default()
+    }
+
+    //每帧开始时清空，之后重新添加
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.screen_vertices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty() && self.screen_vertices.is_empty()
+    }
+
+    pub fn vertices(&self) -> &[DebugVertex] {
+        &self.vertices
+    }
+
+    pub fn screen_vertices(&self) -> &[DebugVertex] {
+        &self.screen_vertices
+    }
+
+    pub fn line(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 4]) {
+        self.vertices.push(DebugVertex { position: from, color });
+        self.vertices.push(DebugVertex { position: to, color });
+    }
+
+    //屏幕空间的线段，坐标是逻辑像素，左上角为原点
+    pub fn line_2d(&mut self, from: [f32; 2], to: [f32; 2], color: [f32; 4]) {
+        self.screen_vertices.push(DebugVertex { position: [from[0], from[1], 0.0], color });
+        self.screen_vertices.push(DebugVertex { position: [to[0], to[1], 0.0], color });
+    }
+
+    //轴对齐包围盒的12条边
+    pub fn aabb(&mut self, min: [f32; 3], max: [f32; 3], color: [f32; 4]) {
+        let corner = |i: usize| {
+            [
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ]
+        };
+        for i in 0..8 {
+            //每个角和坐标比它大的三个相邻角相连，每条边只画一次
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.line(corner(i), corner(i | axis), color);
+                }
+            }
+        }
+    }
+
+    //线框球：三个坐标平面上的大圆
+    pub fn sphere(&mut self, center: [f32; 3], radius: f32, color: [f32; 4]) {
+        let center = Vector3::from(center);
+        self.circle(center, Vector3::unit_x() * radius, Vector3::unit_y() * radius, color);
+        self.circle(center, Vector3::unit_y() * radius, Vector3::unit_z() * radius, color);
+        self.circle(center, Vector3::unit_z() * radius, Vector3::unit_x() * radius, color);
+    }
+
+    //带箭头的线段，箭头长度是线段长度的 1/5
+    pub fn arrow(&mut self, from: [f32; 3], to: [f32; 3], color: [f32; 4]) {
+        self.line(from, to, color);
+
+        let direction = Vector3::from(to) - Vector3::from(from);
+        let length = direction.magnitude();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        //任取一个不和方向平行的向量，得到两个垂直方向
+        let up = if direction.y.abs() < 0.99 { Vector3::unit_y() } else { Vector3::unit_x() };
+        let side = direction.cross(up).normalize();
+        let up = side.cross(direction);
+
+        let head = length * 0.2;
+        let base = Vector3::from(to) - direction * head;
+        for offset in [side, -side, up, -up] {
+            self.line(to, (base + offset * head * 0.4).into(), color);
+        }
+    }
+
+    //XZ 平面上以 center 为中心的正方形网格，size 是边长，divisions 是每边的格数
+    pub fn grid(&mut self, center: [f32; 3], size: f32, divisions: u32, color: [f32; 4]) {
+        let divisions = divisions.max(1);
+        let half = size / 2.0;
+        for i in 0..=divisions {
+            let offset = -half + size * i as f32 / divisions as f32;
+            self.line(
+                [center[0] + offset, center[1], center[2] - half],
+                [center[0] + offset, center[1], center[2] + half],
+                color,
+            );
+            self.line(
+                [center[0] - half, center[1], center[2] + offset],
+                [center[0] + half, center[1], center[2] + offset],
+                color,
+            );
+        }
+    }
+
+    //坐标轴，X 红色，Y 绿色，Z 蓝色
+    pub fn axes(&mut self, origin: [f32; 3], length: f32) {
+        let [x, y, z] = origin;
+        self.arrow(origin, [x + length, y, z], [1.0, 0.0, 0.0, 1.0]);
+        self.arrow(origin, [x, y + length, z], [0.0, 1.0, 0.0, 1.0]);
+        self.arrow(origin, [x, y, z + length], [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    //center + axis_a * cos + axis_b * sin
+    fn circle(&mut self, center: Vector3<f32>, axis_a: Vector3<f32>, axis_b: Vector3<f32>, color: [f32; 4]) {
+        let point = |i: usize| -> [f32; 3] {
+            let angle = TAU * i as f32 / SPHERE_SEGMENTS as f32;
+            (center + axis_a * angle.cos() + axis_b * angle.sin()).into()
+        };
+        for i in 0..SPHERE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+}
+
+//调试线条的管线和每帧的顶点缓冲区
+//顶点缓冲区和 uniform 缓冲区一样由环形分配器每帧分配，不需要等待上一帧的绘制完成
+pub struct DebugDrawRenderer {
+    pipeline: Arc<GraphicsPipeline>,
+    vertex_buffer_allocator: SubbufferAllocator,
+}
+
+impl DebugDrawRenderer {
+    //render_pass 需要和场景使用的相同，cache 使用 MeshPipelines 的管线缓存，随它一起保存
+    pub fn new(
+        render_pass: Arc<RenderPass>,
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        cache: Arc<PipelineCache>,
+    ) -> DebugDrawRenderer {
+        let device = render_pass.device().clone();
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        //线条被场景遮挡，但不写入深度，半透明的颜色和底下的场景混合
+        let mut depth_stencil_state = DepthStencilState::simple_depth_test();
+        if let Some(depth) = depth_stencil_state.depth.as_mut() {
+            depth.write_enable = StateMode::Fixed(false);
+        }
+
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(DebugVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new().topology(PrimitiveTopology::LineList))
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(1).blend_alpha())
+            .depth_stencil_state(depth_stencil_state)
+            .render_pass(Subpass::from(render_pass, 0).unwrap())
+            .build_with_cache(cache)
+            .build(device)
+            .unwrap();
+
+        let vertex_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator,
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::VERTEX_BUFFER,
+                memory_usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+        );
+
+        DebugDrawRenderer {
+            pipeline,
+            vertex_buffer_allocator,
+        }
+    }
+
+    //在渲染通道内、场景画完之后调用，视口沿用场景的动态视口
+    //screen_proj 把屏幕空间线段的逻辑像素坐标换算到 NDC，见 get_screen_projection
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        debug_draw: &DebugDraw,
+        view_proj: Matrix4<f32>,
+        screen_proj: Matrix4<f32>,
+    ) {
+        if debug_draw.is_empty() {
+            return;
+        }
+
+        let world = debug_draw.vertices();
+        let screen = debug_draw.screen_vertices();
+        let vertex_buffer = self
+            .vertex_buffer_allocator
+            .allocate_slice::<DebugVertex>((world.len() + screen.len()) as u64)
+            .unwrap();
+        {
+            let mut contents = vertex_buffer.write().unwrap();
+            contents[..world.len()].copy_from_slice(world);
+            contents[world.len()..].copy_from_slice(screen);
+        }
+
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_vertex_buffers(0, vertex_buffer);
+        //两组线段只是投影矩阵不同，用 first_vertex 选择缓冲区中的范围
+        for (matrix, first, count) in [(view_proj, 0, world.len()), (screen_proj, world.len(), screen.len())] {
+            if count == 0 {
+                continue;
+            }
+            builder
+                .push_constants(
+                    self.pipeline.layout().clone(),
+                    0,
+                    vs::PushConstants {
+                        view_proj: matrix.into(),
+                    },
+                )
+                .draw(count as u32, 1, first as u32, 0)
+                .unwrap();
+        }
+    }
+}
+
+//网格着色器 uniform 数据的投影矩阵 * 观察矩阵 * 世界矩阵，调试线条和场景使用相同的世界空间
+pub fn get_view_projection(uniforms: &mesh_glsl::vs::Data) -> Matrix4<f32> {
+    Matrix4::from(uniforms.proj) * Matrix4::from(uniforms.view) * Matrix4::from(uniforms.world)
+}
+
+//逻辑像素坐标到 NDC 的矩阵，viewport_size 是物理像素，Vulkan 的 NDC 中 Y 向下，不需要翻转
+pub fn get_screen_projection(viewport_size: [f32; 2], scale_factor: f32) -> Matrix4<f32> {
+    Matrix4::from_translation(Vector3::new(-1.0, -1.0, 0.0))
+        * Matrix4::from_nonuniform_scale(
+            2.0 * scale_factor / viewport_size[0],
+            2.0 * scale_factor / viewport_size[1],
+            1.0,
+        )
+}
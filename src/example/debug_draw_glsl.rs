@@ -0,0 +1,40 @@
+//调试线条着色器，顶点已经在世界空间中，只需要乘观察投影矩阵，颜色不加光照
+pub mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec4 color;
+
+            layout(location = 0) out vec4 v_color;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 view_proj;
+            } push;
+
+            void main() {
+                v_color = color;
+                gl_Position = push.view_proj * vec4(position, 1.0);
+            }
+        ",
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec4 v_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            void main() {
+                f_color = v_color;
+            }
+        ",
+    }
+}
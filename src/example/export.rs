@@ -0,0 +1,273 @@
+//动画导出：在一段时间内按固定时间步长逐帧渲染，保存为编号的PNG序列、Y4M 视频流或 GIF 动画
+//输出路径决定格式：
+//  目录（已存在或者没有扩展名）  frame_00000.png, frame_00001.png, ...
+//  *.y4m                         未压缩的 YUV4MPEG2（4:2:0），可以直接交给 ffmpeg 等编码器
+//  -                             Y4M 写到标准输出，例如 my_winit export ... - | ffmpeg -i - out.mp4
+//  *.gif                         循环播放的 GIF 动画
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use crate::example::fractal::FractalRenderer;
+use crate::example::render_mesh::{get_mesh_uniforms, RenderOptions, SceneRenderer};
+use crate::example::scene::load_scene;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExportSettings {
+    //开始和结束时间（秒），结束时间不包含在内
+    pub start: f32,
+    pub end: f32,
+    pub fps: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        ExportSettings {
+            start: 0.0,
+            end: 5.0,
+            fps: 30,
+            width: 640,
+            height: 480,
+        }
+    }
+}
+
+impl ExportSettings {
+    pub fn frame_count(&self) -> u32 {
+        (((self.end - self.start) * self.fps as f32).round() as u32).max(1)
+    }
+
+    //第 frame 帧的时间，用帧号计算而不是累加，长动画也不会有误差
+    pub fn frame_time(&self, frame: u32) -> f32 {
+        self.start + frame as f32 / self.fps as f32
+    }
+}
+
+enum FrameWriter {
+    PngSequence(PathBuf),
+    Y4m(BufWriter<Box<dyn Write>>),
+    Gif(GifEncoder<BufWriter<File>>),
+}
+
+impl FrameWriter {
+    fn create(output: &Path, settings: &ExportSettings) -> Result<FrameWriter, String> {
+        let extension = output
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+
+        let writer = match extension.as_deref() {
+            _ if output == Path::new("-") => {
+                let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(Box::new(io::stdout()));
+                write_y4m_header(&mut writer, settings).map_err(|e| format!("failed to write to stdout: {e}"))?;
+                FrameWriter::Y4m(writer)
+            }
+            Some("y4m") => {
+                let file = File::create(output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+                let mut writer: BufWriter<Box<dyn Write>> = BufWriter::new(Box::new(file));
+                write_y4m_header(&mut writer, settings).map_err(|e| format!("failed to write {}: {e}", output.display()))?;
+                FrameWriter::Y4m(writer)
+            }
+            Some("gif") => {
+                let file = File::create(output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+                //speed 是颜色量化的速度，1 最慢质量最好，30 最快
+                let mut encoder = GifEncoder::new_with_speed(BufWriter::new(file), 10);
+                encoder.set_repeat(Repeat::Infinite).map_err(|e| format!("failed to write {}: {e}", output.display()))?;
+                FrameWriter::Gif(encoder)
+            }
+            None => {
+                fs::create_dir_all(output).map_err(|e| format!("failed to create {}: {e}", output.display()))?;
+                FrameWriter::PngSequence(output.to_path_buf())
+            }
+            Some(_) if output.is_dir() => FrameWriter::PngSequence(output.to_path_buf()),
+            Some(extension) => return Err(format!("unsupported export format: .{extension}")),
+        };
+        Ok(writer)
+    }
+
+    fn write(&mut self, index: u32, image: RgbaImage, settings: &ExportSettings) -> Result<(), String> {
+        match self {
+            FrameWriter::PngSequence(dir) => {
+                let path = dir.join(format!("frame_{index:05}.png"));
+                image.save(&path).map_err(|e| format!("failed to save {}: {e}", path.display()))
+            }
+            FrameWriter::Y4m(writer) => write_y4m_frame(writer, &image).map_err(|e| format!("failed to write frame {index}: {e}")),
+            FrameWriter::Gif(encoder) => {
+                //GIF 的帧延迟以10毫秒为单位，帧率不是100的约数时会有舍入
+                let delay = Delay::from_numer_denom_ms(1000, settings.fps);
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                    .map_err(|e| format!("failed to encode frame {index}: {e}"))
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            FrameWriter::PngSequence(_) => Ok(()),
+            FrameWriter::Y4m(mut writer) => writer.flush().map_err(|e| e.to_string()),
+            //GifEncoder 在 drop 时写入文件尾
+            FrameWriter::Gif(encoder) => {
+                drop(encoder);
+                Ok(())
+            }
+        }
+    }
+}
+
+//按设置逐帧调用 render(时间) 并写入 output，render 返回的图像大小必须是 width x height
+pub fn export_frames(output: &Path, settings: &ExportSettings, mut render: impl FnMut(f32) -> RgbaImage) -> Result<(), String> {
+    let mut writer = FrameWriter::create(output, settings)?;
+    let frame_count = settings.frame_count();
+
+    for index in 0..frame_count {
+        let image = render(settings.frame_time(index));
+        if image.dimensions() != (settings.width, settings.height) {
+            return Err(format!(
+                "frame {index} is {}x{}, expected {}x{}",
+                image.width(),
+                image.height(),
+                settings.width,
+                settings.height,
+            ));
+        }
+        writer.write(index, image, settings)?;
+        //标准输出可能是视频流，进度写到标准错误
+        eprint!("\rexported {}/{frame_count} frames", index + 1);
+    }
+    eprintln!();
+
+    writer.finish()
+}
+
+//模型绕Y轴旋转的动画，和 render-mesh 窗口中的自动旋转相同
+pub fn export_scene(model: &Path, output: &Path, settings: &ExportSettings, options: &RenderOptions) -> Result<(), String> {
+    let scene = load_scene(model)?;
+    let mut renderer = SceneRenderer::new(&scene, settings.width, settings.height);
+    let result = export_frames(output, settings, |time| {
+        let uniforms = get_mesh_uniforms(renderer.bounds(), renderer.aspect_ratio(), time, options);
+        renderer.render(uniforms, options)
+    });
+    renderer.save_cache();
+    result
+}
+
+//Mandelbrot 缩放动画，画面每秒放大 zoom_per_second 倍
+//着色器使用单精度浮点，放大到 10^5 倍左右以后会出现块状
+pub fn export_fractal(output: &Path, settings: &ExportSettings, center: [f32; 2], zoom_per_second: f32) -> Result<(), String> {
+    let renderer = FractalRenderer::new(settings.width, settings.height);
+    export_frames(output, settings, |time| {
+        renderer.render(center, 3.0 / zoom_per_second.powf(time))
+    })
+}
+
+//C420jpeg：4:2:0 色度采样，全范围 BT.601，和 JPEG 使用的 YCbCr 相同
+fn write_y4m_header(writer: &mut impl Write, settings: &ExportSettings) -> io::Result<()> {
+    writeln!(
+        writer,
+        "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C420jpeg",
+        settings.width, settings.height, settings.fps,
+    )
+}
+
+fn write_y4m_frame(writer: &mut impl Write, image: &RgbaImage) -> io::Result<()> {
+    let (width, height) = image.dimensions();
+    let chroma_width = (width + 1) / 2;
+    let chroma_height = (height + 1) / 2;
+
+    let mut y_plane = Vec::with_capacity((width * height) as usize);
+    for pixel in image.pixels() {
+        let [r, g, b, _] = pixel.0.map(|value| value as f32);
+        y_plane.push((0.299 * r + 0.587 * g + 0.114 * b).round() as u8);
+    }
+
+    //色度取 2x2 像素的平均值，宽高是奇数时最后一行/列只有一个像素
+    let mut u_plane = Vec::with_capacity((chroma_width * chroma_height) as usize);
+    let mut v_plane = Vec::with_capacity((chroma_width * chroma_height) as usize);
+    for cy in 0..chroma_height {
+        for cx in 0..chroma_width {
+            let mut sum = [0.0f32; 3];
+            let mut count = 0.0;
+            for y in cy * 2..(cy * 2 + 2).min(height) {
+                for x in cx * 2..(cx * 2 + 2).min(width) {
+                    for (total, value) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+                        *total += value as f32;
+                    }
+                    count += 1.0;
+                }
+            }
+            let [r, g, b] = sum.map(|value| value / count);
+            u_plane.push((128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b).round().clamp(0.0, 255.0) as u8);
+            v_plane.push((128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b).round().clamp(0.0, 255.0) as u8);
+        }
+    }
+
+    writer.write_all(b"FRAME\n")?;
+    writer.write_all(&y_plane)?;
+    writer.write_all(&u_plane)?;
+    writer.write_all(&v_plane)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+    use super::*;
+
+    fn settings(start: f32, end: f32, fps: u32) -> ExportSettings {
+        ExportSettings { start, end, fps, ..Default::default() }
+    }
+
+    #[test]
+    fn y4m_frame_layout_with_odd_size() {
+        const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+        const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+        const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+        const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+        #[rustfmt::skip]
+        let pixels = [
+            RED,   BLACK, BLUE,
+            BLACK, RED,   BLUE,
+            GREEN, GREEN, BLACK,
+        ];
+        let image = RgbaImage::from_fn(3, 3, |x, y| pixels[(y * 3 + x) as usize]);
+
+        let mut bytes = Vec::new();
+        write_y4m_frame(&mut bytes, &image).unwrap();
+
+        //FRAME 头 + 3x3 的亮度 + 两个 2x2 的色度平面
+        assert_eq!(bytes.len(), 6 + 9 + 4 + 4);
+        assert_eq!(&bytes[..6], b"FRAME\n");
+        assert_eq!(bytes[6..15], [76, 0, 29, 0, 76, 29, 150, 150, 0]);
+        //左上角是 2x2 的平均值，右边一列和下面一行只有两个像素，右下角只有一个像素
+        assert_eq!(bytes[15..19], [106, 255, 44, 128]);
+        assert_eq!(bytes[19..23], [192, 107, 21, 128]);
+    }
+
+    #[test]
+    fn frame_count_rounds_fractional_ranges() {
+        assert_eq!(settings(0.0, 5.0, 30).frame_count(), 150);
+        //0.75 秒 * 30 = 22.5 帧
+        assert_eq!(settings(0.5, 1.25, 30).frame_count(), 23);
+        assert_eq!(settings(0.0, 0.1, 24).frame_count(), 2);
+        //不到半帧或者结束时间在开始时间之前时也导出一帧
+        assert_eq!(settings(0.0, 0.01, 30).frame_count(), 1);
+        assert_eq!(settings(2.0, 1.0, 30).frame_count(), 1);
+    }
+
+    #[test]
+    fn frame_time_starts_at_start_and_stays_before_end() {
+        let settings = settings(0.5, 1.25, 30);
+        let count = settings.frame_count();
+        assert_eq!(settings.frame_time(0), 0.5);
+        assert!((settings.frame_time(3) - 0.6).abs() < 1e-6);
+        assert!((settings.frame_time(count - 1) - (0.5 + 22.0 / 30.0)).abs() < 1e-6);
+        assert!(settings.frame_time(count - 1) < settings.end);
+
+        //用帧号计算时间，很多帧以后也没有累加误差
+        let long = ExportSettings { start: 0.0, end: 3600.0, fps: 60, ..Default::default() };
+        assert_eq!(long.frame_time(60 * 3000), 3000.0);
+    }
+}
@@ -0,0 +1,247 @@
+//Mandelbrot 缩放动画的渲染器，HdrFractalRenderer 是输出浮点颜色的版本
+//和 operator_image_shader 一样用计算着色器写入存储图像再复制到缓冲区，设备和管线只创建一次
+use std::sync::Arc;
+use image::RgbaImage;
+use vulkano::buffer::{BufferUsage, Subbuffer};
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::memory::allocator::MemoryUsage;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
+use crate::example::fractal_glsl::{hdr_shader, shader};
+use crate::example::hdr::{FormatConverter, ReadbackFormat, ReadbackImage};
+use crate::example::pipeline_cache::SharedPipelineCache;
+
+//缩放动画默认的中心，海马谷附近有足够多的细节
+pub const DEFAULT_ZOOM_CENTER: [f32; 2] = [-0.743_643_9, 0.131_825_9];
+
+pub struct FractalRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    pipeline: Arc<ComputePipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    image: Arc<StorageImage>,
+    buffer: Subbuffer<[u8]>,
+    width: u32,
+    height: u32,
+}
+
+impl FractalRenderer {
+    pub fn new(width: u32, height: u32) -> FractalRenderer {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+        let memory_allocator = Arc::new(create_memory_allocator(device.clone()));
+
+        let image = StorageImage::new(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(queue.queue_family_index()),
+        ).unwrap();
+        let view = ImageView::new_default(image.clone()).unwrap();
+
+        let shader = shader::load(device.clone()).expect("failed to create shader module");
+        let pipeline_cache = SharedPipelineCache::new(device.clone());
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+        let set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [WriteDescriptorSet::image_view(0, view)],
+        ).unwrap();
+
+        let buffer = create_iter_buffer(
+            memory_allocator,
+            BufferUsage::TRANSFER_DST,
+            MemoryUsage::Download,
+            (0..width * height * 4).map(|_| 0u8),
+        );
+
+        FractalRenderer {
+            device: device.clone(),
+            queue,
+            command_buffer_allocator: create_buffer_allocator(device),
+            pipeline,
+            set,
+            image,
+            buffer,
+            width,
+            height,
+        }
+    }
+
+    //center 是画面中心，scale 是画面高度对应的复平面范围
+    pub fn render(&self, center: [f32; 2], scale: f32) -> RgbaImage {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                shader::PushConstants {
+                    center,
+                    scale,
+                    max_iterations: max_iterations(scale),
+                },
+            )
+            .dispatch([(self.width + 7) / 8, (self.height + 7) / 8, 1])
+            .unwrap()
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(self.image.clone(), self.buffer.clone()))
+            .unwrap();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let content = self.buffer.read().unwrap();
+        RgbaImage::from_raw(self.width, self.height, content.to_vec()).unwrap()
+    }
+}
+
+//HDR 版本的渲染器：在 rgba32f 存储图像中计算线性颜色，读回时用 FormatConverter 转换成要保存的格式
+pub struct HdrFractalRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    pipeline: Arc<ComputePipeline>,
+    set: Arc<PersistentDescriptorSet>,
+    view: Arc<ImageView<StorageImage>>,
+    converter: FormatConverter,
+    width: u32,
+    height: u32,
+}
+
+impl HdrFractalRenderer {
+    pub fn new(width: u32, height: u32) -> HdrFractalRenderer {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+        let memory_allocator = Arc::new(create_memory_allocator(device.clone()));
+
+        let image = StorageImage::new(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R32G32B32A32_SFLOAT,
+            Some(queue.queue_family_index()),
+        ).unwrap();
+        let view = ImageView::new_default(image).unwrap();
+
+        let shader = hdr_shader::load(device.clone()).expect("failed to create shader module");
+        let pipeline_cache = SharedPipelineCache::new(device.clone());
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+        let converter = FormatConverter::new(device.clone(), memory_allocator, pipeline_cache.cache());
+
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+        let set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [WriteDescriptorSet::image_view(0, view.clone())],
+        ).unwrap();
+
+        HdrFractalRenderer {
+            device: device.clone(),
+            queue,
+            command_buffer_allocator: create_buffer_allocator(device),
+            pipeline,
+            set,
+            view,
+            converter,
+            width,
+            height,
+        }
+    }
+
+    //和 FractalRenderer::render 相同的画面，颜色先乘以 exposure 再转换成 format
+    pub fn render(&self, center: [f32; 2], scale: f32, format: ReadbackFormat, exposure: f32) -> ReadbackImage {
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                self.pipeline.layout().clone(),
+                0,
+                self.set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                hdr_shader::PushConstants {
+                    center,
+                    scale,
+                    max_iterations: max_iterations(scale),
+                },
+            )
+            .dispatch([(self.width + 7) / 8, (self.height + 7) / 8, 1])
+            .unwrap();
+        let buffer = self.converter.record(&mut builder, self.view.clone(), format, exposure);
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let content = buffer.read().unwrap();
+        ReadbackImage::decode(&content, self.width, self.height, format)
+    }
+}
+
+//放得越大需要越多的迭代才能看出边界的细节
+fn max_iterations(scale: f32) -> u32 {
+    (200.0 + 50.0 * (3.0 / scale).log2().max(0.0)) as u32
+}
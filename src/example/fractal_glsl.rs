@@ -0,0 +1,100 @@
+//Mandelbrot 计算着色器，和 image_glsl 相同，但中心、缩放和迭代次数通过推送常量传入，用于生成缩放动画
+pub mod shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform writeonly image2D img;
+
+            layout(push_constant) uniform PushConstants {
+                //画面中心在复平面上的位置
+                vec2 center;
+                //画面高度对应的复平面范围
+                float scale;
+                uint max_iterations;
+            } push;
+
+            void main() {
+                vec2 size = vec2(imageSize(img));
+                vec2 offset = (gl_GlobalInvocationID.xy + vec2(0.5) - size / 2.0) / size.y;
+                vec2 c = push.center + offset * push.scale;
+
+                vec2 z = vec2(0.0, 0.0);
+                uint i;
+                for (i = 0; i < push.max_iterations; i++) {
+                    z = vec2(
+                        z.x * z.x - z.y * z.y + c.x,
+                        z.y * z.x + z.x * z.y + c.y
+                    );
+
+                    if (dot(z, z) > 256.0) {
+                        break;
+                    }
+                }
+
+                vec4 to_write = vec4(0.0, 0.0, 0.0, 1.0);
+                if (i < push.max_iterations) {
+                    //连续的迭代次数，避免颜色出现一圈一圈的色带
+                    float n = float(i) + 1.0 - log2(log2(dot(z, z)) / 2.0);
+                    float t = n / 64.0;
+                    to_write.rgb = 0.5 + 0.5 * cos(6.28318 * (t + vec3(0.0, 0.1, 0.2)));
+                }
+                imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+            }
+        ",
+    }
+}
+
+//HDR 版本：写入 rgba32f 存储图像，颜色不量化，并转换成线性颜色，和其他浮点图像一样保存为 EXR/HDR
+pub mod hdr_shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform writeonly image2D img;
+
+            layout(push_constant) uniform PushConstants {
+                vec2 center;
+                float scale;
+                uint max_iterations;
+            } push;
+
+            vec3 srgb_to_linear(vec3 c) {
+                return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), greaterThan(c, vec3(0.04045)));
+            }
+
+            void main() {
+                vec2 size = vec2(imageSize(img));
+                vec2 offset = (gl_GlobalInvocationID.xy + vec2(0.5) - size / 2.0) / size.y;
+                vec2 c = push.center + offset * push.scale;
+
+                vec2 z = vec2(0.0, 0.0);
+                uint i;
+                for (i = 0; i < push.max_iterations; i++) {
+                    z = vec2(
+                        z.x * z.x - z.y * z.y + c.x,
+                        z.y * z.x + z.x * z.y + c.y
+                    );
+
+                    if (dot(z, z) > 256.0) {
+                        break;
+                    }
+                }
+
+                vec4 to_write = vec4(0.0, 0.0, 0.0, 1.0);
+                if (i < push.max_iterations) {
+                    float n = float(i) + 1.0 - log2(log2(dot(z, z)) / 2.0);
+                    float t = n / 64.0;
+                    to_write.rgb = srgb_to_linear(0.5 + 0.5 * cos(6.28318 * (t + vec3(0.0, 0.1, 0.2))));
+                }
+                imageStore(img, ivec2(gl_GlobalInvocationID.xy), to_write);
+            }
+        ",
+    }
+}
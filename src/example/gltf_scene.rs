@@ -0,0 +1,295 @@
+//glTF 2.0 场景导入
+//支持 .gltf（外部或内嵌的 buffer/贴图）和 .glb，只读取本地文件
+use std::path::Path;
+use std::sync::Arc;
+use cgmath::Matrix4;
+use gltf::image::Format as GltfFormat;
+use gltf::material::AlphaMode;
+use gltf::mesh::Mode;
+use image::RgbaImage;
+use crate::example::mesh::{compute_normals, Material, Mesh, MeshGroup, MeshVertex, WHITE};
+use crate::example::pipeline_desc::BlendMode;
+use crate::example::scene::{Scene, SceneNode};
+
+pub fn load_gltf(path: &Path) -> Result<Scene, String> {
+    //import 会按相对路径读取外部文件并解码 data URI，不会访问网络
+    let (document, buffers, images) =
+        gltf::import(path).map_err(|e| format!("failed to load {}: {e}", path.display()))?;
+
+    //贴图按 image 索引转换一次，多个材质引用同一张图时共享
+    let images: Vec<Option<Arc<RgbaImage>>> = images.into_iter().map(convert_image).collect();
+    let texture_image = |texture: gltf::Texture| images[texture.source().index()].clone();
+
+    let mut scene = Scene::default();
+
+    for material in document.materials() {
+        let pbr = material.pbr_metallic_roughness();
+        let emissive = material.emissive_factor();
+        scene.materials.push(Material {
+            name: material.name().unwrap_or_default().to_string(),
+            base_color: pbr.base_color_factor(),
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive,
+            base_color_texture: pbr.base_color_texture().and_then(|info| texture_image(info.texture())),
+            metallic_roughness_texture: pbr
+                .metallic_roughness_texture()
+                .and_then(|info| texture_image(info.texture())),
+            //MASK 需要在着色器中按 alphaCutoff 丢弃片段，这里当作不透明处理
+            blend: match material.alpha_mode() {
+                AlphaMode::Blend => BlendMode::Alpha,
+                AlphaMode::Opaque | AlphaMode::Mask => BlendMode::Opaque,
+            },
+        });
+    }
+    //没有指定材质的图元使用规范中的默认材质：白色、金属度1、粗糙度1
+    let default_material = scene.materials.len();
+    scene.materials.push(Material {
+        base_color: [1.0; 4],
+        metallic: 1.0,
+        roughness: 1.0,
+        ..Default::default()
+    });
+
+    //glTF 的 mesh 由多个图元组成，每个图元对应一个 MeshGroup
+    for gltf_mesh in document.meshes() {
+        let mut mesh = Mesh::default();
+
+        for primitive in gltf_mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                println!(
+                    "skipping primitive of mesh {:?}: mode {:?} is not supported",
+                    gltf_mesh.name(),
+                    primitive.mode()
+                );
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                Some(positions) => positions.collect(),
+                None => continue,
+            };
+            let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+            let uvs: Option<Vec<[f32; 2]>> = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().collect());
+            let colors: Option<Vec<[f32; 4]>> = reader
+                .read_colors(0)
+                .map(|colors| colors.into_rgba_f32().collect());
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            //损坏的文件中属性数量或者索引可能超出顶点数量，计算法线和合并索引时会越界
+            let attribute_counts = [normals.as_ref().map(Vec::len), uvs.as_ref().map(Vec::len), colors.as_ref().map(Vec::len)];
+            if attribute_counts.into_iter().flatten().any(|count| count != positions.len()) {
+                return Err(format!(
+                    "failed to load {}: mesh {:?} has attributes with different vertex counts",
+                    path.display(),
+                    gltf_mesh.name().unwrap_or_default(),
+                ));
+            }
+            if let Some(index) = indices.iter().find(|index| **index as usize >= positions.len()) {
+                return Err(format!(
+                    "failed to load {}: mesh {:?} references vertex {index}, but the primitive has only {} vertices",
+                    path.display(),
+                    gltf_mesh.name().unwrap_or_default(),
+                    positions.len(),
+                ));
+            }
+
+            let base_vertex = mesh.vertices.len() as u32;
+            for (i, position) in positions.iter().enumerate() {
+                mesh.vertices.push(MeshVertex {
+                    position: *position,
+                    normal: normals.as_ref().map_or([0.0; 3], |normals| normals[i]),
+                    uv: uvs.as_ref().map_or([0.0; 2], |uvs| uvs[i]),
+                    color: colors.as_ref().map_or(WHITE, |colors| colors[i]),
+                });
+            }
+            if normals.is_none() {
+                compute_normals(&mut mesh.vertices[base_vertex as usize..], &indices);
+            }
+
+            let first_index = mesh.indices.len() as u32;
+            mesh.indices.extend(indices.iter().map(|index| base_vertex + index));
+            mesh.groups.push(MeshGroup {
+                name: gltf_mesh.name().unwrap_or_default().to_string(),
+                first_index,
+                index_count: indices.len() as u32,
+                material: primitive.material().index().unwrap_or(default_material),
+            });
+        }
+
+        scene.meshes.push(mesh);
+    }
+
+    for node in document.nodes() {
+        scene.nodes.push(SceneNode {
+            name: node.name().unwrap_or_default().to_string(),
+            //matrix() 会把 TRS 形式的变换也转换成列主序矩阵
+            transform: Matrix4::from(node.transform().matrix()),
+            children: node.children().map(|child| child.index()).collect(),
+            mesh: node.mesh().map(|mesh| mesh.index()),
+        });
+    }
+
+    //优先使用默认场景，文件中没有场景时，把所有不是其他节点子节点的节点作为根节点
+    match document.default_scene().or_else(|| document.scenes().next()) {
+        Some(gltf_scene) => scene.roots = gltf_scene.nodes().map(|node| node.index()).collect(),
+        None => {
+            let mut is_child = vec![false; scene.nodes.len()];
+            for node in &scene.nodes {
+                for child in &node.children {
+                    is_child[*child] = true;
+                }
+            }
+            scene.roots = (0..scene.nodes.len()).filter(|i| !is_child[*i]).collect();
+        }
+    }
+
+    if scene.draws().is_empty() {
+        return Err(format!("{} contains no renderable meshes", path.display()));
+    }
+
+    Ok(scene)
+}
+
+//把 glTF 解码出的图片统一转换成 RGBA8
+fn convert_image(data: gltf::image::Data) -> Option<Arc<RgbaImage>> {
+    let pixel_count = (data.width * data.height) as usize;
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+
+    match data.format {
+        GltfFormat::R8 => data.pixels.iter().for_each(|r| rgba.extend([*r, *r, *r, 255])),
+        GltfFormat::R8G8 => data.pixels.chunks_exact(2).for_each(|p| rgba.extend([p[0], p[1], 0, 255])),
+        GltfFormat::R8G8B8 => data.pixels.chunks_exact(3).for_each(|p| rgba.extend([p[0], p[1], p[2], 255])),
+        GltfFormat::R8G8B8A8 => rgba = data.pixels,
+        //16位的图片只保留高8位（小端序，每个分量的第二个字节）
+        GltfFormat::R16 => data.pixels.chunks_exact(2).for_each(|p| rgba.extend([p[1], p[1], p[1], 255])),
+        GltfFormat::R16G16 => data.pixels.chunks_exact(4).for_each(|p| rgba.extend([p[1], p[3], 0, 255])),
+        GltfFormat::R16G16B16 => data.pixels.chunks_exact(6).for_each(|p| rgba.extend([p[1], p[3], p[5], 255])),
+        GltfFormat::R16G16B16A16 => data.pixels.chunks_exact(8).for_each(|p| rgba.extend([p[1], p[3], p[5], p[7]])),
+        format => {
+            println!("unsupported glTF image format {format:?}");
+            return None;
+        }
+    }
+
+    RgbaImage::from_raw(data.width, data.height, rgba).map(Arc::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use cgmath::Matrix4;
+    use super::*;
+
+    fn asset_path(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets").join(name)
+    }
+
+    fn load_sample() -> Scene {
+        load_gltf(&asset_path("hierarchy.gltf")).unwrap()
+    }
+
+    fn assert_matrix_eq(actual: Matrix4<f32>, expected: Matrix4<f32>) {
+        let actual: &[[f32; 4]; 4] = actual.as_ref();
+        let expected: &[[f32; 4]; 4] = expected.as_ref();
+        for (a, e) in actual.iter().flatten().zip(expected.iter().flatten()) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn loads_node_hierarchy() {
+        let scene = load_sample();
+        assert_eq!(scene.nodes.len(), 2);
+        assert_eq!(scene.roots, vec![0]);
+        assert_eq!(scene.nodes[0].name, "parent");
+        assert_eq!(scene.nodes[0].children, vec![1]);
+        assert_eq!(scene.nodes[0].mesh, Some(0));
+        assert_eq!(scene.nodes[1].name, "child");
+        assert!(scene.nodes[1].children.is_empty());
+        assert_eq!(scene.nodes[1].mesh, Some(1));
+    }
+
+    #[test]
+    fn composes_parent_and_child_transforms() {
+        let scene = load_sample();
+        let parent = Matrix4::from_translation([-0.6, 0.0, 0.0].into());
+        assert_matrix_eq(scene.nodes[0].transform, parent);
+
+        //子节点的 TRS：平移 (1.2, 0, 0)，绕 y 轴旋转 45 度，缩放 0.8
+        let r = 0.8 * std::f32::consts::FRAC_1_SQRT_2;
+        let child = Matrix4::new(
+            r, 0.0, -r, 0.0,
+            0.0, 0.8, 0.0, 0.0,
+            r, 0.0, r, 0.0,
+            1.2, 0.0, 0.0, 1.0,
+        );
+        assert_matrix_eq(scene.nodes[1].transform, child);
+
+        let mut draws = scene.draws();
+        draws.sort_by_key(|(mesh, _)| *mesh);
+        assert_eq!(draws.len(), 2);
+        assert_matrix_eq(draws[0].1, parent);
+        assert_matrix_eq(draws[1].1, parent * child);
+    }
+
+    #[test]
+    fn maps_primitives_to_mesh_groups() {
+        let scene = load_sample();
+        assert_eq!(scene.meshes.len(), 2);
+
+        let quad = &scene.meshes[0];
+        assert_eq!(quad.vertices.len(), 4);
+        assert_eq!(quad.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(quad.groups.len(), 1);
+        assert_eq!((quad.groups[0].first_index, quad.groups[0].index_count, quad.groups[0].material), (0, 6, 0));
+        assert_eq!(quad.groups[0].name, "quad");
+
+        //每个图元的顶点追加到同一个网格，索引加上图元的第一个顶点
+        let panes = &scene.meshes[1];
+        assert_eq!(panes.vertices.len(), 8);
+        assert_eq!(panes.indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]);
+        assert_eq!(panes.groups.len(), 2);
+        assert_eq!((panes.groups[0].first_index, panes.groups[0].index_count, panes.groups[0].material), (0, 6, 1));
+        //没有材质的图元使用追加在最后的默认材质
+        assert_eq!((panes.groups[1].first_index, panes.groups[1].index_count, panes.groups[1].material), (6, 6, 2));
+    }
+
+    #[test]
+    fn reads_material_factors_and_blend_modes() {
+        let scene = load_sample();
+        assert_eq!(scene.materials.len(), 3);
+
+        let gold = &scene.materials[0];
+        assert_eq!(gold.name, "gold");
+        assert_eq!(gold.base_color, [1.0, 0.766, 0.336, 1.0]);
+        assert_eq!((gold.metallic, gold.roughness), (1.0, 0.35));
+        assert_eq!(gold.emissive, [0.0; 3]);
+        assert_eq!(gold.blend, BlendMode::Opaque);
+        assert!(gold.base_color_texture.is_none());
+
+        let glass = &scene.materials[1];
+        assert_eq!(glass.name, "glass");
+        assert_eq!(glass.base_color, [0.6, 0.8, 1.0, 0.4]);
+        assert_eq!((glass.metallic, glass.roughness), (0.0, 0.1));
+        assert_eq!(glass.emissive, [0.0, 0.1, 0.2]);
+        assert_eq!(glass.blend, BlendMode::Alpha);
+
+        let default = &scene.materials[2];
+        assert_eq!(default.base_color, [1.0; 4]);
+        assert_eq!((default.metallic, default.roughness), (1.0, 1.0));
+        assert_eq!(default.blend, BlendMode::Opaque);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        //三个顶点的三角形，索引是 0, 1, 5
+        let error = load_gltf(&asset_path("invalid_index.gltf")).unwrap_err();
+        assert!(error.ends_with("mesh \"broken\" references vertex 5, but the primitive has only 3 vertices"), "{error}");
+    }
+}
@@ -0,0 +1,300 @@
+//golden 图像回归测试
+//每个离屏示例在当前可用的设备上（包括 lavapipe 这样的软件实现）渲染一次，和保存的 golden 图像逐像素比较：
+//通道差超过 tolerance 的像素算作不同，不同像素的百分比超过 max_percent 时失败，
+//失败时把实际画面和差异图写到 output_dir，差异图中不同的像素为红色，其余像素是变暗的 golden 图像，另外报告 PSNR、SSIM 和类 FLIP 误差并保存 FLIP 热力图
+//没有 Vulkan 驱动（ICD）时所有用例都跳过，可以用 VK_ICD_FILENAMES 指定 lavapipe 的 ICD 文件
+//有软件光栅化参考实现的用例，GPU 的输出还要和 CPU 渲染的参考图像比较
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use image::{Rgba, RgbaImage};
+use crate::example::buffer::find_vulkan_device;
+use crate::example::graphics_pipeline::render_vertex;
+use crate::example::image_diff::{diff_images, HeatmapMetric};
+use crate::example::image_shader::render_image_shader;
+use crate::example::images::render_image;
+use crate::example::software::{render_image_software, render_vertex_software};
+
+//一个示例和它的 golden 图像，路径相对于 golden 目录
+pub struct GoldenCase {
+    pub name: &'static str,
+    pub golden: &'static str,
+    pub render: fn() -> RgbaImage,
+    //软件光栅化画出的参考图像，GPU 的输出还要和它比较，避免 golden 图像本身是错误的输出
+    pub reference: Option<fn() -> RgbaImage>,
+}
+
+//所有离屏渲染的示例，golden 图像就是示例自己保存并提交到仓库的文件
+//新的用例需要先在有 Vulkan 设备的机器上用 golden --update 生成 golden 图像并提交，否则用例失败
+pub fn golden_cases() -> Vec<GoldenCase> {
+    vec![
+        GoldenCase { name: "image", golden: "image.png", render: render_image, reference: Some(render_image_software) },
+        GoldenCase { name: "image_shader", golden: "image_shader.png", render: render_image_shader, reference: None },
+        GoldenCase { name: "image_vertex", golden: "image_vertex.png", render: render_vertex, reference: Some(render_vertex_software) },
+    ]
+}
+
+#[derive(Clone, Debug)]
+pub struct GoldenSettings {
+    pub golden_dir: PathBuf,
+    //失败时写入实际画面和差异图的目录
+    pub output_dir: PathBuf,
+    //每个通道允许的差
+    pub tolerance: u8,
+    //允许不同的像素百分比
+    pub max_percent: f64,
+    //用渲染结果覆盖 golden 图像，而不是比较
+    pub update: bool,
+    //只运行这些名字的用例，为空时运行全部
+    pub only: Vec<String>,
+}
+
+impl Default for GoldenSettings {
+    fn default() -> Self {
+        GoldenSettings {
+            golden_dir: PathBuf::from("."),
+            output_dir: PathBuf::from("golden-diff"),
+            tolerance: 2,
+            max_percent: 0.1,
+            update: false,
+            only: Vec::new(),
+        }
+    }
+}
+
+//两张同样大小的图像的比较结果
+#[derive(Clone, Debug)]
+pub struct ImageComparison {
+    pub differing_pixels: u64,
+    pub total_pixels: u64,
+    //所有像素所有通道中最大的差
+    pub max_difference: u8,
+    pub diff: RgbaImage,
+}
+
+impl ImageComparison {
+    pub fn differing_percent(&self) -> f64 {
+        if self.total_pixels == 0 {
+            0.0
+        } else {
+            self.differing_pixels as f64 * 100.0 / self.total_pixels as f64
+        }
+    }
+}
+
+//大小不同时返回 Err
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> Result<ImageComparison, String> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(format!(
+            "image size {}x{} does not match the golden image {}x{}",
+            actual.width(),
+            actual.height(),
+            expected.width(),
+            expected.height(),
+        ));
+    }
+
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    for ((a, e), d) in actual.pixels().zip(expected.pixels()).zip(diff.pixels_mut()) {
+        let difference = (0..4).map(|c| a[c].abs_diff(e[c])).max().unwrap();
+        max_difference = max_difference.max(difference);
+        *d = if difference > tolerance {
+            differing_pixels += 1;
+            Rgba([128 + difference / 2, 0, 0, 255])
+        } else {
+            let luma = ((e[0] as u32 * 299 + e[1] as u32 * 587 + e[2] as u32 * 114) / 3000) as u8;
+            Rgba([luma, luma, luma, 255])
+        };
+    }
+
+    Ok(ImageComparison {
+        differing_pixels,
+        total_pixels: actual.width() as u64 * actual.height() as u64,
+        max_difference,
+        diff,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GoldenOutcome {
+    Passed,
+    Failed(String),
+    //golden 图像还不存在，算作失败，用 --update 创建
+    Missing,
+    Updated,
+}
+
+//运行所有选中的用例并打印结果，有用例失败或缺少 golden 图像时返回 false
+//没有可用的 Vulkan 设备时打印原因，跳过所有用例并返回 true
+pub fn run_golden_tests(settings: &GoldenSettings) -> bool {
+    let device = match find_vulkan_device() {
+        Ok(device) => device,
+        Err(e) => {
+            println!("skipping golden image tests: {e}");
+            return true;
+        }
+    };
+    println!("running golden image tests on {device}");
+
+    let mut failed = 0;
+    let mut count = 0;
+    for case in golden_cases() {
+        if !settings.only.is_empty() && !settings.only.iter().any(|name| name == case.name) {
+            continue;
+        }
+        count += 1;
+        let outcome = run_golden_case(&case, settings);
+        match &outcome {
+            GoldenOutcome::Passed => println!("{}: ok", case.name),
+            GoldenOutcome::Failed(reason) => {
+                failed += 1;
+                println!("{}: FAILED, {reason}", case.name);
+            }
+            GoldenOutcome::Missing => {
+                failed += 1;
+                println!(
+                    "{}: FAILED, {} does not exist, run golden --update to create it",
+                    case.name,
+                    settings.golden_dir.join(case.golden).display(),
+                );
+            }
+            GoldenOutcome::Updated => println!("{}: updated {}", case.name, settings.golden_dir.join(case.golden).display()),
+        }
+    }
+    println!("{failed} of {count} golden image tests failed");
+    failed == 0
+}
+
+pub fn run_golden_case(case: &GoldenCase, settings: &GoldenSettings) -> GoldenOutcome {
+    let golden_path = settings.golden_dir.join(case.golden);
+    if !settings.update && !golden_path.exists() {
+        return GoldenOutcome::Missing;
+    }
+
+    //示例出错时会 panic，当作这个用例失败，继续运行其他用例
+    let actual = match panic::catch_unwind(case.render) {
+        Ok(image) => image,
+        Err(_) => return GoldenOutcome::Failed("rendering panicked".to_string()),
+    };
+
+    //参考图像在 update 时也检查，不把错误的输出保存成 golden 图像
+    if let Some(reference) = case.reference {
+        if let Err(reason) = compare_with_reference(case, &actual, &reference(), settings) {
+            return GoldenOutcome::Failed(reason);
+        }
+    }
+
+    if settings.update {
+        return match save_image(&actual, &golden_path) {
+            Ok(()) => GoldenOutcome::Updated,
+            Err(e) => GoldenOutcome::Failed(e),
+        };
+    }
+
+    let expected = match image::open(&golden_path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => return GoldenOutcome::Failed(format!("failed to load {}: {e}", golden_path.display())),
+    };
+    let comparison = match compare_images(&actual, &expected, settings.tolerance) {
+        Ok(comparison) => comparison,
+        Err(e) => {
+            let _ = save_image(&actual, &settings.output_dir.join(format!("{}.actual.png", case.name)));
+            return GoldenOutcome::Failed(e);
+        }
+    };
+    if comparison.differing_percent() <= settings.max_percent {
+        return GoldenOutcome::Passed;
+    }
+
+    let actual_path = settings.output_dir.join(format!("{}.actual.png", case.name));
+    let diff_path = settings.output_dir.join(format!("{}.diff.png", case.name));
+    let heatmap_path = settings.output_dir.join(format!("{}.heatmap.png", case.name));
+    //大小已经检查过，diff_images 不会失败
+    let metrics = diff_images(&actual, &expected, HeatmapMetric::Flip).unwrap();
+    let saved = save_image(&actual, &actual_path)
+        .and_then(|_| save_image(&comparison.diff, &diff_path))
+        .and_then(|_| save_image(&metrics.heatmap, &heatmap_path));
+    let mut reason = format!(
+        "{:.3}% of the pixels differ by more than {} (max difference {}, allowed {}%), PSNR {:.2} dB, SSIM {:.5}, FLIP {:.5}",
+        comparison.differing_percent(),
+        settings.tolerance,
+        comparison.max_difference,
+        settings.max_percent,
+        metrics.psnr,
+        metrics.ssim,
+        metrics.flip,
+    );
+    match saved {
+        Ok(()) => reason.push_str(&format!(", see {}, {} and {}", actual_path.display(), diff_path.display(), heatmap_path.display())),
+        Err(e) => reason.push_str(&format!(", {e}")),
+    }
+    GoldenOutcome::Failed(reason)
+}
+
+fn compare_with_reference(case: &GoldenCase, actual: &RgbaImage, reference: &RgbaImage, settings: &GoldenSettings) -> Result<(), String> {
+    let comparison = compare_images(actual, reference, settings.tolerance)?;
+    if comparison.differing_percent() <= settings.max_percent {
+        return Ok(());
+    }
+    let reference_path = settings.output_dir.join(format!("{}.reference.png", case.name));
+    let diff_path = settings.output_dir.join(format!("{}.reference-diff.png", case.name));
+    let saved = save_image(reference, &reference_path).and_then(|_| save_image(&comparison.diff, &diff_path));
+    let mut reason = format!(
+        "{:.3}% of the pixels differ from the software reference by more than {} (max difference {})",
+        comparison.differing_percent(),
+        settings.tolerance,
+        comparison.max_difference,
+    );
+    match saved {
+        Ok(()) => reason.push_str(&format!(", see {} and {}", reference_path.display(), diff_path.display())),
+        Err(e) => reason.push_str(&format!(", {e}")),
+    }
+    Err(reason)
+}
+
+fn save_image(image: &RgbaImage, path: &Path) -> Result<(), String> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| format!("failed to create {}: {e}", parent.display()))?;
+    }
+    image.save(path).map_err(|e| format!("failed to save {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_images_rejects_different_sizes() {
+        let actual = RgbaImage::new(4, 4);
+        let expected = RgbaImage::new(4, 3);
+        assert!(compare_images(&actual, &expected, 0).is_err());
+    }
+
+    #[test]
+    fn compare_images_tolerance_is_inclusive() {
+        let expected = RgbaImage::from_pixel(2, 1, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([102, 100, 100, 255]));
+        actual.put_pixel(1, 0, Rgba([100, 97, 100, 255]));
+
+        //差为 2 的像素在 tolerance 以内，差为 3 的像素不同
+        let comparison = compare_images(&actual, &expected, 2).unwrap();
+        assert_eq!(comparison.differing_pixels, 1);
+        assert_eq!(comparison.total_pixels, 2);
+        assert_eq!(comparison.max_difference, 3);
+        assert_eq!(comparison.differing_percent(), 50.0);
+        assert_eq!(comparison.diff.get_pixel(1, 0)[0], 128 + 3 / 2);
+
+        let comparison = compare_images(&actual, &expected, 3).unwrap();
+        assert_eq!(comparison.differing_pixels, 0);
+    }
+
+    #[test]
+    fn differing_percent_of_empty_image_is_zero() {
+        let comparison = compare_images(&RgbaImage::new(0, 0), &RgbaImage::new(0, 0), 0).unwrap();
+        assert_eq!(comparison.total_pixels, 0);
+        assert_eq!(comparison.differing_percent(), 0.0);
+    }
+}
@@ -22,10 +22,10 @@
 
 
 use std::sync::Arc;
-use image::{ImageBuffer, Rgba};
+use image::RgbaImage;
 use vulkano::buffer::{BufferContents, BufferUsage};
 use vulkano::memory::allocator::MemoryUsage;
-use crate::example::buffer::{choose_device, create_auto_command_buffer_builder, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
+use crate::example::buffer::{choose_device, find_vulkan_device, create_auto_command_buffer_builder, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
 use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo};
 use vulkano::image::view::ImageView;
 use vulkano::command_buffer::{CopyImageToBufferInfo, RenderPassBeginInfo, SubpassContents};
@@ -36,7 +36,9 @@ use vulkano::pipeline::GraphicsPipeline;
 use vulkano::render_pass::Subpass;
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
+use crate::example::pipeline_cache::SharedPipelineCache;
 use crate::example::pipeline_glsl::{fs, vs};
+use crate::example::software::render_vertex_software;
 
 //顶点数据
 #[derive(BufferContents, Vertex)]
@@ -46,12 +48,33 @@ pub struct MyVertex {
     pub position: [f32; 2],
 }
 
+//渲染的图像大小
+pub const VERTEX_IMAGE_SIZE: u32 = 1024;
+
+//没有 Vulkan 设备时用软件光栅化画同样的图像
 pub fn operator_vertex() {
-    //三角形顶点数据
-    let vertex1 = MyVertex { position: [-0.5, -0.5] };
-    let vertex2 = MyVertex { position: [0.0, 0.5] };
-    let vertex3 = MyVertex { position: [0.5, -0.25] };
+    let image = match find_vulkan_device() {
+        Ok(_) => render_vertex(),
+        Err(e) => {
+            println!("{e}, falling back to the software rasterizer");
+            render_vertex_software()
+        }
+    };
+    image.save("image_vertex.png").unwrap();
+    println!("Everything vertex succeeded!");
+}
 
+//三角形顶点数据，软件光栅化也使用
+pub fn triangle_vertices() -> [MyVertex; 3] {
+    [
+        MyVertex { position: [-0.5, -0.5] },
+        MyVertex { position: [0.0, 0.5] },
+        MyVertex { position: [0.5, -0.25] },
+    ]
+}
+
+//图形管线画出的 1024x1024 三角形，golden 测试和 image_vertex.png 比较
+pub fn render_vertex() -> RgbaImage {
     //创建实例
     let instance = create_instance();
     //选择一台设备
@@ -69,8 +92,8 @@ pub fn operator_vertex() {
     let image = StorageImage::new(
         &memory_allocator,
         ImageDimensions::Dim2d {
-            width: 1024,
-            height: 1024,
+            width: VERTEX_IMAGE_SIZE,
+            height: VERTEX_IMAGE_SIZE,
             array_layers: 1,
         },
         Format::R8G8B8A8_UNORM,
@@ -83,7 +106,7 @@ pub fn operator_vertex() {
         memory_allocator_arc.clone(),
         BufferUsage::VERTEX_BUFFER,
         MemoryUsage::Upload,
-        vec![vertex1, vertex2, vertex3],
+        triangle_vertices(),
     );
 
     //顶点着色器
@@ -127,10 +150,11 @@ pub fn operator_vertex() {
 
     let viewport = Viewport {
         origin: [0.0, 0.0],
-        dimensions: [1024.0, 1024.0],
+        dimensions: [VERTEX_IMAGE_SIZE as f32, VERTEX_IMAGE_SIZE as f32],
         depth_range: 0.0..1.0,
     };
 
+    let pipeline_cache = SharedPipelineCache::new(device.clone());
     let pipeline = GraphicsPipeline::start()
         // Describes the layout of the vertex input and how should it behave
         .vertex_input_state(MyVertex::per_vertex())
@@ -145,6 +169,8 @@ pub fn operator_vertex() {
         .fragment_shader(fs.entry_point("main").unwrap(), ())
         // This graphics pipeline object concerns the first pass of the render pass.
         .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
+        // Reuse the compiled results of the previous run.
+        .build_with_cache(pipeline_cache.cache())
         // Now that everything is specified, we call `build`.
         .build(device.clone())
         .unwrap();
@@ -162,7 +188,7 @@ pub fn operator_vertex() {
         memory_allocator_arc.clone(),
         BufferUsage::TRANSFER_DST,
         MemoryUsage::Download,
-        (0..1024 * 1024 * 4).map(|_| 0u8),
+        (0..VERTEX_IMAGE_SIZE * VERTEX_IMAGE_SIZE * 4).map(|_| 0u8),
     );
 
     build
@@ -193,8 +219,5 @@ pub fn operator_vertex() {
     future.wait(None).unwrap();
 
     let buffer_content = buf.read().unwrap();
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &buffer_content[..]).unwrap();
-    image.save("image_vertex.png").unwrap();
-
-    println!("Everything vertex succeeded!");
+    RgbaImage::from_raw(VERTEX_IMAGE_SIZE, VERTEX_IMAGE_SIZE, buffer_content.to_vec()).unwrap()
 }
\ No newline at end of file
@@ -0,0 +1,306 @@
+//浮点渲染目标和高精度输出
+//离屏渲染可以使用 R16G16B16A16_SFLOAT 或 R32G32B32A32_SFLOAT 的颜色图像，颜色保持线性、不截断到 0 到 1，
+//读回时先在 GPU 上用 hdr_glsl 的计算着色器转换成要保存的格式：
+//RGBA32F 保存为 OpenEXR 或 Radiance HDR，sRGB 编码的 RGBA16 保存为 16 位 PNG，sRGB 编码的 RGBA8 保存为其他格式
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use image::codecs::hdr::HdrEncoder;
+use image::{ImageBuffer, Rgb, Rgba, Rgba32FImage, RgbaImage};
+use vulkano::buffer::{BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::format::Format;
+use vulkano::image::view::ImageViewAbstract;
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sampler::{Sampler, SamplerCreateInfo};
+use crate::example::buffer::create_iter_buffer;
+use crate::example::fractal::{HdrFractalRenderer, DEFAULT_ZOOM_CENTER};
+use crate::example::hdr_glsl::convert;
+use crate::example::render_mesh::{get_mesh_uniforms, RenderOptions, SceneRenderer};
+use crate::example::scene::load_scene;
+
+//浮点颜色附件的格式
+//R32G32B32A32_SFLOAT 不一定支持混合，有半透明材质的场景应该使用 R16G16B16A16_SFLOAT
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HdrFormat {
+    Rgba16Float,
+    Rgba32Float,
+}
+
+impl HdrFormat {
+    pub fn format(&self) -> Format {
+        match self {
+            HdrFormat::Rgba16Float => Format::R16G16B16A16_SFLOAT,
+            HdrFormat::Rgba32Float => Format::R32G32B32A32_SFLOAT,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<HdrFormat> {
+        match name {
+            "f16" | "rgba16f" => Some(HdrFormat::Rgba16Float),
+            "f32" | "rgba32f" => Some(HdrFormat::Rgba32Float),
+            _ => None,
+        }
+    }
+}
+
+//读回的格式，对应转换着色器的 mode
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadbackFormat {
+    //线性的 RGBA32F
+    Float32,
+    //sRGB 编码的 RGBA16 UNORM
+    Unorm16Srgb,
+    //sRGB 编码的 RGBA8 UNORM
+    Unorm8Srgb,
+}
+
+impl ReadbackFormat {
+    //按输出文件的扩展名选择：.exr 和 .hdr 读回浮点数，.png 读回 16 位，其他格式读回 8 位
+    pub fn for_path(path: &Path) -> ReadbackFormat {
+        match path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_ascii_lowercase()).as_deref() {
+            Some("exr") | Some("hdr") => ReadbackFormat::Float32,
+            Some("png") => ReadbackFormat::Unorm16Srgb,
+            _ => ReadbackFormat::Unorm8Srgb,
+        }
+    }
+
+    fn mode(&self) -> u32 {
+        match self {
+            ReadbackFormat::Float32 => 0,
+            ReadbackFormat::Unorm16Srgb => 1,
+            ReadbackFormat::Unorm8Srgb => 2,
+        }
+    }
+
+    //每个像素在缓冲区中占的 u32 个数
+    fn words_per_pixel(&self) -> u32 {
+        match self {
+            ReadbackFormat::Float32 => 4,
+            ReadbackFormat::Unorm16Srgb => 2,
+            ReadbackFormat::Unorm8Srgb => 1,
+        }
+    }
+}
+
+//读回的图像
+#[derive(Clone, Debug)]
+pub enum ReadbackImage {
+    Float(Rgba32FImage),
+    Rgba16(ImageBuffer<Rgba<u16>, Vec<u16>>),
+    Rgba8(RgbaImage),
+}
+
+impl ReadbackImage {
+    //转换着色器写入的数据，words 按行排列
+    pub fn decode(words: &[u32], width: u32, height: u32, format: ReadbackFormat) -> ReadbackImage {
+        match format {
+            ReadbackFormat::Float32 => {
+                ReadbackImage::Float(Rgba32FImage::from_raw(width, height, words.iter().map(|word| f32::from_bits(*word)).collect()).unwrap())
+            }
+            //packUnorm2x16 把第一个分量放在低 16 位
+            ReadbackFormat::Unorm16Srgb => ReadbackImage::Rgba16(
+                ImageBuffer::from_raw(width, height, words.iter().flat_map(|word| [*word as u16, (*word >> 16) as u16]).collect()).unwrap(),
+            ),
+            ReadbackFormat::Unorm8Srgb => {
+                ReadbackImage::Rgba8(RgbaImage::from_raw(width, height, words.iter().flat_map(|word| word.to_le_bytes()).collect()).unwrap())
+            }
+        }
+    }
+
+    //浮点图像只能保存为 .exr 或 .hdr，16 位图像只能保存为 PNG（或 TIFF 等支持 16 位的格式）
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        match self {
+            ReadbackImage::Float(image) => save_float_image(image, path),
+            ReadbackImage::Rgba16(image) => image.save(path).map_err(|e| format!("failed to save {}: {e}", path.display())),
+            ReadbackImage::Rgba8(image) => image.save(path).map_err(|e| format!("failed to save {}: {e}", path.display())),
+        }
+    }
+}
+
+//.exr 保存线性的 RGBA32F，.hdr 是 Radiance RGBE，只有 RGB，负数截断为 0
+pub fn save_float_image(image: &Rgba32FImage, path: &Path) -> Result<(), String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("exr") => image.save(path).map_err(|e| format!("failed to save {}: {e}", path.display())),
+        Some("hdr") => {
+            let file = File::create(path).map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+            let pixels: Vec<Rgb<f32>> = image
+                .pixels()
+                .map(|pixel| Rgb([pixel[0].max(0.0), pixel[1].max(0.0), pixel[2].max(0.0)]))
+                .collect();
+            HdrEncoder::new(BufWriter::new(file))
+                .encode(&pixels, image.width() as usize, image.height() as usize)
+                .map_err(|e| format!("failed to save {}: {e}", path.display()))
+        }
+        _ => Err(format!("{} is not an .exr or .hdr file", path.display())),
+    }
+}
+
+//把颜色图像转换成读回格式的计算管线，和使用它的渲染器在同一个设备上创建
+pub struct FormatConverter {
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    pipeline: Arc<ComputePipeline>,
+    sampler: Arc<Sampler>,
+}
+
+impl FormatConverter {
+    pub fn new(device: Arc<Device>, memory_allocator: Arc<StandardMemoryAllocator>, pipeline_cache: Arc<PipelineCache>) -> FormatConverter {
+        let shader = convert::load(device.clone()).expect("failed to create shader module");
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+        //texelFetch 不使用过滤，默认的采样器就可以
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo::default()).unwrap();
+
+        FormatConverter {
+            memory_allocator,
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device),
+            pipeline,
+            sampler,
+        }
+    }
+
+    //录制转换命令，view 需要有 SAMPLED 用途；命令缓冲区执行完以后用 ReadbackImage::decode 解读返回的缓冲区
+    pub fn record(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        view: Arc<dyn ImageViewAbstract>,
+        format: ReadbackFormat,
+        exposure: f32,
+    ) -> Subbuffer<[u32]> {
+        let [width, height] = view.image().dimensions().width_height();
+        let buffer = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Download,
+            (0..width * height * format.words_per_pixel()).map(|_| 0u32),
+        );
+        let set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::image_view_sampler(0, view, self.sampler.clone()),
+                WriteDescriptorSet::buffer(1, buffer.clone()),
+            ],
+        ).unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, self.pipeline.layout().clone(), 0, set)
+            .push_constants(self.pipeline.layout().clone(), 0, convert::PushConstants { mode: format.mode(), exposure })
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap();
+        buffer
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HdrSettings {
+    pub width: u32,
+    pub height: u32,
+    pub format: HdrFormat,
+    //读回之前颜色乘以的倍数
+    pub exposure: f32,
+    //mandelbrot 的中心和画面高度对应的复平面范围
+    pub center: [f32; 2],
+    pub scale: f32,
+}
+
+impl Default for HdrSettings {
+    fn default() -> Self {
+        HdrSettings {
+            width: 1024,
+            height: 1024,
+            format: HdrFormat::Rgba16Float,
+            exposure: 1.0,
+            center: DEFAULT_ZOOM_CENTER,
+            scale: 3.0,
+        }
+    }
+}
+
+//source 是模型文件或 mandelbrot，输出的格式按 output 的扩展名选择
+//mandelbrot 总是在 rgba32f 存储图像中计算，settings.format 只影响模型的颜色附件
+pub fn render_hdr(source: &str, output: &Path, settings: &HdrSettings, options: &RenderOptions) -> Result<(), String> {
+    let readback = ReadbackFormat::for_path(output);
+    let image = if source == "mandelbrot" {
+        let renderer = HdrFractalRenderer::new(settings.width, settings.height);
+        renderer.render(settings.center, settings.scale, readback, settings.exposure)
+    } else {
+        let scene = load_scene(Path::new(source))?;
+        let mut renderer = SceneRenderer::with_format(&scene, settings.width, settings.height, settings.format.format());
+        let uniforms = get_mesh_uniforms(renderer.bounds(), renderer.aspect_ratio(), 0.0, options);
+        let image = renderer.render_readback(uniforms, options, readback, settings.exposure);
+        renderer.save_cache();
+        image
+    };
+    image.save(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn readback_format_follows_extension() {
+        assert_eq!(ReadbackFormat::for_path(Path::new("out.exr")), ReadbackFormat::Float32);
+        assert_eq!(ReadbackFormat::for_path(Path::new("out.HDR")), ReadbackFormat::Float32);
+        assert_eq!(ReadbackFormat::for_path(Path::new("dir.png/out.png")), ReadbackFormat::Unorm16Srgb);
+        assert_eq!(ReadbackFormat::for_path(Path::new("out.Png")), ReadbackFormat::Unorm16Srgb);
+        assert_eq!(ReadbackFormat::for_path(Path::new("out.jpg")), ReadbackFormat::Unorm8Srgb);
+        assert_eq!(ReadbackFormat::for_path(Path::new("out")), ReadbackFormat::Unorm8Srgb);
+    }
+
+    #[test]
+    fn decode_float32() {
+        let values = [0.5f32, -1.0, 2.25, 1.0, 0.0, 8.0, 0.125, 0.75];
+        let words: Vec<u32> = values.iter().map(|value| value.to_bits()).collect();
+        match ReadbackImage::decode(&words, 2, 1, ReadbackFormat::Float32) {
+            ReadbackImage::Float(image) => {
+                assert_eq!(image.dimensions(), (2, 1));
+                assert_eq!(image.get_pixel(0, 0).0, [0.5, -1.0, 2.25, 1.0]);
+                assert_eq!(image.get_pixel(1, 0).0, [0.0, 8.0, 0.125, 0.75]);
+            }
+            other => panic!("expected a float image, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decode_unorm16_puts_low_half_first() {
+        //packUnorm2x16(rg) 和 packUnorm2x16(ba)，r 和 b 在低 16 位
+        let words = [0x2222_1111, 0x4444_3333, 0x0000_ffff, 0xffff_0000];
+        match ReadbackImage::decode(&words, 1, 2, ReadbackFormat::Unorm16Srgb) {
+            ReadbackImage::Rgba16(image) => {
+                assert_eq!(image.dimensions(), (1, 2));
+                assert_eq!(image.get_pixel(0, 0).0, [0x1111, 0x2222, 0x3333, 0x4444]);
+                assert_eq!(image.get_pixel(0, 1).0, [0xffff, 0, 0, 0xffff]);
+            }
+            other => panic!("expected a 16-bit image, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn decode_unorm8_puts_low_byte_first() {
+        //packUnorm4x8 把 r 放在最低的 8 位
+        let words = [0x4433_2211, 0xff00_80ff];
+        match ReadbackImage::decode(&words, 2, 1, ReadbackFormat::Unorm8Srgb) {
+            ReadbackImage::Rgba8(image) => {
+                assert_eq!(image.dimensions(), (2, 1));
+                assert_eq!(image.get_pixel(0, 0).0, [0x11, 0x22, 0x33, 0x44]);
+                assert_eq!(image.get_pixel(1, 0).0, [0xff, 0x80, 0x00, 0xff]);
+            }
+            other => panic!("expected an 8-bit image, got {other:?}"),
+        }
+    }
+}
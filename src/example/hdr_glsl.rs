@@ -0,0 +1,55 @@
+//在 GPU 上把任意可采样的颜色图像转换成要读回的格式，写入存储缓冲区
+//用 texelFetch 读取，采样会把 UNORM、sRGB、16 位和 32 位浮点格式都转换成浮点数，所以一个着色器可以处理所有的源格式
+pub mod convert {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) uniform sampler2D src;
+
+            layout(set = 0, binding = 1) writeonly buffer Pixels {
+                uint words[];
+            } dst;
+
+            layout(push_constant) uniform PushConstants {
+                //0 是 RGBA32F（每个像素 4 个字），1 是 sRGB 编码的 RGBA16 UNORM（2 个字），2 是 sRGB 编码的 RGBA8 UNORM（1 个字）
+                uint mode;
+                //颜色先乘以曝光
+                float exposure;
+            } push;
+
+            vec3 linear_to_srgb(vec3 c) {
+                return mix(c * 12.92, 1.055 * pow(c, vec3(1.0 / 2.4)) - 0.055, greaterThan(c, vec3(0.0031308)));
+            }
+
+            void main() {
+                ivec2 size = textureSize(src, 0);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+
+                vec4 color = texelFetch(src, p, 0);
+                color.rgb *= push.exposure;
+                uint index = uint(p.y * size.x + p.x);
+                if (push.mode == 0) {
+                    for (uint c = 0; c < 4; c++) {
+                        dst.words[index * 4 + c] = floatBitsToUint(color[c]);
+                    }
+                    return;
+                }
+
+                vec4 encoded = vec4(linear_to_srgb(clamp(color.rgb, 0.0, 1.0)), clamp(color.a, 0.0, 1.0));
+                if (push.mode == 1) {
+                    dst.words[index * 2] = packUnorm2x16(encoded.rg);
+                    dst.words[index * 2 + 1] = packUnorm2x16(encoded.ba);
+                } else {
+                    dst.words[index] = packUnorm4x8(encoded);
+                }
+            }
+        ",
+    }
+}
@@ -0,0 +1,145 @@
+//帧率和帧时间的 HUD
+//每个窗口记录最近若干帧的帧间隔、CPU 录制和提交一帧的时间，以及 GPU 执行一帧的时间（GpuProfiler 的 frame 区间），
+//显示为右上角的文字和右下角的帧时间曲线
+use std::collections::VecDeque;
+use vulkano::swapchain::PresentMode;
+use crate::example::debug_draw::DebugDraw;
+use crate::example::text::{TextAlign, TextLabel};
+
+//保留的帧数，也是曲线的采样数
+pub const HISTORY_LEN: usize = 120;
+//曲线的大小（逻辑像素）和纵轴表示的最长帧时间
+const GRAPH_SIZE: [f32; 2] = [240.0, 60.0];
+const GRAPH_MAX_MS: f32 = 50.0;
+//HUD 和窗口边缘的距离
+const MARGIN: f32 = 8.0;
+const TEXT_SIZE: f32 = 14.0;
+
+//最近若干帧的时间，单位毫秒
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    frame_times: VecDeque<f32>,
+    cpu_times: VecDeque<f32>,
+    gpu_times: VecDeque<f32>,
+}
+
+impl FrameStats {
+    pub fn new() -> FrameStats {
+        FrameStats::default()
+    }
+
+    //两帧之间的间隔
+    pub fn push_frame(&mut self, ms: f32) {
+        push_sample(&mut self.frame_times, ms);
+    }
+
+    pub fn push_cpu(&mut self, ms: f32) {
+        push_sample(&mut self.cpu_times, ms);
+    }
+
+    pub fn push_gpu(&mut self, ms: f32) {
+        push_sample(&mut self.gpu_times, ms);
+    }
+
+    //按平均帧间隔计算，比单帧间隔的倒数稳定
+    pub fn fps(&self) -> f32 {
+        match average(&self.frame_times) {
+            Some(ms) if ms > 0.0 => 1000.0 / ms,
+            _ => 0.0,
+        }
+    }
+
+    pub fn cpu_ms(&self) -> Option<f32> {
+        average(&self.cpu_times)
+    }
+
+    //设备不支持时间戳查询时为 None
+    pub fn gpu_ms(&self) -> Option<f32> {
+        average(&self.gpu_times)
+    }
+
+    pub fn frame_times(&self) -> &VecDeque<f32> {
+        &self.frame_times
+    }
+
+    pub fn gpu_times(&self) -> &VecDeque<f32> {
+        &self.gpu_times
+    }
+}
+
+fn push_sample(history: &mut VecDeque<f32>, ms: f32) {
+    if history.len() == HISTORY_LEN {
+        history.pop_front();
+    }
+    history.push_back(ms);
+}
+
+fn average(history: &VecDeque<f32>) -> Option<f32> {
+    if history.is_empty() {
+        None
+    } else {
+        Some(history.iter().sum::<f32>() / history.len() as f32)
+    }
+}
+
+//HUD 中和帧时间无关的信息
+pub struct HudInfo<'a> {
+    pub device_name: &'a str,
+    //离屏渲染（输入回放）时没有交换链，为 None
+    pub present_mode: Option<PresentMode>,
+    //交换链图像或离屏图像的大小（物理像素）
+    pub resolution: [u32; 2],
+}
+
+//右上角的文字，logical_size 是窗口的逻辑大小
+pub fn get_hud_label(stats: &FrameStats, info: &HudInfo, logical_size: [f32; 2]) -> TextLabel {
+    let gpu = match stats.gpu_ms() {
+        Some(ms) => format!("{ms:.2} ms"),
+        None => "n/a".to_string(),
+    };
+    let present_mode = match info.present_mode {
+        Some(present_mode) => format!("{present_mode:?}"),
+        None => "offscreen".to_string(),
+    };
+    let text = format!(
+        "{:.1} FPS\nCPU {:.2} ms\nGPU {gpu}\n{}x{} {present_mode}\n{}",
+        stats.fps(),
+        stats.cpu_ms().unwrap_or(0.0),
+        info.resolution[0],
+        info.resolution[1],
+        info.device_name,
+    );
+    TextLabel {
+        size: TEXT_SIZE,
+        align: TextAlign::Right,
+        ..TextLabel::new(text, [logical_size[0] - MARGIN, MARGIN])
+    }
+}
+
+//右下角的帧时间曲线：白色是帧间隔，青色是 GPU 时间，绿线和黄线分别是 60 FPS 和 30 FPS
+pub fn draw_frame_graph(debug_draw: &mut DebugDraw, stats: &FrameStats, logical_size: [f32; 2]) {
+    let [width, height] = GRAPH_SIZE;
+    let left = logical_size[0] - MARGIN - width;
+    let bottom = logical_size[1] - MARGIN;
+    let top = bottom - height;
+    let right = left + width;
+
+    let frame = [0.5, 0.5, 0.5, 0.8];
+    debug_draw.line_2d([left, top], [right, top], frame);
+    debug_draw.line_2d([right, top], [right, bottom], frame);
+    debug_draw.line_2d([right, bottom], [left, bottom], frame);
+    debug_draw.line_2d([left, bottom], [left, top], frame);
+
+    let y = |ms: f32| bottom - (ms / GRAPH_MAX_MS).clamp(0.0, 1.0) * height;
+    debug_draw.line_2d([left, y(1000.0 / 60.0)], [right, y(1000.0 / 60.0)], [0.0, 1.0, 0.0, 0.5]);
+    debug_draw.line_2d([left, y(1000.0 / 30.0)], [right, y(1000.0 / 30.0)], [1.0, 1.0, 0.0, 0.5]);
+
+    //最新的一帧在最右边
+    for (history, color) in [(stats.frame_times(), [1.0, 1.0, 1.0, 1.0]), (stats.gpu_times(), [0.0, 1.0, 1.0, 1.0])] {
+        let skipped = HISTORY_LEN - history.len();
+        let x = |i: usize| left + width * (skipped + i) as f32 / (HISTORY_LEN - 1) as f32;
+        for (i, (a, b)) in history.iter().zip(history.iter().skip(1)).enumerate() {
+            debug_draw.line_2d([x(i), y(*a)], [x(i + 1), y(*b)], color);
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//两张图像的差异
+//逐像素计算通道的绝对误差、亮度的 SSIM（7x7 窗口）和类 FLIP 的感知误差，再汇总成平均/最大绝对误差、PSNR、平均 SSIM 和平均 FLIP，
+//并把选中的指标画成热力图（黑色表示没有差异，越亮差异越大）
+//类 FLIP 误差是 NVIDIA FLIP 的简化版本：CIELAB 中的 HyAB 颜色差按边缘（Sobel 梯度）的差放大，没有模拟人眼的对比敏感度滤波
+//逐像素的部分可以在 CPU 上计算，也可以用计算着色器在 GPU 上计算，两者的公式相同，汇总和热力图总是在 CPU 上完成
+use std::path::Path;
+use std::sync::Arc;
+use image::{Rgba, RgbaImage};
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, find_vulkan_device, get_queue};
+use crate::example::image_diff_glsl::shader;
+use crate::example::pipeline_cache::SharedPipelineCache;
+
+//SSIM 窗口的半径和常数，常数对应 K1 = 0.01、K2 = 0.03，亮度范围为1
+const SSIM_RADIUS: i32 = 3;
+const SSIM_C1: f32 = 0.0001;
+const SSIM_C2: f32 = 0.0009;
+
+//热力图显示的指标
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeatmapMetric {
+    //通道绝对误差的最大值，按整张图像最大的误差归一化
+    Absolute,
+    //1 - SSIM
+    Ssim,
+    Flip,
+}
+
+impl HeatmapMetric {
+    pub fn from_name(name: &str) -> Option<HeatmapMetric> {
+        match name {
+            "abs" => Some(HeatmapMetric::Absolute),
+            "ssim" => Some(HeatmapMetric::Ssim),
+            "flip" => Some(HeatmapMetric::Flip),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DiffSettings {
+    pub metric: HeatmapMetric,
+    //用计算着色器计算逐像素的误差，适合大图像，没有 Vulkan 设备时退回 CPU
+    pub gpu: bool,
+}
+
+impl Default for DiffSettings {
+    fn default() -> Self {
+        DiffSettings {
+            metric: HeatmapMetric::Flip,
+            gpu: false,
+        }
+    }
+}
+
+//一个像素的误差，绝对误差的范围是 0 到 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelError {
+    pub abs_error: [f32; 4],
+    pub ssim: f32,
+    pub flip: f32,
+}
+
+pub struct ErrorMap {
+    pub width: u32,
+    pub height: u32,
+    //按行排列
+    pub pixels: Vec<PixelError>,
+}
+
+//汇总的结果，绝对误差和 MSE 的单位是 8 位的颜色值
+#[derive(Clone, Debug)]
+pub struct ImageDiff {
+    pub width: u32,
+    pub height: u32,
+    pub mean_abs_error: [f64; 4],
+    pub max_abs_error: [u8; 4],
+    pub mse: [f64; 4],
+    //RGB 三个通道的 PSNR，两张图像相同时为无穷大
+    pub psnr: f64,
+    pub ssim: f64,
+    pub flip: f64,
+    pub max_flip: f64,
+    pub heatmap: RgbaImage,
+}
+
+impl ImageDiff {
+    //两张图像完全相同
+    pub fn identical(&self) -> bool {
+        self.max_abs_error == [0; 4]
+    }
+
+    pub fn summary(&self) -> String {
+        let [r, g, b, a] = self.mean_abs_error;
+        let [max_r, max_g, max_b, max_a] = self.max_abs_error;
+        format!(
+            "{}x{}\nmean absolute error R {r:.3} G {g:.3} B {b:.3} A {a:.3}\nmax absolute error R {max_r} G {max_g} B {max_b} A {max_a}\nPSNR {:.2} dB\nSSIM {:.5}\nFLIP mean {:.5} max {:.5}",
+            self.width,
+            self.height,
+            self.psnr,
+            self.ssim,
+            self.flip,
+            self.max_flip,
+        )
+    }
+}
+
+//大小不同时返回 Err
+pub fn diff_images(a: &RgbaImage, b: &RgbaImage, metric: HeatmapMetric) -> Result<ImageDiff, String> {
+    Ok(summarize(&error_map(a, b)?, metric))
+}
+
+//读取两个文件比较，heatmap 不为 None 时保存热力图
+pub fn diff_files(a: &Path, b: &Path, heatmap: Option<&Path>, settings: &DiffSettings) -> Result<ImageDiff, String> {
+    let load = |path: &Path| {
+        image::open(path)
+            .map(|image| image.to_rgba8())
+            .map_err(|e| format!("failed to load {}: {e}", path.display()))
+    };
+    let (a, b) = (load(a)?, load(b)?);
+
+    let map = if !settings.gpu {
+        error_map(&a, &b)?
+    } else {
+        match find_vulkan_device() {
+            Ok(_) => GpuImageDiff::new().error_map(&a, &b)?,
+            Err(e) => {
+                println!("{e}, computing the difference on the CPU");
+                error_map(&a, &b)?
+            }
+        }
+    };
+    let diff = summarize(&map, settings.metric);
+    if let Some(path) = heatmap {
+        diff.heatmap.save(path).map_err(|e| format!("failed to save {}: {e}", path.display()))?;
+    }
+    Ok(diff)
+}
+
+//CPU 上的逐像素误差，和 image_diff_glsl 的计算着色器相同
+pub fn error_map(a: &RgbaImage, b: &RgbaImage) -> Result<ErrorMap, String> {
+    check_sizes(a, b)?;
+    let (width, height) = a.dimensions();
+    let colors = |image: &RgbaImage| -> Vec<[f32; 4]> {
+        image.pixels().map(|pixel| pixel.0.map(|value| value as f32 / 255.0)).collect()
+    };
+    let (colors_a, colors_b) = (colors(a), colors(b));
+    let lumas = |colors: &[[f32; 4]]| -> Vec<f32> { colors.iter().map(luma).collect() };
+    let (luma_a, luma_b) = (lumas(&colors_a), lumas(&colors_b));
+    let labs = |colors: &[[f32; 4]]| -> Vec<[f32; 3]> { colors.iter().map(to_lab).collect() };
+    let (lab_a, lab_b) = (labs(&colors_a), labs(&colors_b));
+
+    //超出边界的坐标取最近的边缘像素
+    let index = |x: i32, y: i32| {
+        y.clamp(0, height as i32 - 1) as usize * width as usize + x.clamp(0, width as i32 - 1) as usize
+    };
+    let gradient = |lab: &[[f32; 3]], x: i32, y: i32| {
+        let l = |i: i32, j: i32| lab[index(x + i, y + j)][0] / 100.0;
+        let gx = (l(1, -1) + 2.0 * l(1, 0) + l(1, 1)) - (l(-1, -1) + 2.0 * l(-1, 0) + l(-1, 1));
+        let gy = (l(-1, 1) + 2.0 * l(0, 1) + l(1, 1)) - (l(-1, -1) + 2.0 * l(0, -1) + l(1, -1));
+        (gx * gx + gy * gy).sqrt() / 4.0
+    };
+
+    let mut pixels = Vec::with_capacity(colors_a.len());
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let i = index(x, y);
+
+            let mut sums = [0.0f32; 5];
+            for j in -SSIM_RADIUS..=SSIM_RADIUS {
+                for k in -SSIM_RADIUS..=SSIM_RADIUS {
+                    let n = index(x + k, y + j);
+                    let (la, lb) = (luma_a[n], luma_b[n]);
+                    sums[0] += la;
+                    sums[1] += lb;
+                    sums[2] += la * la;
+                    sums[3] += lb * lb;
+                    sums[4] += la * lb;
+                }
+            }
+            let n = ((2 * SSIM_RADIUS + 1) * (2 * SSIM_RADIUS + 1)) as f32;
+            let [mean_a, mean_b, mean_aa, mean_bb, mean_ab] = sums.map(|sum| sum / n);
+            let var_a = mean_aa - mean_a * mean_a;
+            let var_b = mean_bb - mean_b * mean_b;
+            let covariance = mean_ab - mean_a * mean_b;
+            let ssim = ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2));
+
+            let (la, lb) = (lab_a[i], lab_b[i]);
+            let hyab = (la[0] - lb[0]).abs() + ((la[1] - lb[1]).powi(2) + (la[2] - lb[2]).powi(2)).sqrt();
+            let color_error = (hyab / 100.0).clamp(0.0, 1.0).powf(0.7);
+            let feature_error = (gradient(&lab_a, x, y) - gradient(&lab_b, x, y)).abs().clamp(0.0, 1.0);
+            let flip = if feature_error >= 1.0 { 1.0 } else { color_error.powf(1.0 - feature_error) };
+
+            pixels.push(PixelError {
+                abs_error: std::array::from_fn(|c| (colors_a[i][c] - colors_b[i][c]).abs()),
+                ssim,
+                flip,
+            });
+        }
+    }
+    Ok(ErrorMap { width, height, pixels })
+}
+
+fn check_sizes(a: &RgbaImage, b: &RgbaImage) -> Result<(), String> {
+    if a.dimensions() != b.dimensions() {
+        return Err(format!(
+            "image sizes do not match: {}x{} and {}x{}",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height(),
+        ));
+    }
+    Ok(())
+}
+
+fn luma(color: &[f32; 4]) -> f32 {
+    0.299 * color[0] + 0.587 * color[1] + 0.114 * color[2]
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value > 0.04045 {
+        ((value + 0.055) / 1.055).powf(2.4)
+    } else {
+        value / 12.92
+    }
+}
+
+fn lab_f(t: f32) -> f32 {
+    if t > 0.008856 {
+        t.powf(1.0 / 3.0)
+    } else {
+        7.787 * t + 16.0 / 116.0
+    }
+}
+
+//D65 白点的 CIELAB
+fn to_lab(color: &[f32; 4]) -> [f32; 3] {
+    let [r, g, b] = [color[0], color[1], color[2]].map(srgb_to_linear);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let [fx, fy, fz] = [x, y, z].map(lab_f);
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+//汇总逐像素的误差并画出热力图
+pub fn summarize(map: &ErrorMap, metric: HeatmapMetric) -> ImageDiff {
+    let count = map.pixels.len().max(1) as f64;
+    let mut abs_sum = [0.0f64; 4];
+    let mut squared_sum = [0.0f64; 4];
+    let mut max_abs_error = [0u8; 4];
+    let mut ssim_sum = 0.0;
+    let mut flip_sum = 0.0;
+    let mut max_flip = 0.0f64;
+    for pixel in &map.pixels {
+        for c in 0..4 {
+            let error = (pixel.abs_error[c] * 255.0).round();
+            abs_sum[c] += error as f64;
+            squared_sum[c] += (error * error) as f64;
+            max_abs_error[c] = max_abs_error[c].max(error as u8);
+        }
+        ssim_sum += pixel.ssim as f64;
+        flip_sum += pixel.flip as f64;
+        max_flip = max_flip.max(pixel.flip as f64);
+    }
+    let mse = squared_sum.map(|sum| sum / count);
+    let rgb_mse = (mse[0] + mse[1] + mse[2]) / 3.0;
+    let psnr = if rgb_mse == 0.0 {
+        f64::INFINITY
+    } else {
+        10.0 * (255.0 * 255.0 / rgb_mse).log10()
+    };
+
+    let largest = max_abs_error.iter().copied().max().unwrap_or(0).max(1) as f32 / 255.0;
+    let heatmap = RgbaImage::from_fn(map.width, map.height, |x, y| {
+        let pixel = &map.pixels[y as usize * map.width as usize + x as usize];
+        let value = match metric {
+            HeatmapMetric::Absolute => pixel.abs_error.iter().copied().fold(0.0, f32::max) / largest,
+            HeatmapMetric::Ssim => 1.0 - pixel.ssim,
+            HeatmapMetric::Flip => pixel.flip,
+        };
+        heat_color(value)
+    });
+
+    ImageDiff {
+        width: map.width,
+        height: map.height,
+        mean_abs_error: abs_sum.map(|sum| sum / count),
+        max_abs_error,
+        mse,
+        psnr,
+        ssim: ssim_sum / count,
+        flip: flip_sum / count,
+        max_flip,
+        heatmap,
+    }
+}
+
+//近似 inferno 的颜色表，0 是黑色，1 是浅黄色
+fn heat_color(value: f32) -> Rgba<u8> {
+    const STOPS: [[f32; 3]; 5] = [
+        [0.0, 0.0, 4.0],
+        [87.0, 16.0, 110.0],
+        [188.0, 55.0, 84.0],
+        [249.0, 142.0, 9.0],
+        [252.0, 255.0, 164.0],
+    ];
+    let t = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (t.floor() as usize).min(STOPS.len() - 2);
+    let f = t - i as f32;
+    let [r, g, b] = std::array::from_fn(|c| (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * f).round() as u8);
+    Rgba([r, g, b, 255])
+}
+
+//GPU 上的逐像素误差，设备和管线只创建一次，缓冲区按图像大小每次创建
+pub struct GpuImageDiff {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    pipeline: Arc<ComputePipeline>,
+}
+
+impl Default for GpuImageDiff {
+    fn default() -> Self {
+        GpuImageDiff::new()
+    }
+}
+
+impl GpuImageDiff {
+    pub fn new() -> GpuImageDiff {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+
+        let shader = shader::load(device.clone()).expect("failed to create shader module");
+        let pipeline_cache = SharedPipelineCache::new(device.clone());
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+
+        GpuImageDiff {
+            memory_allocator: Arc::new(create_memory_allocator(device.clone())),
+            command_buffer_allocator: create_buffer_allocator(device.clone()),
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+            device,
+            queue,
+            pipeline,
+        }
+    }
+
+    pub fn diff(&self, a: &RgbaImage, b: &RgbaImage, metric: HeatmapMetric) -> Result<ImageDiff, String> {
+        Ok(summarize(&self.error_map(a, b)?, metric))
+    }
+
+    pub fn error_map(&self, a: &RgbaImage, b: &RgbaImage) -> Result<ErrorMap, String> {
+        check_sizes(a, b)?;
+        let (width, height) = a.dimensions();
+
+        //每个像素打包成一个 u32，着色器用 unpackUnorm4x8 解开，R 在最低的字节
+        let upload = |image: &RgbaImage| {
+            create_iter_buffer(
+                self.memory_allocator.clone(),
+                BufferUsage::STORAGE_BUFFER,
+                MemoryUsage::Upload,
+                image.pixels().map(|pixel| u32::from_le_bytes(pixel.0)),
+            )
+        };
+        let buffer_a = upload(a);
+        let buffer_b = upload(b);
+        //和着色器中的 PixelError 相同：abs_error 和 metrics 两个 vec4
+        let errors = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Download,
+            (0..width as usize * height as usize).map(|_| [0.0f32; 8]),
+        );
+
+        let set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [
+                WriteDescriptorSet::buffer(0, buffer_a),
+                WriteDescriptorSet::buffer(1, buffer_b),
+                WriteDescriptorSet::buffer(2, errors.clone()),
+            ],
+        ).unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        builder
+            .bind_pipeline_compute(self.pipeline.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, self.pipeline.layout().clone(), 0, set)
+            .push_constants(self.pipeline.layout().clone(), 0, shader::PushConstants { width, height })
+            .dispatch([(width + 7) / 8, (height + 7) / 8, 1])
+            .unwrap();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let content = errors.read().unwrap();
+        let pixels = content
+            .iter()
+            .map(|error| PixelError {
+                abs_error: [error[0], error[1], error[2], error[3]],
+                ssim: error[4],
+                flip: error[5],
+            })
+            .collect();
+        Ok(ErrorMap { width, height, pixels })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba([100, 100, 100, 255]))
+    }
+
+    #[test]
+    fn identical_images_have_no_error() {
+        let image = RgbaImage::from_fn(8, 6, |x, y| Rgba([(x * 30) as u8, (y * 40) as u8, 200, 255]));
+        let diff = diff_images(&image, &image, HeatmapMetric::Flip).unwrap();
+
+        assert!(diff.identical());
+        assert_eq!(diff.psnr, f64::INFINITY);
+        assert!((diff.ssim - 1.0).abs() < 1e-4, "{}", diff.ssim);
+        assert_eq!(diff.flip, 0.0);
+        assert_eq!(diff.max_flip, 0.0);
+        assert_eq!(diff.mean_abs_error, [0.0; 4]);
+        assert!(diff.heatmap.pixels().all(|pixel| *pixel == heat_color(0.0)));
+    }
+
+    #[test]
+    fn one_changed_pixel() {
+        let a = gray(4, 4);
+        let mut b = gray(4, 4);
+        b.put_pixel(1, 2, Rgba([130, 90, 100, 255]));
+        let diff = diff_images(&a, &b, HeatmapMetric::Absolute).unwrap();
+
+        assert!(!diff.identical());
+        assert_eq!(diff.max_abs_error, [30, 10, 0, 0]);
+        assert_eq!(diff.mean_abs_error, [30.0 / 16.0, 10.0 / 16.0, 0.0, 0.0]);
+        assert_eq!(diff.mse, [900.0 / 16.0, 100.0 / 16.0, 0.0, 0.0]);
+        let expected_psnr = 10.0 * (255.0f64 * 255.0 / ((900.0 + 100.0) / 16.0 / 3.0)).log10();
+        assert!((diff.psnr - expected_psnr).abs() < 1e-9);
+        assert!(diff.ssim < 1.0);
+        assert!(diff.max_flip > 0.0);
+
+        //绝对误差的热力图按最大的误差归一化，只有改变的像素是最亮的颜色
+        assert_eq!(*diff.heatmap.get_pixel(1, 2), heat_color(1.0));
+        assert_eq!(*diff.heatmap.get_pixel(0, 0), heat_color(0.0));
+    }
+
+    #[test]
+    fn mismatched_sizes_are_rejected() {
+        let error = diff_images(&gray(2, 2), &gray(3, 2), HeatmapMetric::Ssim).unwrap_err();
+        assert_eq!(error, "image sizes do not match: 2x2 and 3x2");
+    }
+
+    #[test]
+    fn heat_color_stops() {
+        assert_eq!(heat_color(0.0), Rgba([0, 0, 4, 255]));
+        assert_eq!(heat_color(0.25), Rgba([87, 16, 110, 255]));
+        assert_eq!(heat_color(0.5), Rgba([188, 55, 84, 255]));
+        assert_eq!(heat_color(1.0), Rgba([252, 255, 164, 255]));
+        //超出范围的值取两端的颜色
+        assert_eq!(heat_color(-1.0), heat_color(0.0));
+        assert_eq!(heat_color(2.0), heat_color(1.0));
+    }
+}
@@ -0,0 +1,130 @@
+//逐像素计算两张图像的误差，和 image_diff 中的 CPU 实现相同
+//两张图像以打包的 RGBA8 存在存储缓冲区中，每个像素输出通道的绝对误差（0 到 1）、SSIM 和类 FLIP 误差
+pub mod shader {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer ImageA {
+                uint pixels[];
+            } image_a;
+
+            layout(set = 0, binding = 1) readonly buffer ImageB {
+                uint pixels[];
+            } image_b;
+
+            struct PixelError {
+                vec4 abs_error;
+                //x 是 SSIM，y 是类 FLIP 误差
+                vec4 metrics;
+            };
+
+            layout(set = 0, binding = 2) writeonly buffer Errors {
+                PixelError errors[];
+            } result;
+
+            layout(push_constant) uniform PushConstants {
+                uint width;
+                uint height;
+            } push;
+
+            const int SSIM_RADIUS = 3;
+            const float SSIM_C1 = 0.0001;
+            const float SSIM_C2 = 0.0009;
+
+            //超出边界的坐标取最近的边缘像素
+            vec4 load(bool second, ivec2 p) {
+                p = clamp(p, ivec2(0), ivec2(push.width, push.height) - 1);
+                uint index = uint(p.y) * push.width + uint(p.x);
+                return unpackUnorm4x8(second ? image_b.pixels[index] : image_a.pixels[index]);
+            }
+
+            float luma(vec4 color) {
+                return dot(color.rgb, vec3(0.299, 0.587, 0.114));
+            }
+
+            vec3 srgb_to_linear(vec3 c) {
+                return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), greaterThan(c, vec3(0.04045)));
+            }
+
+            float lab_f(float t) {
+                return t > 0.008856 ? pow(t, 1.0 / 3.0) : 7.787 * t + 16.0 / 116.0;
+            }
+
+            //D65 白点的 CIELAB
+            vec3 to_lab(vec4 color) {
+                vec3 c = srgb_to_linear(color.rgb);
+                float x = dot(c, vec3(0.4124, 0.3576, 0.1805)) / 0.95047;
+                float y = dot(c, vec3(0.2126, 0.7152, 0.0722));
+                float z = dot(c, vec3(0.0193, 0.1192, 0.9505)) / 1.08883;
+                float fx = lab_f(x);
+                float fy = lab_f(y);
+                float fz = lab_f(z);
+                return vec3(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz));
+            }
+
+            //L 的 Sobel 梯度大小，归一化到 0 到 1
+            float gradient(bool second, ivec2 p) {
+                float l[9];
+                for (int j = 0; j < 3; j++) {
+                    for (int i = 0; i < 3; i++) {
+                        l[j * 3 + i] = to_lab(load(second, p + ivec2(i - 1, j - 1))).x / 100.0;
+                    }
+                }
+                float gx = (l[2] + 2.0 * l[5] + l[8]) - (l[0] + 2.0 * l[3] + l[6]);
+                float gy = (l[6] + 2.0 * l[7] + l[8]) - (l[0] + 2.0 * l[1] + l[2]);
+                return length(vec2(gx, gy)) / 4.0;
+            }
+
+            void main() {
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= int(push.width) || p.y >= int(push.height)) {
+                    return;
+                }
+                vec4 a = load(false, p);
+                vec4 b = load(true, p);
+
+                //7x7 窗口的亮度 SSIM
+                float sum_a = 0.0;
+                float sum_b = 0.0;
+                float sum_aa = 0.0;
+                float sum_bb = 0.0;
+                float sum_ab = 0.0;
+                for (int j = -SSIM_RADIUS; j <= SSIM_RADIUS; j++) {
+                    for (int i = -SSIM_RADIUS; i <= SSIM_RADIUS; i++) {
+                        float la = luma(load(false, p + ivec2(i, j)));
+                        float lb = luma(load(true, p + ivec2(i, j)));
+                        sum_a += la;
+                        sum_b += lb;
+                        sum_aa += la * la;
+                        sum_bb += lb * lb;
+                        sum_ab += la * lb;
+                    }
+                }
+                float n = float((2 * SSIM_RADIUS + 1) * (2 * SSIM_RADIUS + 1));
+                float mean_a = sum_a / n;
+                float mean_b = sum_b / n;
+                float var_a = sum_aa / n - mean_a * mean_a;
+                float var_b = sum_bb / n - mean_b * mean_b;
+                float covariance = sum_ab / n - mean_a * mean_b;
+                float ssim = ((2.0 * mean_a * mean_b + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                    / ((mean_a * mean_a + mean_b * mean_b + SSIM_C1) * (var_a + var_b + SSIM_C2));
+
+                //类 FLIP 误差：HyAB 颜色差，边缘差越大越接近 1
+                vec3 lab_a = to_lab(a);
+                vec3 lab_b = to_lab(b);
+                float hyab = abs(lab_a.x - lab_b.x) + length(lab_a.yz - lab_b.yz);
+                float color_error = pow(clamp(hyab / 100.0, 0.0, 1.0), 0.7);
+                float feature_error = clamp(abs(gradient(false, p) - gradient(true, p)), 0.0, 1.0);
+                float flip = feature_error >= 1.0 ? 1.0 : pow(color_error, 1.0 - feature_error);
+
+                uint index = uint(p.y) * push.width + uint(p.x);
+                result.errors[index].abs_error = abs(a - b);
+                result.errors[index].metrics = vec4(ssim, flip, 0.0, 0.0);
+            }
+        ",
+    }
+}
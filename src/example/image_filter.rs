@@ -0,0 +1,470 @@
+//GPU 图像滤镜
+//和 operator_image_shader 一样在计算着色器中读写存储图像，输入图像解开到 rgba32f 图像后，
+//每个滤镜从一张图像读、写到另一张图像，两张图像交替使用，最后打包回 RGBA8 读回 CPU
+//所有滤镜录制在同一个命令缓冲区中，每个滤镜之间的屏障由 AutoCommandBufferBuilder 插入
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use image::RgbaImage;
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::shader::ShaderModule;
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
+use crate::example::image_filter_glsl::{convolve, lut, neighborhood, pack, pointwise, unpack};
+use crate::example::pipeline_cache::SharedPipelineCache;
+
+//模糊半径的上限，避免一个调用在着色器中循环太久
+pub const MAX_BLUR_RADIUS: u32 = 64;
+
+//3D LUT，data 按红色变化最快的顺序排列，共 size³ 个颜色
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    pub size: u32,
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    //不改变颜色的 LUT
+    pub fn identity(size: u32) -> Lut3d {
+        let scale = (size.max(2) - 1) as f32;
+        let mut data = Vec::with_capacity((size * size * size) as usize);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 / scale, g as f32 / scale, b as f32 / scale]);
+                }
+            }
+        }
+        Lut3d { size, data }
+    }
+
+    //读取 Adobe/Resolve 的 .cube 文件，只支持 LUT_3D_SIZE，DOMAIN_MIN/DOMAIN_MAX 必须是默认的 0 到 1
+    pub fn load(path: &Path) -> Result<Lut3d, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+        Lut3d::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Lut3d, String> {
+        let mut size = None;
+        let mut data = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            match keyword {
+                "LUT_3D_SIZE" => match words.next().and_then(|value| value.parse::<u32>().ok()) {
+                    Some(value) if (2..=256).contains(&value) => size = Some(value),
+                    _ => return Err(format!("line {}: LUT_3D_SIZE expects a number from 2 to 256", number + 1)),
+                },
+                "LUT_1D_SIZE" => return Err("1D LUTs are not supported".to_string()),
+                "TITLE" | "DOMAIN_MIN" | "DOMAIN_MAX" | "LUT_1D_INPUT_RANGE" | "LUT_3D_INPUT_RANGE" => {}
+                _ => {
+                    let values: Option<Vec<f32>> = line.split_whitespace().map(|value| value.parse().ok()).collect();
+                    match values.as_deref() {
+                        Some([r, g, b]) => data.push([*r, *g, *b]),
+                        _ => return Err(format!("line {}: expected three numbers, found \"{line}\"", number + 1)),
+                    }
+                }
+            }
+        }
+        let size = size.ok_or_else(|| "missing LUT_3D_SIZE".to_string())?;
+        if data.len() != (size * size * size) as usize {
+            return Err(format!("expected {} colors for LUT_3D_SIZE {size}, found {}", size * size * size, data.len()));
+        }
+        Ok(Lut3d { size, data })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Filter {
+    //可分离的高斯模糊，标准差是半径的三分之一
+    GaussianBlur { radius: u32 },
+    BoxBlur { radius: u32 },
+    //亮度的梯度大小，输出灰度图像，scale 是梯度的放大倍数
+    Sobel { scale: f32 },
+    Sharpen { amount: f32 },
+    //brightness 加在颜色上，contrast 以 0.5 为中心缩放，最后做 1 / gamma 次方
+    Adjust { brightness: f32, contrast: f32, gamma: f32 },
+    Grayscale,
+    //亮度不小于 level 时为白色，否则为黑色
+    Threshold { level: f32 },
+    Lut(Arc<Lut3d>),
+}
+
+impl Filter {
+    //命令行的写法：blur:5、box:3、sobel、sharpen:0.5、brightness:0.1、contrast:1.2、gamma:2.2、grayscale、threshold:0.5、lut:grade.cube
+    pub fn parse(spec: &str) -> Result<Filter, String> {
+        let (name, argument) = match spec.split_once(':') {
+            Some((name, argument)) => (name, Some(argument)),
+            None => (spec, None),
+        };
+        let number = |default: Option<f32>| -> Result<f32, String> {
+            match argument {
+                Some(value) => value.parse().map_err(|_| format!("{name} expects a number, found \"{value}\"")),
+                None => default.ok_or_else(|| format!("{name} expects a value, for example {name}:1")),
+            }
+        };
+        let radius = || -> Result<u32, String> {
+            match number(Some(2.0))? {
+                value if (0.0..=MAX_BLUR_RADIUS as f32).contains(&value) => Ok(value.round() as u32),
+                _ => Err(format!("{name} expects a radius from 0 to {MAX_BLUR_RADIUS}")),
+            }
+        };
+        match name {
+            "blur" | "gaussian" => Ok(Filter::GaussianBlur { radius: radius()? }),
+            "box" => Ok(Filter::BoxBlur { radius: radius()? }),
+            "sobel" => Ok(Filter::Sobel { scale: number(Some(0.25))? }),
+            "sharpen" => Ok(Filter::Sharpen { amount: number(Some(1.0))? }),
+            "brightness" => Ok(Filter::Adjust { brightness: number(None)?, contrast: 1.0, gamma: 1.0 }),
+            "contrast" => Ok(Filter::Adjust { brightness: 0.0, contrast: number(None)?, gamma: 1.0 }),
+            "gamma" => match number(None)? {
+                gamma if gamma > 0.0 => Ok(Filter::Adjust { brightness: 0.0, contrast: 1.0, gamma }),
+                _ => Err("gamma expects a positive number".to_string()),
+            },
+            "grayscale" | "gray" => Ok(Filter::Grayscale),
+            "threshold" => Ok(Filter::Threshold { level: number(Some(0.5))? }),
+            "lut" => match argument {
+                Some(path) => Ok(Filter::Lut(Arc::new(Lut3d::load(Path::new(path))?))),
+                None => Err("lut expects a .cube file, for example lut:grade.cube".to_string()),
+            },
+            _ => Err(format!("unknown filter \"{name}\"")),
+        }
+    }
+}
+
+//滤镜使用的设备和管线，只创建一次，图像按输入的大小每次创建
+pub struct ImageFilters {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    unpack: Arc<ComputePipeline>,
+    pack: Arc<ComputePipeline>,
+    convolve: Arc<ComputePipeline>,
+    neighborhood: Arc<ComputePipeline>,
+    pointwise: Arc<ComputePipeline>,
+    lut: Arc<ComputePipeline>,
+}
+
+impl Default for ImageFilters {
+    fn default() -> Self {
+        ImageFilters::new()
+    }
+}
+
+impl ImageFilters {
+    pub fn new() -> ImageFilters {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+
+        let pipeline_cache = SharedPipelineCache::new(device.clone());
+        let create_pipeline = |shader: Arc<ShaderModule>| {
+            ComputePipeline::new(
+                device.clone(),
+                shader.entry_point("main").unwrap(),
+                &(),
+                Some(pipeline_cache.cache()),
+                |_| {},
+            ).expect("failed to create compute pipeline")
+        };
+        let unpack = create_pipeline(unpack::load(device.clone()).expect("failed to create shader module"));
+        let pack = create_pipeline(pack::load(device.clone()).expect("failed to create shader module"));
+        let convolve = create_pipeline(convolve::load(device.clone()).expect("failed to create shader module"));
+        let neighborhood = create_pipeline(neighborhood::load(device.clone()).expect("failed to create shader module"));
+        let pointwise = create_pipeline(pointwise::load(device.clone()).expect("failed to create shader module"));
+        let lut = create_pipeline(lut::load(device.clone()).expect("failed to create shader module"));
+
+        ImageFilters {
+            memory_allocator: Arc::new(create_memory_allocator(device.clone())),
+            command_buffer_allocator: create_buffer_allocator(device.clone()),
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+            device,
+            queue,
+            unpack,
+            pack,
+            convolve,
+            neighborhood,
+            pointwise,
+            lut,
+        }
+    }
+
+    //按顺序应用所有滤镜，filters 为空时返回原图像
+    pub fn apply(&self, image: &RgbaImage, filters: &[Filter]) -> Result<RgbaImage, String> {
+        check_image_size(image)?;
+        let (width, height) = image.dimensions();
+
+        //每个像素打包成一个 u32，R 在最低的字节，和 unpackUnorm4x8/packUnorm4x8 相同
+        let input = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Upload,
+            image.pixels().map(|pixel| u32::from_le_bytes(pixel.0)),
+        );
+        let output = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Download,
+            (0..width * height).map(|_| 0u32),
+        );
+        let views = [(); 2].map(|_| {
+            let image = StorageImage::new(
+                &self.memory_allocator,
+                ImageDimensions::Dim2d {
+                    width,
+                    height,
+                    array_layers: 1,
+                },
+                Format::R32G32B32A32_SFLOAT,
+                Some(self.queue.queue_family_index()),
+            ).unwrap();
+            ImageView::new_default(image).unwrap()
+        });
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        let groups = [(width + 7) / 8, (height + 7) / 8, 1];
+        //从当前结果所在的图像读，写到另一张图像
+        let targets = |current: usize| {
+            vec![
+                WriteDescriptorSet::image_view(0, views[current].clone()),
+                WriteDescriptorSet::image_view(1, views[1 - current].clone()),
+            ]
+        };
+
+        self.bind(&mut builder, &self.unpack, vec![WriteDescriptorSet::buffer(0, input), WriteDescriptorSet::image_view(1, views[0].clone())]);
+        builder.dispatch(groups).unwrap();
+        let mut current = 0;
+        for filter in filters {
+            match filter {
+                //分成水平和竖直两遍
+                Filter::GaussianBlur { radius } | Filter::BoxBlur { radius } => {
+                    let sigma = match filter {
+                        Filter::GaussianBlur { .. } => (*radius as f32 / 3.0).max(0.5),
+                        _ => 0.0,
+                    };
+                    for direction in [[1, 0], [0, 1]] {
+                        self.bind(&mut builder, &self.convolve, targets(current));
+                        builder
+                            .push_constants(
+                                self.convolve.layout().clone(),
+                                0,
+                                convolve::PushConstants { direction, radius: *radius as i32, sigma },
+                            )
+                            .dispatch(groups)
+                            .unwrap();
+                        current = 1 - current;
+                    }
+                    continue;
+                }
+                Filter::Sobel { scale } => self.record_neighborhood(
+                    &mut builder,
+                    targets(current),
+                    groups,
+                    neighborhood::PushConstants { mode: 0, amount: *scale },
+                ),
+                Filter::Sharpen { amount } => self.record_neighborhood(
+                    &mut builder,
+                    targets(current),
+                    groups,
+                    neighborhood::PushConstants { mode: 1, amount: *amount },
+                ),
+                Filter::Adjust { brightness, contrast, gamma } => self.record_pointwise(
+                    &mut builder,
+                    targets(current),
+                    groups,
+                    pointwise::PushConstants { mode: 0, brightness: *brightness, contrast: *contrast, gamma: *gamma, threshold: 0.0 },
+                ),
+                Filter::Grayscale => self.record_pointwise(
+                    &mut builder,
+                    targets(current),
+                    groups,
+                    pointwise::PushConstants { mode: 1, brightness: 0.0, contrast: 1.0, gamma: 1.0, threshold: 0.0 },
+                ),
+                Filter::Threshold { level } => self.record_pointwise(
+                    &mut builder,
+                    targets(current),
+                    groups,
+                    pointwise::PushConstants { mode: 2, brightness: 0.0, contrast: 1.0, gamma: 1.0, threshold: *level },
+                ),
+                Filter::Lut(table) => {
+                    //std430 中 vec4 数组的步长是 16 字节，每个颜色补一个分量
+                    let entries = create_iter_buffer(
+                        self.memory_allocator.clone(),
+                        BufferUsage::STORAGE_BUFFER,
+                        MemoryUsage::Upload,
+                        table.data.iter().map(|[r, g, b]| [*r, *g, *b, 1.0f32]),
+                    );
+                    let mut writes = targets(current);
+                    writes.push(WriteDescriptorSet::buffer(2, entries));
+                    self.bind(&mut builder, &self.lut, writes);
+                    builder
+                        .push_constants(self.lut.layout().clone(), 0, lut::PushConstants { size: table.size })
+                        .dispatch(groups)
+                        .unwrap();
+                }
+            }
+            current = 1 - current;
+        }
+        self.bind(&mut builder, &self.pack, vec![WriteDescriptorSet::image_view(0, views[current].clone()), WriteDescriptorSet::buffer(1, output.clone())]);
+        builder.dispatch(groups).unwrap();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let content = output.read().unwrap();
+        let bytes = content.iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+        Ok(RgbaImage::from_raw(width, height, bytes).unwrap())
+    }
+
+    fn bind(&self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, pipeline: &Arc<ComputePipeline>, writes: Vec<WriteDescriptorSet>) {
+        let set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            writes,
+        ).unwrap();
+        builder
+            .bind_pipeline_compute(pipeline.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline.layout().clone(), 0, set);
+    }
+
+    fn record_neighborhood(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        targets: Vec<WriteDescriptorSet>,
+        groups: [u32; 3],
+        push_constants: neighborhood::PushConstants,
+    ) {
+        self.bind(builder, &self.neighborhood, targets);
+        builder
+            .push_constants(self.neighborhood.layout().clone(), 0, push_constants)
+            .dispatch(groups)
+            .unwrap();
+    }
+
+    fn record_pointwise(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        targets: Vec<WriteDescriptorSet>,
+        groups: [u32; 3],
+        push_constants: pointwise::PushConstants,
+    ) {
+        self.bind(builder, &self.pointwise, targets);
+        builder
+            .push_constants(self.pointwise.layout().clone(), 0, push_constants)
+            .dispatch(groups)
+            .unwrap();
+    }
+}
+
+//宽或高为0的图像无法创建 GPU 图像，返回错误
+fn check_image_size(image: &RgbaImage) -> Result<(), String> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Err(format!("cannot filter an empty {width}x{height} image"));
+    }
+    Ok(())
+}
+
+//读取 input，应用滤镜后保存到 output
+pub fn filter_file(input: &Path, output: &Path, filters: &[Filter]) -> Result<(), String> {
+    let image = image::open(input)
+        .map_err(|e| format!("failed to load {}: {e}", input.display()))?
+        .to_rgba8();
+    let result = ImageFilters::new()
+        .apply(&image, filters)
+        .map_err(|e| format!("{}: {e}", input.display()))?;
+    result.save(output).map_err(|e| format!("failed to save {}: {e}", output.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cube_with_comments_and_blank_lines() {
+        let text = "# graded in Resolve\nTITLE \"warm\"\n\nLUT_3D_SIZE 2\nDOMAIN_MIN 0 0 0\nDOMAIN_MAX 1 1 1\n\n\
+                    0 0 0\n1 0 0\n  # comment between entries\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n\n1 1 1\n";
+        assert_eq!(Lut3d::parse(text), Ok(Lut3d::identity(2)));
+    }
+
+    #[test]
+    fn rejects_cube_with_wrong_entry_count() {
+        let missing = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n";
+        assert_eq!(Lut3d::parse(missing), Err(String::from("expected 8 colors for LUT_3D_SIZE 2, found 2")));
+        let extra = format!("LUT_3D_SIZE 2\n{}", "0.5 0.5 0.5\n".repeat(9));
+        assert_eq!(Lut3d::parse(&extra), Err(String::from("expected 8 colors for LUT_3D_SIZE 2, found 9")));
+    }
+
+    #[test]
+    fn rejects_invalid_cube_lines() {
+        assert_eq!(Lut3d::parse("LUT_1D_SIZE 16\n"), Err(String::from("1D LUTs are not supported")));
+        assert_eq!(Lut3d::parse("0 0 0\n"), Err(String::from("missing LUT_3D_SIZE")));
+        assert_eq!(
+            Lut3d::parse("LUT_3D_SIZE 1\n"),
+            Err(String::from("line 1: LUT_3D_SIZE expects a number from 2 to 256"))
+        );
+        assert_eq!(
+            Lut3d::parse("LUT_3D_SIZE 2\n\n0 0\n"),
+            Err(String::from("line 3: expected three numbers, found \"0 0\""))
+        );
+    }
+
+    #[test]
+    fn parses_blur_radius() {
+        assert_eq!(Filter::parse("blur:5"), Ok(Filter::GaussianBlur { radius: 5 }));
+        assert_eq!(Filter::parse("blur"), Ok(Filter::GaussianBlur { radius: 2 }));
+        assert_eq!(Filter::parse("box:0"), Ok(Filter::BoxBlur { radius: 0 }));
+        let out_of_range = Err(format!("blur expects a radius from 0 to {MAX_BLUR_RADIUS}"));
+        assert_eq!(Filter::parse("blur:-1"), out_of_range);
+        assert_eq!(Filter::parse(&format!("blur:{}", MAX_BLUR_RADIUS + 1)), out_of_range);
+        assert_eq!(Filter::parse("blur:wide"), Err(String::from("blur expects a number, found \"wide\"")));
+    }
+
+    #[test]
+    fn rejects_non_positive_gamma() {
+        assert_eq!(Filter::parse("gamma:2.2"), Ok(Filter::Adjust { brightness: 0.0, contrast: 1.0, gamma: 2.2 }));
+        assert_eq!(Filter::parse("gamma:0"), Err(String::from("gamma expects a positive number")));
+        assert_eq!(Filter::parse("gamma:-1"), Err(String::from("gamma expects a positive number")));
+        assert_eq!(Filter::parse("gamma"), Err(String::from("gamma expects a value, for example gamma:1")));
+    }
+
+    #[test]
+    fn rejects_unknown_filter() {
+        assert_eq!(Filter::parse("emboss:2"), Err(String::from("unknown filter \"emboss\"")));
+        assert_eq!(Filter::parse(""), Err(String::from("unknown filter \"\"")));
+    }
+
+    #[test]
+    fn empty_image_is_an_error() {
+        assert_eq!(check_image_size(&RgbaImage::new(0, 0)), Err(String::from("cannot filter an empty 0x0 image")));
+        assert_eq!(check_image_size(&RgbaImage::new(4, 0)), Err(String::from("cannot filter an empty 4x0 image")));
+        assert_eq!(check_image_size(&RgbaImage::new(1, 1)), Ok(()));
+    }
+}
@@ -0,0 +1,249 @@
+//图像滤镜的计算着色器
+//中间结果保存在 rgba32f 存储图像中，链式滤镜之间不会量化到 8 位，只有输入和输出是打包的 RGBA8 存储缓冲区
+
+//打包的 RGBA8 缓冲区解开到浮点图像
+pub mod unpack {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0) readonly buffer Pixels {
+                uint pixels[];
+            } src;
+
+            layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D dst;
+
+            void main() {
+                ivec2 size = imageSize(dst);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+                imageStore(dst, p, unpackUnorm4x8(src.pixels[p.y * size.x + p.x]));
+            }
+        ",
+    }
+}
+
+//浮点图像打包回 RGBA8 缓冲区
+pub mod pack {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform readonly image2D src;
+
+            layout(set = 0, binding = 1) writeonly buffer Pixels {
+                uint pixels[];
+            } dst;
+
+            void main() {
+                ivec2 size = imageSize(src);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+                dst.pixels[p.y * size.x + p.x] = packUnorm4x8(clamp(imageLoad(src, p), 0.0, 1.0));
+            }
+        ",
+    }
+}
+
+//一维卷积，高斯模糊和方框模糊都分成水平和竖直两遍
+pub mod convolve {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform readonly image2D src;
+            layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D dst;
+
+            layout(push_constant) uniform PushConstants {
+                //(1, 0) 是水平方向，(0, 1) 是竖直方向
+                ivec2 direction;
+                int radius;
+                //高斯的标准差，0 表示方框模糊
+                float sigma;
+            } push;
+
+            void main() {
+                ivec2 size = imageSize(src);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+
+                vec4 sum = vec4(0.0);
+                float weight_sum = 0.0;
+                for (int i = -push.radius; i <= push.radius; i++) {
+                    float weight = push.sigma > 0.0 ? exp(-float(i * i) / (2.0 * push.sigma * push.sigma)) : 1.0;
+                    //超出边界的坐标取最近的边缘像素
+                    ivec2 q = clamp(p + push.direction * i, ivec2(0), size - 1);
+                    sum += imageLoad(src, q) * weight;
+                    weight_sum += weight;
+                }
+                imageStore(dst, p, sum / weight_sum);
+            }
+        ",
+    }
+}
+
+//3x3 邻域的滤镜：Sobel 边缘检测和锐化
+pub mod neighborhood {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform readonly image2D src;
+            layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D dst;
+
+            layout(push_constant) uniform PushConstants {
+                //0 是 Sobel，1 是锐化
+                uint mode;
+                float amount;
+            } push;
+
+            vec4 load(ivec2 p) {
+                return imageLoad(src, clamp(p, ivec2(0), imageSize(src) - 1));
+            }
+
+            float luma(vec4 color) {
+                return dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+            }
+
+            void main() {
+                ivec2 size = imageSize(src);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+
+                vec4 center = load(p);
+                vec4 result;
+                if (push.mode == 0) {
+                    float l[9];
+                    for (int j = 0; j < 3; j++) {
+                        for (int i = 0; i < 3; i++) {
+                            l[j * 3 + i] = luma(load(p + ivec2(i - 1, j - 1)));
+                        }
+                    }
+                    float gx = (l[2] + 2.0 * l[5] + l[8]) - (l[0] + 2.0 * l[3] + l[6]);
+                    float gy = (l[6] + 2.0 * l[7] + l[8]) - (l[0] + 2.0 * l[1] + l[2]);
+                    //梯度大小最大为 4 * sqrt(2)，乘 amount 以后截断
+                    float magnitude = clamp(length(vec2(gx, gy)) * push.amount, 0.0, 1.0);
+                    result = vec4(vec3(magnitude), center.a);
+                } else {
+                    vec4 neighbors = load(p + ivec2(-1, 0)) + load(p + ivec2(1, 0)) + load(p + ivec2(0, -1)) + load(p + ivec2(0, 1));
+                    result = vec4(center.rgb * (1.0 + 4.0 * push.amount) - neighbors.rgb * push.amount, center.a);
+                }
+                imageStore(dst, p, result);
+            }
+        ",
+    }
+}
+
+//逐像素的颜色调整：亮度/对比度/伽马、灰度和阈值
+pub mod pointwise {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform readonly image2D src;
+            layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D dst;
+
+            layout(push_constant) uniform PushConstants {
+                //0 是亮度/对比度/伽马，1 是灰度，2 是阈值
+                uint mode;
+                float brightness;
+                float contrast;
+                float gamma;
+                float threshold;
+            } push;
+
+            void main() {
+                ivec2 size = imageSize(src);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+
+                vec4 color = imageLoad(src, p);
+                float luma = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+                if (push.mode == 0) {
+                    vec3 adjusted = (color.rgb - 0.5) * push.contrast + 0.5 + push.brightness;
+                    color.rgb = pow(max(adjusted, vec3(0.0)), vec3(1.0 / push.gamma));
+                } else if (push.mode == 1) {
+                    color.rgb = vec3(luma);
+                } else {
+                    color.rgb = vec3(step(push.threshold, luma));
+                }
+                imageStore(dst, p, color);
+            }
+        ",
+    }
+}
+
+//3D LUT 调色，LUT 在存储缓冲区中，红色变化最快，三线性插值
+pub mod lut {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba32f) uniform readonly image2D src;
+            layout(set = 0, binding = 1, rgba32f) uniform writeonly image2D dst;
+
+            layout(set = 0, binding = 2) readonly buffer Table {
+                vec4 entries[];
+            } table;
+
+            layout(push_constant) uniform PushConstants {
+                //每个方向的格点数
+                uint size;
+            } push;
+
+            vec3 entry(ivec3 i) {
+                return table.entries[(i.b * push.size + i.g) * push.size + i.r].rgb;
+            }
+
+            void main() {
+                ivec2 size = imageSize(src);
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                if (p.x >= size.x || p.y >= size.y) {
+                    return;
+                }
+
+                vec4 color = imageLoad(src, p);
+                vec3 position = clamp(color.rgb, 0.0, 1.0) * float(push.size - 1);
+                ivec3 i0 = ivec3(floor(position));
+                ivec3 i1 = min(i0 + 1, ivec3(push.size - 1));
+                vec3 f = position - vec3(i0);
+
+                vec3 c00 = mix(entry(i0), entry(ivec3(i1.r, i0.g, i0.b)), f.r);
+                vec3 c10 = mix(entry(ivec3(i0.r, i1.g, i0.b)), entry(ivec3(i1.r, i1.g, i0.b)), f.r);
+                vec3 c01 = mix(entry(ivec3(i0.r, i0.g, i1.b)), entry(ivec3(i1.r, i0.g, i1.b)), f.r);
+                vec3 c11 = mix(entry(ivec3(i0.r, i1.g, i1.b)), entry(i1), f.r);
+                vec3 c0 = mix(c00, c10, f.g);
+                vec3 c1 = mix(c01, c11, f.g);
+                imageStore(dst, p, vec4(mix(c0, c1, f.b), color.a));
+            }
+        ",
+    }
+}
@@ -1,5 +1,5 @@
 use std::sync::Arc;
-use image::{ImageBuffer, Rgba};
+use image::RgbaImage;
 use vulkano::buffer::BufferUsage;
 use vulkano::command_buffer::CopyImageToBufferInfo;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
@@ -13,8 +13,15 @@ use vulkano::sync;
 use vulkano::sync::GpuFuture;
 use crate::example::buffer::{choose_device, create_auto_command_buffer_builder, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
 use crate::example::image_glsl::shader;
+use crate::example::pipeline_cache::SharedPipelineCache;
 
 pub fn operator_image_shader() {
+    render_image_shader().save("image_shader.png").unwrap();
+    println!("Everything is succeeded!");
+}
+
+//计算着色器生成的 1024x1024 Mandelbrot 图像，golden 测试和 image_shader.png 比较
+pub fn render_image_shader() -> RgbaImage {
     //创建一个实例
     let instance = create_instance();
     //选择一个物理设备
@@ -44,11 +51,12 @@ pub fn operator_image_shader() {
 
     //创建描述符集
     let shader = shader::load(device.clone()).expect("failed to create shader module");
+    let pipeline_cache = SharedPipelineCache::new(device.clone());
     let compute_pipeline = ComputePipeline::new(
         device.clone(),
         shader.entry_point("main").unwrap(),
         &(),
-        None,
+        Some(pipeline_cache.cache()),
         |_| {},
     ).expect("failed to create compute pipeline");
 
@@ -101,7 +109,5 @@ pub fn operator_image_shader() {
     future.wait(None).unwrap();
 
     let content = buffer.read().unwrap();
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &content[..]).unwrap();
-    image.save("image_shader.png").unwrap();
-    println!("Everything is succeeded!");
+    RgbaImage::from_raw(1024, 1024, content.to_vec()).unwrap()
 }
\ No newline at end of file
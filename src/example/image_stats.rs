@@ -0,0 +1,411 @@
+//GPU 上的图像直方图和统计量
+//和 operator_image_shader 一样把图像放在存储图像中，用描述符集绑定给计算着色器：
+//histogram 着色器在共享内存中原子计数，得到每个通道的直方图和用于自动曝光的对数亮度直方图，
+//reduce 着色器按工作组归约出最小值、最大值、和与平方和，平均值和方差由 CPU 合并所有工作组的结果后计算
+use std::path::Path;
+use std::sync::Arc;
+use image::{Rgba, RgbaImage};
+use vulkano::buffer::BufferUsage;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyBufferToImageInfo};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageDimensions, StorageImage};
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
+use crate::example::image_stats_glsl::{histogram, reduce};
+use crate::example::pipeline_cache::SharedPipelineCache;
+
+//着色器中共享内存的大小决定了桶数的上限
+pub const MAX_BINS: u32 = 256;
+//两个着色器的工作组都是 16x16
+const GROUP_SIZE: u32 = 16;
+
+#[derive(Clone, Copy, Debug)]
+pub struct HistogramSettings {
+    //每个直方图的桶数，2 到 MAX_BINS
+    pub bins: u32,
+    //亮度直方图覆盖的 log2 亮度范围，小于 log_min 的亮度计入第 1 个桶，大于 log_max 的计入最后一个桶
+    pub log_luminance_range: [f32; 2],
+}
+
+impl Default for HistogramSettings {
+    fn default() -> Self {
+        HistogramSettings {
+            bins: 256,
+            log_luminance_range: [-10.0, 0.0],
+        }
+    }
+}
+
+//一个通道的统计量，范围是 0 到 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ChannelStats {
+    pub min: f32,
+    pub max: f32,
+    pub mean: f64,
+    pub variance: f64,
+}
+
+impl ChannelStats {
+    pub fn standard_deviation(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+//线性亮度的 log2 直方图，第 0 个桶是接近黑色（亮度小于 0.00001）的像素，
+//其余的桶把 [log_min, log_max] 等分
+#[derive(Clone, Debug, PartialEq)]
+pub struct LuminanceHistogram {
+    pub counts: Vec<u32>,
+    pub log_min: f32,
+    pub log_max: f32,
+}
+
+impl LuminanceHistogram {
+    //不计黑色像素的平均 log2 亮度，按桶的中心计算，没有其他像素时返回 log_min
+    pub fn average_log_luminance(&self) -> f32 {
+        let step = (self.log_max - self.log_min) / (self.counts.len() - 1) as f32;
+        let mut weighted = 0.0f64;
+        let mut total = 0u64;
+        for (i, count) in self.counts.iter().enumerate().skip(1) {
+            weighted += (self.log_min + step * (i as f32 - 0.5)) as f64 * *count as f64;
+            total += *count as u64;
+        }
+        if total == 0 {
+            self.log_min
+        } else {
+            (weighted / total as f64) as f32
+        }
+    }
+
+    //自动曝光的倍数，把平均亮度映射到 key（常用 0.18 的中灰）
+    pub fn exposure(&self, key: f32) -> f32 {
+        key / self.average_log_luminance().exp2()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageStats {
+    pub width: u32,
+    pub height: u32,
+    //R、G、B、A 的直方图
+    pub histograms: [Vec<u32>; 4],
+    pub luminance: LuminanceHistogram,
+    pub channels: [ChannelStats; 4],
+}
+
+impl ImageStats {
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{}x{}, {} bins", self.width, self.height, self.histograms[0].len());
+        for (name, channel) in ["R", "G", "B", "A"].iter().zip(&self.channels) {
+            summary.push_str(&format!(
+                "\n{name} min {:.4} max {:.4} mean {:.4} stddev {:.4}",
+                channel.min,
+                channel.max,
+                channel.mean,
+                channel.standard_deviation(),
+            ));
+        }
+        summary.push_str(&format!(
+            "\naverage log2 luminance {:.3}, exposure for middle gray {:.3}",
+            self.luminance.average_log_luminance(),
+            self.luminance.exposure(0.18),
+        ));
+        summary
+    }
+}
+
+//R、G、B 直方图叠加画出的图像，三个通道相加，重叠的部分变亮；纵轴按最大的计数归一化
+pub fn plot_histogram(stats: &ImageStats, width: u32, height: u32) -> RgbaImage {
+    let largest = stats.histograms[..3].iter().flatten().copied().max().unwrap_or(0).max(1);
+    let bins = stats.histograms[0].len() as u32;
+    let mut plot = RgbaImage::from_pixel(width, height, Rgba([24, 24, 24, 255]));
+    for x in 0..width {
+        let bin = (x * bins / width) as usize;
+        for (c, histogram) in stats.histograms[..3].iter().enumerate() {
+            let bar = (histogram[bin] as u64 * height as u64 / largest as u64) as u32;
+            for y in height - bar..height {
+                let pixel = plot.get_pixel_mut(x, y);
+                pixel[c] = pixel[c].saturating_add(200);
+            }
+        }
+    }
+    plot
+}
+
+//设备和管线只创建一次，图像和缓冲区按图像大小每次创建
+pub struct GpuImageStats {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    histogram: Arc<ComputePipeline>,
+    reduce: Arc<ComputePipeline>,
+}
+
+impl Default for GpuImageStats {
+    fn default() -> Self {
+        GpuImageStats::new()
+    }
+}
+
+impl GpuImageStats {
+    pub fn new() -> GpuImageStats {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let (device, mut queues) = create_device(physical_device, queue_index);
+        let queue = queues.next().unwrap();
+
+        let pipeline_cache = SharedPipelineCache::new(device.clone());
+        let histogram_shader = histogram::load(device.clone()).expect("failed to create shader module");
+        let histogram = ComputePipeline::new(
+            device.clone(),
+            histogram_shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+        let reduce_shader = reduce::load(device.clone()).expect("failed to create shader module");
+        let reduce = ComputePipeline::new(
+            device.clone(),
+            reduce_shader.entry_point("main").unwrap(),
+            &(),
+            Some(pipeline_cache.cache()),
+            |_| {},
+        ).expect("failed to create compute pipeline");
+
+        GpuImageStats {
+            memory_allocator: Arc::new(create_memory_allocator(device.clone())),
+            command_buffer_allocator: create_buffer_allocator(device.clone()),
+            descriptor_set_allocator: StandardDescriptorSetAllocator::new(device.clone()),
+            device,
+            queue,
+            histogram,
+            reduce,
+        }
+    }
+
+    //桶数或亮度范围无效、图像为空时返回 Err
+    pub fn analyze(&self, image: &RgbaImage, settings: &HistogramSettings) -> Result<ImageStats, String> {
+        check_input(image, settings)?;
+        let bins = settings.bins;
+        let [log_min, log_max] = settings.log_luminance_range;
+        let (width, height) = image.dimensions();
+        let groups = [(width + GROUP_SIZE - 1) / GROUP_SIZE, (height + GROUP_SIZE - 1) / GROUP_SIZE, 1];
+
+        let storage_image = StorageImage::new(
+            &self.memory_allocator,
+            ImageDimensions::Dim2d {
+                width,
+                height,
+                array_layers: 1,
+            },
+            Format::R8G8B8A8_UNORM,
+            Some(self.queue.queue_family_index()),
+        ).unwrap();
+        let view = ImageView::new_default(storage_image.clone()).unwrap();
+        let upload = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::TRANSFER_SRC,
+            MemoryUsage::Upload,
+            image.as_raw().iter().copied(),
+        );
+        //从零开始计数
+        let counts = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Download,
+            (0..5 * bins).map(|_| 0u32),
+        );
+        //和着色器中的 Partial 相同：sum、sum_squares、min_value、max_value 四个 vec4
+        let partials = create_iter_buffer(
+            self.memory_allocator.clone(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryUsage::Download,
+            (0..groups[0] * groups[1]).map(|_| [0.0f32; 16]),
+        );
+
+        let histogram_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.histogram.layout().set_layouts().get(0).unwrap().clone(),
+            [WriteDescriptorSet::image_view(0, view.clone()), WriteDescriptorSet::buffer(1, counts.clone())],
+        ).unwrap();
+        let reduce_set = PersistentDescriptorSet::new(
+            &self.descriptor_set_allocator,
+            self.reduce.layout().set_layouts().get(0).unwrap().clone(),
+            [WriteDescriptorSet::image_view(0, view), WriteDescriptorSet::buffer(1, partials.clone())],
+        ).unwrap();
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        builder
+            .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(upload, storage_image))
+            .unwrap()
+            .bind_pipeline_compute(self.histogram.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, self.histogram.layout().clone(), 0, histogram_set)
+            .push_constants(self.histogram.layout().clone(), 0, histogram::PushConstants { bins, log_min, log_max })
+            .dispatch(groups)
+            .unwrap()
+            .bind_pipeline_compute(self.reduce.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Compute, self.reduce.layout().clone(), 0, reduce_set)
+            .dispatch(groups)
+            .unwrap();
+
+        sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), builder.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let counts = counts.read().unwrap();
+        let channel_histogram = |i: u32| counts[(i * bins) as usize..((i + 1) * bins) as usize].to_vec();
+
+        let partials = partials.read().unwrap();
+        let pixel_count = (width as u64 * height as u64).max(1) as f64;
+        let channels = std::array::from_fn(|c| {
+            let mut sum = 0.0f64;
+            let mut sum_squares = 0.0f64;
+            let mut min = 1.0f32;
+            let mut max = 0.0f32;
+            for partial in partials.iter() {
+                sum += partial[c] as f64;
+                sum_squares += partial[4 + c] as f64;
+                min = min.min(partial[8 + c]);
+                max = max.max(partial[12 + c]);
+            }
+            let mean = sum / pixel_count;
+            ChannelStats {
+                min,
+                max,
+                mean,
+                variance: (sum_squares / pixel_count - mean * mean).max(0.0),
+            }
+        });
+
+        Ok(ImageStats {
+            width,
+            height,
+            histograms: std::array::from_fn(|c| channel_histogram(c as u32)),
+            luminance: LuminanceHistogram {
+                counts: channel_histogram(4),
+                log_min,
+                log_max,
+            },
+            channels,
+        })
+    }
+}
+
+//宽或高为0的图像无法创建 GPU 图像和缓冲区
+fn check_input(image: &RgbaImage, settings: &HistogramSettings) -> Result<(), String> {
+    if !(2..=MAX_BINS).contains(&settings.bins) {
+        return Err(format!("the number of bins must be from 2 to {MAX_BINS}"));
+    }
+    let [log_min, log_max] = settings.log_luminance_range;
+    if log_min >= log_max {
+        return Err("the luminance range must be increasing".to_string());
+    }
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return Err(format!("cannot analyze an empty {width}x{height} image"));
+    }
+    Ok(())
+}
+
+//读取 path 统计，plot 不为 None 时保存直方图图像
+pub fn analyze_file(path: &Path, settings: &HistogramSettings, plot: Option<&Path>) -> Result<ImageStats, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("failed to load {}: {e}", path.display()))?
+        .to_rgba8();
+    let stats = GpuImageStats::new().analyze(&image, settings)?;
+    if let Some(plot) = plot {
+        plot_histogram(&stats, 512, 256)
+            .save(plot)
+            .map_err(|e| format!("failed to save {}: {e}", plot.display()))?;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luminance(counts: Vec<u32>) -> LuminanceHistogram {
+        LuminanceHistogram { counts, log_min: -4.0, log_max: 0.0 }
+    }
+
+    fn stats(red: Vec<u32>, green: Vec<u32>, blue: Vec<u32>) -> ImageStats {
+        let alpha = vec![0; red.len()];
+        ImageStats {
+            width: 1,
+            height: 1,
+            histograms: [red, green, blue, alpha],
+            luminance: luminance(vec![0; 5]),
+            channels: [ChannelStats::default(); 4],
+        }
+    }
+
+    #[test]
+    fn average_log_luminance_uses_bin_centers() {
+        //4 个桶等分 [-4, 0]，第 1 个桶的中心是 -3.5，第 4 个是 -0.5，第 0 个桶的黑色像素不计入
+        let histogram = luminance(vec![100, 1, 0, 0, 1]);
+        assert_eq!(histogram.average_log_luminance(), -2.0);
+        assert!((histogram.exposure(0.18) - 0.72).abs() < 1e-6);
+
+        assert_eq!(luminance(vec![0, 0, 3, 0, 0]).average_log_luminance(), -2.5);
+        assert_eq!(luminance(vec![0, 0, 0, 0, 2]).average_log_luminance(), -0.5);
+    }
+
+    #[test]
+    fn empty_histogram_falls_back_to_log_min() {
+        for counts in [vec![0; 5], vec![7, 0, 0, 0, 0]] {
+            let histogram = luminance(counts);
+            assert_eq!(histogram.average_log_luminance(), -4.0);
+            assert!((histogram.exposure(0.18) - 2.88).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn plots_histogram_bars() {
+        //4 列对应 2 个桶，柱高按最大的计数 10 归一化
+        let plot = plot_histogram(&stats(vec![10, 5], vec![0, 10], vec![0, 0]), 4, 10);
+        assert_eq!(plot.dimensions(), (4, 10));
+        assert_eq!(plot.get_pixel(0, 0).0, [224, 24, 24, 255]);
+        assert_eq!(plot.get_pixel(1, 9).0, [224, 24, 24, 255]);
+        //第 2 个桶的红色只有一半高，和绿色重叠的部分两个通道都变亮
+        assert_eq!(plot.get_pixel(2, 4).0, [24, 224, 24, 255]);
+        assert_eq!(plot.get_pixel(3, 5).0, [224, 224, 24, 255]);
+    }
+
+    #[test]
+    fn plots_empty_histogram_as_background() {
+        let plot = plot_histogram(&stats(vec![0; 4], vec![0; 4], vec![0; 4]), 8, 4);
+        assert!(plot.pixels().all(|pixel| pixel.0 == [24, 24, 24, 255]));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let settings = HistogramSettings::default();
+        assert_eq!(check_input(&RgbaImage::new(1, 1), &settings), Ok(()));
+        assert_eq!(check_input(&RgbaImage::new(0, 0), &settings), Err(String::from("cannot analyze an empty 0x0 image")));
+        assert_eq!(check_input(&RgbaImage::new(3, 0), &settings), Err(String::from("cannot analyze an empty 3x0 image")));
+        let one_bin = HistogramSettings { bins: 1, ..settings };
+        assert_eq!(check_input(&RgbaImage::new(1, 1), &one_bin), Err(format!("the number of bins must be from 2 to {MAX_BINS}")));
+        let reversed = HistogramSettings { log_luminance_range: [0.0, -10.0], ..settings };
+        assert_eq!(check_input(&RgbaImage::new(1, 1), &reversed), Err(String::from("the luminance range must be increasing")));
+    }
+}
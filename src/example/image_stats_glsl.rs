@@ -0,0 +1,144 @@
+//图像直方图和统计量的计算着色器，输入和 operator_image_shader 一样是 rgba8 存储图像
+
+//R、G、B、A 四个通道和对数亮度的直方图
+//每个工作组先在共享内存中原子计数，再把不为零的计数加到全局缓冲区，减少全局原子操作的冲突
+pub mod histogram {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D img;
+
+            //五个直方图依次排列：R、G、B、A、亮度，每个 push.bins 个计数
+            layout(set = 0, binding = 1) buffer Histogram {
+                uint counts[];
+            } histogram;
+
+            layout(push_constant) uniform PushConstants {
+                uint bins;
+                //亮度直方图覆盖的 log2 亮度范围，第 0 个桶保留给接近黑色的像素
+                float log_min;
+                float log_max;
+            } push;
+
+            const uint MAX_BINS = 256;
+            const uint HISTOGRAMS = 5;
+            const uint THREADS = 256;
+
+            shared uint local_counts[HISTOGRAMS * MAX_BINS];
+
+            vec3 srgb_to_linear(vec3 c) {
+                return mix(c / 12.92, pow((c + 0.055) / 1.055, vec3(2.4)), greaterThan(c, vec3(0.04045)));
+            }
+
+            uint channel_bin(float value) {
+                return min(uint(value * float(push.bins)), push.bins - 1);
+            }
+
+            uint luminance_bin(vec3 color) {
+                float luminance = dot(srgb_to_linear(color), vec3(0.2126, 0.7152, 0.0722));
+                if (luminance < 0.00001) {
+                    return 0;
+                }
+                float t = clamp((log2(luminance) - push.log_min) / (push.log_max - push.log_min), 0.0, 1.0);
+                return 1 + min(uint(t * float(push.bins - 1)), push.bins - 2);
+            }
+
+            void main() {
+                uint count = HISTOGRAMS * push.bins;
+                for (uint i = gl_LocalInvocationIndex; i < count; i += THREADS) {
+                    local_counts[i] = 0;
+                }
+                barrier();
+
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                ivec2 size = imageSize(img);
+                if (p.x < size.x && p.y < size.y) {
+                    vec4 color = imageLoad(img, p);
+                    for (uint c = 0; c < 4; c++) {
+                        atomicAdd(local_counts[c * push.bins + channel_bin(color[c])], 1);
+                    }
+                    atomicAdd(local_counts[4 * push.bins + luminance_bin(color.rgb)], 1);
+                }
+                barrier();
+
+                for (uint i = gl_LocalInvocationIndex; i < count; i += THREADS) {
+                    if (local_counts[i] != 0) {
+                        atomicAdd(histogram.counts[i], local_counts[i]);
+                    }
+                }
+            }
+        ",
+    }
+}
+
+//每个工作组归约出各通道的和、平方和、最小值和最大值，CPU 再把所有工作组的结果合起来
+pub mod reduce {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        src: r"
+            #version 460
+
+            layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
+
+            layout(set = 0, binding = 0, rgba8) uniform readonly image2D img;
+
+            struct Partial {
+                vec4 sum;
+                vec4 sum_squares;
+                vec4 min_value;
+                vec4 max_value;
+            };
+
+            //每个工作组一项，按行排列
+            layout(set = 0, binding = 1) writeonly buffer Partials {
+                Partial partials[];
+            } result;
+
+            const uint THREADS = 256;
+
+            shared vec4 sums[THREADS];
+            shared vec4 sum_squares[THREADS];
+            shared vec4 min_values[THREADS];
+            shared vec4 max_values[THREADS];
+
+            void main() {
+                uint i = gl_LocalInvocationIndex;
+                ivec2 p = ivec2(gl_GlobalInvocationID.xy);
+                ivec2 size = imageSize(img);
+                //图像以外的线程不影响结果
+                if (p.x < size.x && p.y < size.y) {
+                    vec4 color = imageLoad(img, p);
+                    sums[i] = color;
+                    sum_squares[i] = color * color;
+                    min_values[i] = color;
+                    max_values[i] = color;
+                } else {
+                    sums[i] = vec4(0.0);
+                    sum_squares[i] = vec4(0.0);
+                    min_values[i] = vec4(1.0);
+                    max_values[i] = vec4(0.0);
+                }
+                barrier();
+
+                for (uint stride = THREADS / 2; stride > 0; stride /= 2) {
+                    if (i < stride) {
+                        sums[i] += sums[i + stride];
+                        sum_squares[i] += sum_squares[i + stride];
+                        min_values[i] = min(min_values[i], min_values[i + stride]);
+                        max_values[i] = max(max_values[i], max_values[i + stride]);
+                    }
+                    barrier();
+                }
+
+                if (i == 0) {
+                    uint group = gl_WorkGroupID.y * gl_NumWorkGroups.x + gl_WorkGroupID.x;
+                    result.partials[group] = Partial(sums[0], sum_squares[0], min_values[0], max_values[0]);
+                }
+            }
+        ",
+    }
+}
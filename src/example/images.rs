@@ -7,9 +7,15 @@ use vulkano::memory::allocator::MemoryUsage;
 use vulkano::sync;
 use vulkano::sync::GpuFuture;
 use crate::example::buffer::{choose_device, create_auto_command_buffer_builder, create_buffer_allocator, create_device, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
-use image::{ImageBuffer, Rgba};
+use image::RgbaImage;
 
 pub fn operator_image() {
+    render_image().save("image.png").unwrap();
+    println!("Everything succeeded!");
+}
+
+//清除成蓝色的 1024x1024 图像，golden 测试和 image.png 比较
+pub fn render_image() -> RgbaImage {
     let instance = create_instance();
     let physical_device = choose_device(instance.clone());
     let queue_index = get_queue(physical_device.clone());
@@ -70,8 +76,5 @@ pub fn operator_image() {
     future.wait(None).unwrap();
 
     let content = buf.read().unwrap();
-    let image = ImageBuffer::<Rgba<u8>, _>::from_raw(1024, 1024, &content[..]).unwrap();
-
-    image.save("image.png").unwrap();
-    println!("Everything succeeded!");
+    RgbaImage::from_raw(1024, 1024, content.to_vec()).unwrap()
 }
\ No newline at end of file
@@ -0,0 +1,459 @@
+//输入状态
+//从 winit 的 WindowEvent 和 DeviceEvent 中收集按键、鼠标按钮、鼠标移动、滚轮和修饰键的状态，
+//每帧结束时调用 end_frame 清空这一帧的增量。
+//程序逻辑通过动作（Action）查询输入，动作和按键的对应关系可以从配置文件加载
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use winit::event::{DeviceEvent, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use crate::example::config::load_config;
+
+//触控板的像素滚动换算成行数
+const PIXELS_PER_LINE: f32 = 40.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(VirtualKeyCode),
+    Mouse(MouseButton),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    //自由相机加速移动
+    Faster,
+    //按住时拖动鼠标旋转相机
+    Rotate,
+    //按住时拖动鼠标平移相机
+    Pan,
+    ToggleCamera,
+    ResetCamera,
+    ToggleWireframe,
+    Screenshot,
+    //打开一个新窗口查看同一个场景
+    NewWindow,
+    ToggleFullscreen,
+    //显示或隐藏调试线条
+    ToggleDebug,
+    //显示或隐藏帧率和帧时间
+    ToggleHud,
+}
+
+impl Action {
+    pub const ALL: [Action; 17] = [
+        Action::MoveForward,
+        Action::MoveBackward,
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::Faster,
+        Action::Rotate,
+        Action::Pan,
+        Action::ToggleCamera,
+        Action::ResetCamera,
+        Action::ToggleWireframe,
+        Action::Screenshot,
+        Action::NewWindow,
+        Action::ToggleFullscreen,
+        Action::ToggleDebug,
+        Action::ToggleHud,
+    ];
+
+    //配置文件中使用的名字
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Faster => "faster",
+            Action::Rotate => "rotate",
+            Action::Pan => "pan",
+            Action::ToggleCamera => "toggle_camera",
+            Action::ResetCamera => "reset_camera",
+            Action::ToggleWireframe => "toggle_wireframe",
+            Action::Screenshot => "screenshot",
+            Action::NewWindow => "new_window",
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::ToggleDebug => "toggle_debug",
+            Action::ToggleHud => "toggle_hud",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+}
+
+//动作到按键的绑定，一个动作可以绑定多个按键
+#[derive(Clone, Debug)]
+pub struct InputBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for InputBindings {
+    fn default() -> Self {
+        use VirtualKeyCode as Key;
+        let bindings = [
+            (Action::MoveForward, vec![Binding::Key(Key::W), Binding::Key(Key::Up)]),
+            (Action::MoveBackward, vec![Binding::Key(Key::S), Binding::Key(Key::Down)]),
+            (Action::MoveLeft, vec![Binding::Key(Key::A), Binding::Key(Key::Left)]),
+            (Action::MoveRight, vec![Binding::Key(Key::D), Binding::Key(Key::Right)]),
+            (Action::MoveUp, vec![Binding::Key(Key::E), Binding::Key(Key::Space)]),
+            (Action::MoveDown, vec![Binding::Key(Key::Q), Binding::Key(Key::LControl)]),
+            (Action::Faster, vec![Binding::Key(Key::LShift), Binding::Key(Key::RShift)]),
+            (Action::Rotate, vec![Binding::Mouse(MouseButton::Left)]),
+            (Action::Pan, vec![Binding::Mouse(MouseButton::Middle), Binding::Mouse(MouseButton::Right)]),
+            (Action::ToggleCamera, vec![Binding::Key(Key::C)]),
+            (Action::ResetCamera, vec![Binding::Key(Key::R)]),
+            (Action::ToggleWireframe, vec![Binding::Key(Key::Z)]),
+            (Action::Screenshot, vec![Binding::Key(Key::F12)]),
+            (Action::NewWindow, vec![Binding::Key(Key::N)]),
+            (Action::ToggleFullscreen, vec![Binding::Key(Key::F11)]),
+            (Action::ToggleDebug, vec![Binding::Key(Key::G)]),
+            (Action::ToggleHud, vec![Binding::Key(Key::F3)]),
+        ];
+        InputBindings {
+            bindings: bindings.into_iter().collect(),
+        }
+    }
+}
+
+impl InputBindings {
+    //从配置文件加载，每行 动作 = 按键[, 按键...]，文件中没有出现的动作使用默认绑定
+    pub fn load(path: &Path) -> Result<InputBindings, String> {
+        let mut bindings = InputBindings::default();
+        for entry in load_config(path)? {
+            let action = Action::from_name(&entry.key)
+                .ok_or_else(|| format!("{}: line {}: unknown action {}", path.display(), entry.line, entry.key))?;
+            let keys = entry
+                .value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(|name| {
+                    parse_binding(name)
+                        .ok_or_else(|| format!("{}: line {}: unknown key {name}", path.display(), entry.line))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            bindings.bindings.insert(action, keys);
+        }
+        Ok(bindings)
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+//输入状态关心的事件，winit 的事件先转换成它再处理，录制和回放也使用它
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InputEvent {
+    Button { binding: Binding, pressed: bool },
+    MouseMotion { delta: [f32; 2] },
+    //向上为正，单位是行
+    MouseWheel { lines: f32 },
+    CursorMoved { position: [f32; 2] },
+    Modifiers(ModifiersState),
+    Focused(bool),
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<InputEvent> {
+        match event {
+            WindowEvent::KeyboardInput {
+                input: KeyboardInput {
+                    state,
+                    virtual_keycode: Some(key),
+                    ..
+                },
+                ..
+            } => Some(InputEvent::Button {
+                binding: Binding::Key(*key),
+                pressed: *state == ElementState::Pressed,
+            }),
+            WindowEvent::MouseInput { state, button, .. } => Some(InputEvent::Button {
+                binding: Binding::Mouse(*button),
+                pressed: *state == ElementState::Pressed,
+            }),
+            WindowEvent::MouseWheel { delta, .. } => Some(InputEvent::MouseWheel {
+                lines: match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                },
+            }),
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                position: [position.x as f32, position.y as f32],
+            }),
+            WindowEvent::ModifiersChanged(modifiers) => Some(InputEvent::Modifiers(*modifiers)),
+            WindowEvent::Focused(focused) => Some(InputEvent::Focused(*focused)),
+            _ => None,
+        }
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<InputEvent> {
+        match event {
+            DeviceEvent::MouseMotion { delta } => Some(InputEvent::MouseMotion {
+                delta: [delta.0 as f32, delta.1 as f32],
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InputState {
+    down: HashSet<Binding>,
+    //这一帧中刚按下的键和按钮，不包含按住时的自动重复
+    pressed: HashSet<Binding>,
+    //这一帧中鼠标移动的距离，来自 DeviceEvent，不受窗口边界限制
+    pub mouse_delta: [f32; 2],
+    //这一帧中滚轮滚动的行数，向上为正
+    pub wheel_delta: f32,
+    pub modifiers: ModifiersState,
+    pub cursor_position: [f32; 2],
+}
+
+impl InputState {
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        if let Some(event) = InputEvent::from_window_event(event) {
+            self.apply(&event);
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let Some(event) = InputEvent::from_device_event(event) {
+            self.apply(&event);
+        }
+    }
+
+    pub fn apply(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::Button { binding, pressed: true } => {
+                if self.down.insert(binding) {
+                    self.pressed.insert(binding);
+                }
+            }
+            InputEvent::Button { binding, pressed: false } => {
+                self.down.remove(&binding);
+            }
+            InputEvent::MouseMotion { delta } => {
+                self.mouse_delta[0] += delta[0];
+                self.mouse_delta[1] += delta[1];
+            }
+            InputEvent::MouseWheel { lines } => {
+                self.wheel_delta += lines;
+            }
+            InputEvent::CursorMoved { position } => {
+                self.cursor_position = position;
+            }
+            InputEvent::Modifiers(modifiers) => {
+                self.modifiers = modifiers;
+            }
+            //失去焦点后收不到松开按键的事件，所有按键都当作已经松开
+            InputEvent::Focused(false) => {
+                self.down.clear();
+                self.modifiers = ModifiersState::empty();
+            }
+            InputEvent::Focused(true) => {}
+        }
+    }
+
+    //每帧处理完输入后调用
+    pub fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.mouse_delta = [0.0; 2];
+        self.wheel_delta = 0.0;
+    }
+
+    pub fn is_down(&self, binding: Binding) -> bool {
+        self.down.contains(&binding)
+    }
+
+    pub fn was_pressed(&self, binding: Binding) -> bool {
+        self.pressed.contains(&binding)
+    }
+
+    pub fn is_action_down(&self, bindings: &InputBindings, action: Action) -> bool {
+        bindings.get(action).iter().any(|binding| self.is_down(*binding))
+    }
+
+    pub fn was_action_pressed(&self, bindings: &InputBindings, action: Action) -> bool {
+        bindings.get(action).iter().any(|binding| self.was_pressed(*binding))
+    }
+
+    //两个相反动作合成的轴，返回 -1、0 或 1
+    pub fn axis(&self, bindings: &InputBindings, negative: Action, positive: Action) -> f32 {
+        let mut value = 0.0;
+        if self.is_action_down(bindings, negative) {
+            value -= 1.0;
+        }
+        if self.is_action_down(bindings, positive) {
+            value += 1.0;
+        }
+        value
+    }
+}
+
+//按键名：字母、数字、F1-F12、方向键和常用的功能键，鼠标按钮写作 MouseLeft/MouseRight/MouseMiddle/Mouse<N>，不区分大小写
+pub fn parse_binding(name: &str) -> Option<Binding> {
+    use VirtualKeyCode as Key;
+    const LETTERS: [VirtualKeyCode; 26] = [
+        Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+        Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    ];
+    const DIGITS: [VirtualKeyCode; 10] = [
+        Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    ];
+    const FUNCTION_KEYS: [VirtualKeyCode; 12] = [
+        Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12,
+    ];
+
+    let name = name.to_lowercase();
+    //VirtualKeyCode 的数字键叫 Key0-Key9
+    let name = match name.strip_prefix("key") {
+        Some(digit) if digit.len() == 1 => digit.to_string(),
+        _ => name,
+    };
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if c.is_ascii_lowercase() {
+            return Some(Binding::Key(LETTERS[(c as u8 - b'a') as usize]));
+        }
+        if c.is_ascii_digit() {
+            return Some(Binding::Key(DIGITS[(c as u8 - b'0') as usize]));
+        }
+    }
+    if let Some(number) = name.strip_prefix('f').and_then(|number| number.parse::<usize>().ok()) {
+        return FUNCTION_KEYS.get(number.wrapping_sub(1)).map(|key| Binding::Key(*key));
+    }
+    if let Some(number) = name.strip_prefix("mouse").and_then(|number| number.parse::<u16>().ok()) {
+        return Some(Binding::Mouse(MouseButton::Other(number)));
+    }
+
+    let binding = match name.as_str() {
+        "mouseleft" => Binding::Mouse(MouseButton::Left),
+        "mouseright" => Binding::Mouse(MouseButton::Right),
+        "mousemiddle" => Binding::Mouse(MouseButton::Middle),
+        "space" => Binding::Key(Key::Space),
+        "escape" | "esc" => Binding::Key(Key::Escape),
+        "tab" => Binding::Key(Key::Tab),
+        "enter" | "return" => Binding::Key(Key::Return),
+        "backspace" | "back" => Binding::Key(Key::Back),
+        "up" => Binding::Key(Key::Up),
+        "down" => Binding::Key(Key::Down),
+        "left" => Binding::Key(Key::Left),
+        "right" => Binding::Key(Key::Right),
+        "lshift" => Binding::Key(Key::LShift),
+        "rshift" => Binding::Key(Key::RShift),
+        "lcontrol" | "lctrl" => Binding::Key(Key::LControl),
+        "rcontrol" | "rctrl" => Binding::Key(Key::RControl),
+        "lalt" => Binding::Key(Key::LAlt),
+        "ralt" => Binding::Key(Key::RAlt),
+        "pageup" => Binding::Key(Key::PageUp),
+        "pagedown" => Binding::Key(Key::PageDown),
+        "home" => Binding::Key(Key::Home),
+        "end" => Binding::Key(Key::End),
+        "insert" => Binding::Key(Key::Insert),
+        "delete" => Binding::Key(Key::Delete),
+        _ => return None,
+    };
+    Some(binding)
+}
+
+//parse_binding 能够解析的名字，无法绑定的按键返回 None
+pub fn binding_name(binding: Binding) -> Option<String> {
+    let name = match binding {
+        Binding::Mouse(MouseButton::Left) => String::from("MouseLeft"),
+        Binding::Mouse(MouseButton::Right) => String::from("MouseRight"),
+        Binding::Mouse(MouseButton::Middle) => String::from("MouseMiddle"),
+        Binding::Mouse(MouseButton::Other(number)) => format!("Mouse{number}"),
+        //VirtualKeyCode 的 Debug 名字和 parse_binding 使用的名字一致，解析回来相同才说明支持这个键
+        Binding::Key(key) => format!("{key:?}"),
+    };
+    (parse_binding(&name) == Some(binding)).then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    //写一个临时的按键配置文件并加载
+    fn load_bindings(name: &str, text: &str) -> Result<InputBindings, String> {
+        let path = std::env::temp_dir().join(format!("my_winit-{name}-{}.cfg", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let bindings = InputBindings::load(&path);
+        let _ = fs::remove_file(&path);
+        bindings
+    }
+
+    fn assert_round_trips(names: &[&str]) {
+        for name in names {
+            assert_eq!(parse_binding(name).and_then(binding_name).as_deref(), Some(*name), "{name}");
+        }
+    }
+
+    #[test]
+    fn key_names_round_trip() {
+        assert_round_trips(&[
+            "A", "W", "Z", "Key0", "Key9", "F1", "F12", "Space", "Escape", "Tab", "Return", "Back", "Up", "Down",
+            "Left", "Right", "PageUp", "PageDown", "Home", "End", "Insert", "Delete",
+        ]);
+    }
+
+    #[test]
+    fn mouse_button_names_round_trip() {
+        assert_round_trips(&["MouseLeft", "MouseRight", "MouseMiddle", "Mouse4", "Mouse12"]);
+    }
+
+    #[test]
+    fn modifier_names_round_trip() {
+        assert_round_trips(&["LShift", "RShift", "LControl", "RControl", "LAlt", "RAlt"]);
+
+        //一个动作绑定多个修饰键，名字不区分大小写，写回时使用规范的名字
+        let bindings = load_bindings("modifiers", "faster = lshift, RCTRL ,LAlt\n").unwrap();
+        let names: Vec<_> = bindings.get(Action::Faster).iter().filter_map(|binding| binding_name(*binding)).collect();
+        assert_eq!(names, ["LShift", "RControl", "LAlt"]);
+    }
+
+    #[test]
+    fn aliases_parse_to_canonical_names() {
+        for (alias, name) in [("w", "W"), ("0", "Key0"), ("esc", "Escape"), ("enter", "Return"), ("lctrl", "LControl"), ("f3", "F3")] {
+            assert_eq!(parse_binding(alias).and_then(binding_name).as_deref(), Some(name), "{alias}");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        for name in ["", "F0", "F13", "Mouse", "MouseX", "Ctrl+S", "AB", "Key10"] {
+            assert_eq!(parse_binding(name), None, "{name}");
+        }
+        //parse_binding 不支持的键没有名字
+        assert_eq!(binding_name(Binding::Key(VirtualKeyCode::Numpad0)), None);
+    }
+
+    #[test]
+    fn missing_actions_keep_default_bindings() {
+        let bindings = load_bindings("defaults", "# only the screenshot key\n\nscreenshot = P\n").unwrap();
+        assert_eq!(bindings.get(Action::Screenshot), [Binding::Key(VirtualKeyCode::P)]);
+        assert_eq!(bindings.get(Action::ToggleHud), InputBindings::default().get(Action::ToggleHud));
+    }
+
+    #[test]
+    fn rejects_malformed_binding_lines() {
+        let error = load_bindings("unknown-action", "screenshot = F12\njump = Space\n").unwrap_err();
+        assert!(error.ends_with("line 2: unknown action jump"), "{error}");
+        let error = load_bindings("unknown-key", "screenshot = F12, F13\n").unwrap_err();
+        assert!(error.ends_with("line 1: unknown key F13"), "{error}");
+        let error = load_bindings("no-value", "screenshot F12\n").unwrap_err();
+        assert!(error.ends_with("line 1: expected key = value"), "{error}");
+    }
+}
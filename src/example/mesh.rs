@@ -0,0 +1,281 @@
+//网格数据
+//所有模型加载器（OBJ、glTF等）都输出同一种 Mesh：一组顶点、一组索引，以及按材质划分的索引区间
+use std::path::Path;
+use std::sync::Arc;
+use image::RgbaImage;
+use vulkano::buffer::BufferContents;
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use crate::example::pipeline_desc::BlendMode;
+use crate::example::scene::Scene;
+
+pub const WHITE: [f32; 4] = [1.0; 4];
+
+//网格顶点数据
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct MeshVertex {
+    #[format(R32G32B32_SFLOAT)]
+    pub position: [f32; 3],
+    #[format(R32G32B32_SFLOAT)]
+    pub normal: [f32; 3],
+    #[format(R32G32_SFLOAT)]
+    pub uv: [f32; 2],
+    //顶点颜色，会与材质的基础颜色相乘，没有顶点颜色的格式填白色
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+//金属度-粗糙度（metallic-roughness）PBR材质，和 glTF 2.0 的材质模型一致
+//OBJ 的漫反射颜色和贴图对应 base_color，金属度为0
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    //sRGB 编码的基础颜色贴图
+    pub base_color_texture: Option<Arc<RgbaImage>>,
+    //线性编码，G 通道是粗糙度，B 通道是金属度
+    pub metallic_roughness_texture: Option<Arc<RgbaImage>>,
+    //不是 Opaque 的材质在半透明阶段按从远到近的顺序绘制
+    pub blend: BlendMode,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            name: String::from("default"),
+            base_color: [0.8, 0.8, 0.8, 1.0],
+            metallic: 0.0,
+            roughness: 0.8,
+            emissive: [0.0; 3],
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            blend: BlendMode::Opaque,
+        }
+    }
+}
+
+//网格中的一个分组，对应索引缓冲区中的一段区间，使用同一个材质绘制
+#[derive(Clone, Debug)]
+pub struct MeshGroup {
+    pub name: String,
+    pub first_index: u32,
+    pub index_count: u32,
+    //场景材质列表中的索引
+    pub material: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Mesh {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+    pub groups: Vec<MeshGroup>,
+    //图元拓扑，默认是三角形列表，没有面的点云使用点列表
+    pub topology: PrimitiveTopology,
+}
+
+impl Mesh {
+    //包围盒，返回 (最小点, 最大点)
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        if self.vertices.is_empty() {
+            return ([0.0; 3], [0.0; 3]);
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in &self.vertices {
+            for i in 0..3 {
+                min[i] = min[i].min(vertex.position[i]);
+                max[i] = max[i].max(vertex.position[i]);
+            }
+        }
+        (min, max)
+    }
+}
+
+//之前窗口中绘制的那个三角形
+pub fn triangle_mesh() -> Mesh {
+    let normal = [0.0, 0.0, 1.0];
+    Mesh {
+        vertices: vec![
+            MeshVertex { position: [-0.5, -0.5, 0.0], normal, uv: [0.0, 0.0], color: WHITE },
+            MeshVertex { position: [0.0, 0.5, 0.0], normal, uv: [0.5, 1.0], color: WHITE },
+            MeshVertex { position: [0.5, -0.25, 0.0], normal, uv: [1.0, 0.0], color: WHITE },
+        ],
+        indices: vec![0, 1, 2],
+        groups: vec![MeshGroup {
+            name: String::from("triangle"),
+            first_index: 0,
+            index_count: 3,
+            material: 0,
+        }],
+        topology: PrimitiveTopology::TriangleList,
+    }
+}
+
+//三角形使用的红色材质
+pub fn triangle_material() -> Material {
+    Material {
+        name: String::from("red"),
+        base_color: [1.0, 0.0, 0.0, 1.0],
+        ..Default::default()
+    }
+}
+
+//加载 Wavefront OBJ 文件（以及它引用的 MTL 材质文件）
+//OBJ 中的每个 o/g 分组会成为一个 MeshGroup，所有分组共用一个顶点缓冲区和索引缓冲区
+pub fn load_obj(path: &Path) -> Result<Scene, String> {
+    let (models, materials) = tobj::load_obj(
+        path,
+        &tobj::LoadOptions {
+            //OBJ 中位置、法线和纹理坐标各自有索引，这里合并成一个索引，才能用于索引缓冲区
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        },
+    ).map_err(|e| format!("failed to load {}: {e}", path.display()))?;
+
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut mesh = Mesh::default();
+    let mut mesh_materials = Vec::new();
+
+    //MTL 文件缺失时仍然可以显示几何体，只是使用默认材质
+    match materials {
+        Ok(materials) => {
+            for material in materials {
+                let diffuse = material.diffuse.unwrap_or([0.8, 0.8, 0.8]);
+                let dissolve = material.dissolve.unwrap_or(1.0);
+                mesh_materials.push(Material {
+                    name: material.name,
+                    base_color: [diffuse[0], diffuse[1], diffuse[2], dissolve],
+                    base_color_texture: material
+                        .diffuse_texture
+                        .and_then(|texture| load_image(&base_dir.join(texture))),
+                    //d 小于1表示材质是半透明的
+                    blend: if dissolve < 1.0 { BlendMode::Alpha } else { BlendMode::Opaque },
+                    ..Default::default()
+                });
+            }
+        }
+        Err(e) => println!("failed to load materials of {}: {e}", path.display()),
+    }
+    let default_material = mesh_materials.len();
+    mesh_materials.push(Material::default());
+
+    for model in models {
+        let obj_mesh = model.mesh;
+        let base_vertex = mesh.vertices.len() as u32;
+        let vertex_count = obj_mesh.positions.len() / 3;
+
+        for i in 0..vertex_count {
+            let position = [
+                obj_mesh.positions[i * 3],
+                obj_mesh.positions[i * 3 + 1],
+                obj_mesh.positions[i * 3 + 2],
+            ];
+            let normal = if obj_mesh.normals.len() >= (i + 1) * 3 {
+                [obj_mesh.normals[i * 3], obj_mesh.normals[i * 3 + 1], obj_mesh.normals[i * 3 + 2]]
+            } else {
+                [0.0; 3]
+            };
+            //OBJ 的纹理坐标原点在左下角，Vulkan 在左上角
+            let uv = if obj_mesh.texcoords.len() >= (i + 1) * 2 {
+                [obj_mesh.texcoords[i * 2], 1.0 - obj_mesh.texcoords[i * 2 + 1]]
+            } else {
+                [0.0; 2]
+            };
+            let color = if obj_mesh.vertex_color.len() >= (i + 1) * 3 {
+                [obj_mesh.vertex_color[i * 3], obj_mesh.vertex_color[i * 3 + 1], obj_mesh.vertex_color[i * 3 + 2], 1.0]
+            } else {
+                WHITE
+            };
+            mesh.vertices.push(MeshVertex { position, normal, uv, color });
+        }
+
+        let first_index = mesh.indices.len() as u32;
+        mesh.indices.extend(obj_mesh.indices.iter().map(|index| base_vertex + index));
+
+        if obj_mesh.normals.is_empty() {
+            compute_normals(&mut mesh.vertices[base_vertex as usize..], &obj_mesh.indices);
+        }
+
+        mesh.groups.push(MeshGroup {
+            name: model.name,
+            first_index,
+            index_count: obj_mesh.indices.len() as u32,
+            material: obj_mesh
+                .material_id
+                .filter(|id| *id < default_material)
+                .unwrap_or(default_material),
+        });
+    }
+
+    if mesh.indices.is_empty() {
+        return Err(format!("{} contains no faces", path.display()));
+    }
+
+    Ok(Scene::from_mesh(mesh, mesh_materials))
+}
+
+//加载贴图图片，失败时只打印错误，材质会退回到没有贴图的情况
+pub fn load_image(path: &Path) -> Option<Arc<RgbaImage>> {
+    match image::open(path) {
+        Ok(image) => Some(Arc::new(image.to_rgba8())),
+        Err(e) => {
+            println!("failed to load texture {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+//文件中没有法线时，用相邻三角形的面法线求和得到平滑的顶点法线
+//indices 是相对于 vertices 起始位置的索引
+pub fn compute_normals(vertices: &mut [MeshVertex], indices: &[u32]) {
+    let mut normals = vec![[0.0f32; 3]; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let a = vertices[triangle[0] as usize].position;
+        let b = vertices[triangle[1] as usize].position;
+        let c = vertices[triangle[2] as usize].position;
+        //面法线的长度与三角形面积成正比，大三角形的权重更大
+        let normal = face_normal(a, b, c, false);
+        for index in triangle {
+            for i in 0..3 {
+                normals[*index as usize][i] += normal[i];
+            }
+        }
+    }
+
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        vertex.normal = normalize(normal);
+    }
+}
+
+//三角形 abc 的面法线（逆时针为正面），normalized 为 false 时保留与面积成正比的长度
+pub fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3], normalized: bool) -> [f32; 3] {
+    let ab = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let ac = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    let normal = [
+        ab[1] * ac[2] - ab[2] * ac[1],
+        ab[2] * ac[0] - ab[0] * ac[2],
+        ab[0] * ac[1] - ab[1] * ac[0],
+    ];
+    if normalized {
+        normalize(normal)
+    } else {
+        normal
+    }
+}
+
+fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+    if length > 0.0 {
+        [v[0] / length, v[1] / length, v[2] / length]
+    } else {
+        [0.0, 0.0, 1.0]
+    }
+}
@@ -0,0 +1,164 @@
+//网格管线着色器
+//set = 0 是每帧更新的 uniform 块（变换矩阵、时间和动画开关），set = 1 是材质贴图，
+//每次绘制的模型矩阵和材质参数通过推送常量传入
+//着色模型是 glTF 2.0 使用的金属度-粗糙度 PBR（Cook-Torrance GGX）
+pub mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 position;
+            layout(location = 1) in vec3 normal;
+            layout(location = 2) in vec2 uv;
+            layout(location = 3) in vec4 color;
+
+            layout(location = 0) out vec3 v_position;
+            layout(location = 1) out vec3 v_normal;
+            layout(location = 2) out vec2 v_uv;
+            layout(location = 3) out vec4 v_color;
+
+            layout(set = 0, binding = 0) uniform Data {
+                mat4 world;
+                mat4 view;
+                mat4 proj;
+                float time;
+                float point_size;
+                float scale_factor;
+                float animate;
+            } uniforms;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 model;
+                vec4 base_color;
+                vec4 emissive;
+                vec2 metallic_roughness;
+                float unlit;
+            } push;
+
+            void main() {
+                mat4 world_model = uniforms.world * push.model;
+                vec4 world_position = world_model * vec4(position, 1.0);
+                v_position = world_position.xyz;
+                //节点可能带有非均匀缩放，法线需要使用逆转置矩阵
+                v_normal = transpose(inverse(mat3(world_model))) * normal;
+                v_uv = uv;
+                v_color = color;
+                //只有点列表拓扑会使用点的大小，point_size 是逻辑像素，乘缩放比例换算成物理像素
+                gl_PointSize = uniforms.point_size * uniforms.scale_factor;
+                gl_Position = uniforms.proj * uniforms.view * world_position;
+            }
+        ",
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec3 v_position;
+            layout(location = 1) in vec3 v_normal;
+            layout(location = 2) in vec2 v_uv;
+            layout(location = 3) in vec4 v_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            layout(set = 0, binding = 0) uniform Data {
+                mat4 world;
+                mat4 view;
+                mat4 proj;
+                float time;
+                float point_size;
+                float scale_factor;
+                float animate;
+            } uniforms;
+
+            layout(set = 1, binding = 0) uniform sampler2D base_color_texture;
+            layout(set = 1, binding = 1) uniform sampler2D metallic_roughness_texture;
+
+            layout(push_constant) uniform PushConstants {
+                mat4 model;
+                vec4 base_color;
+                vec4 emissive;
+                vec2 metallic_roughness;
+                float unlit;
+            } push;
+
+            const float PI = 3.14159265359;
+            const vec3 LIGHT_DIRECTION = vec3(-0.3, -0.8, -0.5);
+            const vec3 LIGHT_COLOR = vec3(3.0);
+            const vec3 AMBIENT_COLOR = vec3(0.15);
+
+            float distribution_ggx(float n_dot_h, float roughness) {
+                float a = roughness * roughness;
+                float a2 = a * a;
+                float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+                return a2 / (PI * d * d);
+            }
+
+            float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+                float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+                float g_v = n_dot_v / (n_dot_v * (1.0 - k) + k);
+                float g_l = n_dot_l / (n_dot_l * (1.0 - k) + k);
+                return g_v * g_l;
+            }
+
+            vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+                return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+            }
+
+            void main() {
+                //线框覆盖层等不需要光照的绘制直接输出推送常量中的颜色
+                if (push.unlit > 0.5) {
+                    f_color = push.base_color;
+                    return;
+                }
+
+                vec4 base_color = push.base_color * v_color * texture(base_color_texture, v_uv);
+                //默认窗口的三角形颜色随时间闪烁
+                if (uniforms.animate > 0.5) {
+                    base_color.rgb *= 0.75 + 0.25 * sin(uniforms.time * 2.0);
+                }
+
+                //没有法线的点云不做光照，直接显示顶点颜色
+                if (dot(v_normal, v_normal) < 0.000001) {
+                    f_color = base_color;
+                    return;
+                }
+
+                vec4 metallic_roughness = texture(metallic_roughness_texture, v_uv);
+                float metallic = push.metallic_roughness.x * metallic_roughness.b;
+                float roughness = clamp(push.metallic_roughness.y * metallic_roughness.g, 0.04, 1.0);
+
+                vec3 camera_position = inverse(uniforms.view)[3].xyz;
+                vec3 n = normalize(v_normal);
+                vec3 v = normalize(camera_position - v_position);
+                //双面光照，CAD 导出的模型经常有朝向不一致的面
+                if (dot(n, v) < 0.0) {
+                    n = -n;
+                }
+                vec3 l = normalize(-LIGHT_DIRECTION);
+                vec3 h = normalize(v + l);
+
+                float n_dot_l = max(dot(n, l), 0.0);
+                float n_dot_v = max(dot(n, v), 0.0001);
+                float n_dot_h = max(dot(n, h), 0.0);
+                float h_dot_v = max(dot(h, v), 0.0);
+
+                vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+                vec3 f = fresnel_schlick(h_dot_v, f0);
+                float d = distribution_ggx(n_dot_h, roughness);
+                float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+                vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l + 0.0001);
+                vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base_color.rgb / PI;
+
+                vec3 color = (diffuse + specular) * LIGHT_COLOR * n_dot_l
+                    + AMBIENT_COLOR * base_color.rgb
+                    + push.emissive.rgb;
+                f_color = vec4(color, base_color.a);
+            }
+        ",
+    }
+}
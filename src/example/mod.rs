@@ -8,5 +8,42 @@ pub mod graphics_pipeline;
 pub mod vertex_glsl;
 pub mod fragment_glsl;
 pub mod pipeline_glsl;
+pub mod mesh_glsl;
+pub mod mesh;
+pub mod scene;
+pub mod gltf_scene;
+pub mod stl_mesh;
+pub mod ply_mesh;
+pub mod render_mesh;
+pub mod pipeline_desc;
+pub mod pipeline_cache;
+pub mod config;
+pub mod input;
+pub mod camera;
+pub mod viewer;
+pub mod recording;
+pub mod screenshot;
+pub mod fractal_glsl;
+pub mod fractal;
+pub mod export;
+pub mod window_config;
+pub mod debug_draw_glsl;
+pub mod debug_draw;
+pub mod text_glsl;
+pub mod text;
+pub mod hud;
+pub mod profiler;
+pub mod benchmark;
+pub mod software;
+pub mod image_diff_glsl;
+pub mod image_diff;
+pub mod image_filter_glsl;
+pub mod image_filter;
+pub mod image_stats_glsl;
+pub mod image_stats;
+pub mod hdr_glsl;
+pub mod hdr;
+pub mod golden;
+pub mod window_manager;
 
 pub mod windows;
\ No newline at end of file
@@ -0,0 +1,207 @@
+//磁盘上的管线缓存
+//创建管线时驱动需要把 SPIR-V 编译成 GPU 指令，把编译结果保存到文件，下次启动时直接读回来就不需要重新编译。
+//缓存文件名包含设备的 pipelineCacheUUID 和驱动版本，换了显卡或者升级驱动后会使用新的文件；
+//读取时还会检查缓存数据的文件头，不匹配时丢弃旧数据，从空缓存开始。
+//所有管线都通过 SharedPipelineCache 使用缓存：同一个设备共用一个 PipelineCache，每个文件在进程中只读一次，
+//释放时把编译结果留在内存里，程序退出前调用 save_pipeline_caches 一次写回磁盘
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::pipeline::cache::PipelineCache;
+
+//VK_PIPELINE_CACHE_HEADER_VERSION_ONE 的文件头：长度、版本、vendorID、deviceID、pipelineCacheUUID
+const HEADER_SIZE: usize = 32;
+const HEADER_VERSION_ONE: u32 = 1;
+
+//进程中的缓存数据，键是缓存文件的路径
+struct CacheFile {
+    //pipeline-<vendor>-<device>，写回时用来找到同一个设备旧驱动版本的文件
+    prefix: String,
+    data: Vec<u8>,
+    //有新的编译结果还没有写回文件
+    dirty: bool,
+}
+
+struct CacheStore {
+    files: BTreeMap<PathBuf, CacheFile>,
+    //正在使用的缓存，同一个设备再次请求时直接共用
+    live: Vec<Weak<PipelineCache>>,
+}
+
+static CACHE_STORE: Mutex<CacheStore> = Mutex::new(CacheStore { files: BTreeMap::new(), live: Vec::new() });
+
+//一个设备上所有管线共用的管线缓存，释放时把编译结果交回进程的缓存数据
+pub struct SharedPipelineCache {
+    cache: Arc<PipelineCache>,
+}
+
+impl SharedPipelineCache {
+    //同一个设备上已经有缓存时共用它，否则从进程中的数据或者缓存文件创建
+    pub fn new(device: Arc<Device>) -> SharedPipelineCache {
+        let mut store = CACHE_STORE.lock().unwrap();
+        store.live.retain(|cache| cache.strong_count() > 0);
+        let shared = store
+            .live
+            .iter()
+            .filter_map(Weak::upgrade)
+            .find(|cache| Arc::ptr_eq(cache.device(), &device));
+        if let Some(cache) = shared {
+            return SharedPipelineCache { cache };
+        }
+
+        let cache = load_pipeline_cache(&mut store, device);
+        store.live.push(Arc::downgrade(&cache));
+        SharedPipelineCache { cache }
+    }
+
+    pub fn cache(&self) -> Arc<PipelineCache> {
+        self.cache.clone()
+    }
+
+    //立即写回磁盘，用于不会正常返回的窗口事件循环
+    pub fn save(&self) {
+        store_pipeline_cache(&self.cache);
+        save_pipeline_caches();
+    }
+}
+
+impl Drop for SharedPipelineCache {
+    fn drop(&mut self) {
+        store_pipeline_cache(&self.cache);
+    }
+}
+
+//把还没有写回的缓存数据写到文件，同一个设备的旧驱动版本留下的文件会被删除
+pub fn save_pipeline_caches() {
+    let mut store = CACHE_STORE.lock().unwrap();
+    for (path, file) in store.files.iter_mut().filter(|(_, file)| file.dirty) {
+        write_cache_file(path, file);
+        file.dirty = false;
+    }
+}
+
+//从进程中的数据或者缓存文件创建管线缓存，文件不存在或者与当前设备不匹配时返回空缓存
+fn load_pipeline_cache(store: &mut CacheStore, device: Arc<Device>) -> Arc<PipelineCache> {
+    let Some(path) = get_pipeline_cache_path(device.physical_device()) else {
+        return PipelineCache::empty(device).expect("failed to create pipeline cache");
+    };
+
+    let file = store.files.entry(path).or_insert_with_key(|path| {
+        let data = fs::read(path)
+            .ok()
+            .filter(|data| {
+                let valid = is_header_valid(device.physical_device(), data);
+                if !valid {
+                    println!("pipeline cache was created by another device or driver, ignoring it");
+                }
+                valid
+            })
+            .unwrap_or_default();
+        CacheFile { prefix: get_device_prefix(device.physical_device()), data, dirty: false }
+    });
+
+    if file.data.is_empty() {
+        PipelineCache::empty(device).expect("failed to create pipeline cache")
+    } else {
+        //文件头已经检查过，数据来自同一个设备和驱动
+        unsafe { PipelineCache::with_data(device, &file.data) }.expect("failed to create pipeline cache")
+    }
+}
+
+//把管线缓存的数据留在进程中，等 save_pipeline_caches 写回
+fn store_pipeline_cache(cache: &PipelineCache) {
+    let physical_device = cache.device().physical_device();
+    let Some(path) = get_pipeline_cache_path(physical_device) else {
+        return;
+    };
+    let data = match cache.get_data() {
+        Ok(data) => data,
+        Err(e) => {
+            println!("failed to read pipeline cache data: {e}");
+            return;
+        }
+    };
+    let file = CacheFile { prefix: get_device_prefix(physical_device), data, dirty: true };
+    CACHE_STORE.lock().unwrap().files.insert(path, file);
+}
+
+fn write_cache_file(path: &Path, file: &CacheFile) {
+    let dir = path.parent().unwrap();
+    if let Err(e) = fs::create_dir_all(dir) {
+        println!("failed to create {}: {e}", dir.display());
+        return;
+    }
+
+    //只删除同一个设备其他驱动版本的 .bin 文件，其他进程正在写的临时文件不能删除
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let stale = name.starts_with(&file.prefix) && name.ends_with(".bin") && entry.path() != path;
+            if stale {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+
+    //先写到临时文件再重命名，避免程序中途退出留下不完整的缓存
+    //临时文件名包含进程号，同时运行的多个程序不会写同一个临时文件，重命名是原子的，最后完成的覆盖之前的
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    if let Err(e) = fs::write(&temp_path, &file.data).and_then(|_| fs::rename(&temp_path, path)) {
+        println!("failed to save pipeline cache to {}: {e}", path.display());
+    }
+}
+
+//<用户缓存目录>/my_winit/pipeline-<vendor>-<device>-<uuid>-<driver>.bin
+pub fn get_pipeline_cache_path(physical_device: &PhysicalDevice) -> Option<PathBuf> {
+    let properties = physical_device.properties();
+    let uuid: String = properties
+        .pipeline_cache_uuid
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let file_name = format!(
+        "{}-{uuid}-{:08x}.bin",
+        get_device_prefix(physical_device),
+        properties.driver_version,
+    );
+    get_cache_dir().map(|dir| dir.join("my_winit").join(file_name))
+}
+
+fn get_device_prefix(physical_device: &PhysicalDevice) -> String {
+    let properties = physical_device.properties();
+    format!("pipeline-{:04x}-{:04x}", properties.vendor_id, properties.device_id)
+}
+
+//各平台的用户缓存目录
+fn get_cache_dir() -> Option<PathBuf> {
+    let non_empty = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    if cfg!(target_os = "windows") {
+        non_empty("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        non_empty("HOME").map(|home| home.join("Library").join("Caches"))
+    } else {
+        non_empty("XDG_CACHE_HOME").or_else(|| non_empty("HOME").map(|home| home.join(".cache")))
+    }
+}
+
+//驱动应该自己检查缓存数据，但有些驱动收到其他设备的数据会崩溃，所以这里先检查一遍
+fn is_header_valid(physical_device: &PhysicalDevice, data: &[u8]) -> bool {
+    if data.len() < HEADER_SIZE {
+        return false;
+    }
+    let read_u32 = |offset: usize| {
+        u32::from_ne_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    };
+
+    let properties = physical_device.properties();
+    read_u32(0) as usize >= HEADER_SIZE
+        && read_u32(4) == HEADER_VERSION_ONE
+        && read_u32(8) == properties.vendor_id
+        && read_u32(12) == properties.device_id
+        && data[16..32] == properties.pipeline_cache_uuid
+}
@@ -0,0 +1,245 @@
+//管线描述和管线缓存
+//拓扑、多边形模式、剔除、线宽、深度偏移、混合都是图形管线的固定状态，改变任意一项都需要另一个管线，
+//所以用 PipelineDesc 描述需要的状态，由 MeshPipelines 按描述创建并缓存
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use vulkano::device::{Device, Features};
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, BlendFactor, BlendOp, ColorBlendState};
+use vulkano::pipeline::graphics::depth_stencil::DepthStencilState;
+use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
+use vulkano::pipeline::graphics::rasterization::{CullMode, DepthBias, FrontFace, PolygonMode, RasterizationState};
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::{GraphicsPipeline, StateMode};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::shader::ShaderModule;
+use crate::example::mesh::MeshVertex;
+use crate::example::mesh_glsl::{fs, vs};
+use crate::example::pipeline_cache::SharedPipelineCache;
+
+//颜色混合预设
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    //不混合，直接覆盖
+    #[default]
+    Opaque,
+    //src * a + dst * (1 - a)
+    Alpha,
+    //src + dst * (1 - a)，颜色需要已经乘过 alpha
+    PremultipliedAlpha,
+    //src * a + dst，用于发光、粒子
+    Additive,
+    //src * dst
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn is_opaque(&self) -> bool {
+        *self == BlendMode::Opaque
+    }
+
+    fn attachment_blend(&self) -> Option<AttachmentBlend> {
+        let (color_source, color_destination, alpha_source, alpha_destination) = match self {
+            BlendMode::Opaque => return None,
+            BlendMode::Alpha => (BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha, BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::PremultipliedAlpha => (BlendFactor::One, BlendFactor::OneMinusSrcAlpha, BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            BlendMode::Additive => (BlendFactor::SrcAlpha, BlendFactor::One, BlendFactor::Zero, BlendFactor::One),
+            BlendMode::Multiply => (BlendFactor::DstColor, BlendFactor::Zero, BlendFactor::Zero, BlendFactor::One),
+        };
+        Some(AttachmentBlend {
+            color_op: BlendOp::Add,
+            color_source,
+            color_destination,
+            alpha_op: BlendOp::Add,
+            alpha_source,
+            alpha_destination,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PipelineDesc {
+    pub topology: PrimitiveTopology,
+    //Line 和 Point 需要设备特性 fill_mode_non_solid
+    pub polygon_mode: PolygonMode,
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    //不等于1时需要设备特性 wide_lines
+    pub line_width: f32,
+    pub depth_bias: Option<DepthBias>,
+    pub blend: BlendMode,
+    //半透明的绘制不写入深度，避免挡住后面画的半透明物体
+    pub depth_write: bool,
+}
+
+impl Default for PipelineDesc {
+    fn default() -> Self {
+        PipelineDesc {
+            topology: PrimitiveTopology::TriangleList,
+            polygon_mode: PolygonMode::Fill,
+            //CAD 导出的模型面朝向经常不一致，默认不剔除
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            line_width: 1.0,
+            depth_bias: None,
+            blend: BlendMode::Opaque,
+            depth_write: true,
+        }
+    }
+}
+
+impl PipelineDesc {
+    //线框覆盖层：线框模式，深度偏移让线画在同一位置的面前面，半透明叠加
+    pub fn wireframe(topology: PrimitiveTopology) -> PipelineDesc {
+        PipelineDesc {
+            topology,
+            polygon_mode: PolygonMode::Line,
+            blend: BlendMode::Alpha,
+            depth_bias: Some(DepthBias {
+                constant_factor: -1.0,
+                clamp: 0.0,
+                slope_factor: -1.0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    //半透明绘制：按预设混合，不写入深度
+    pub fn transparent(topology: PrimitiveTopology, blend: BlendMode) -> PipelineDesc {
+        PipelineDesc {
+            topology,
+            blend,
+            depth_write: false,
+            ..Default::default()
+        }
+    }
+
+    //描述中的状态需要哪些设备特性
+    pub fn required_features(&self) -> Features {
+        Features {
+            fill_mode_non_solid: self.polygon_mode != PolygonMode::Fill,
+            wide_lines: self.line_width != 1.0,
+            ..Features::empty()
+        }
+    }
+
+    //用于哈希和比较的键，f32 按位比较
+    #[allow(clippy::type_complexity)]
+    fn key(&self) -> (PrimitiveTopology, PolygonMode, CullMode, FrontFace, u32, Option<[u32; 3]>, BlendMode, bool) {
+        (
+            self.topology,
+            self.polygon_mode,
+            self.cull_mode,
+            self.front_face,
+            self.line_width.to_bits(),
+            self.depth_bias.as_ref().map(|bias| {
+                [bias.constant_factor.to_bits(), bias.clamp.to_bits(), bias.slope_factor.to_bits()]
+            }),
+            self.blend,
+            self.depth_write,
+        )
+    }
+}
+
+impl PartialEq for PipelineDesc {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for PipelineDesc {}
+
+impl Hash for PipelineDesc {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+//网格管线缓存，所有管线共用同一个渲染通道和着色器，管线布局也相同，描述符集可以在它们之间共用
+//创建管线时使用磁盘上的管线缓存，程序退出前调用 save_cache 把编译结果写回去
+pub struct MeshPipelines {
+    device: Arc<Device>,
+    render_pass: Arc<RenderPass>,
+    vs: Arc<ShaderModule>,
+    fs: Arc<ShaderModule>,
+    cache: SharedPipelineCache,
+    pipelines: HashMap<PipelineDesc, Arc<GraphicsPipeline>>,
+}
+
+impl MeshPipelines {
+    pub fn new(device: Arc<Device>, render_pass: Arc<RenderPass>) -> MeshPipelines {
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+        let cache = SharedPipelineCache::new(device.clone());
+        MeshPipelines {
+            device,
+            render_pass,
+            vs,
+            fs,
+            cache,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn save_cache(&self) {
+        self.cache.save();
+    }
+
+    //其他管线（例如调试线条）也可以使用这个缓存，随 save_cache 一起保存
+    pub fn cache(&self) -> Arc<PipelineCache> {
+        self.cache.cache()
+    }
+
+    //设备是否开启了描述需要的特性
+    pub fn supports(&self, desc: &PipelineDesc) -> bool {
+        self.device.enabled_features().contains(&desc.required_features())
+    }
+
+    //按描述取得管线，第一次使用时创建
+    pub fn get(&mut self, desc: &PipelineDesc) -> Arc<GraphicsPipeline> {
+        if let Some(pipeline) = self.pipelines.get(desc) {
+            return pipeline.clone();
+        }
+
+        let pipeline = self.create(desc);
+        self.pipelines.insert(*desc, pipeline.clone());
+        pipeline
+    }
+
+    fn create(&self, desc: &PipelineDesc) -> Arc<GraphicsPipeline> {
+        let mut rasterization_state = RasterizationState::new()
+            .polygon_mode(desc.polygon_mode)
+            .cull_mode(desc.cull_mode)
+            .front_face(desc.front_face)
+            .line_width(desc.line_width);
+        if let Some(depth_bias) = desc.depth_bias {
+            rasterization_state = rasterization_state.depth_bias(depth_bias);
+        }
+
+        let mut color_blend_state = ColorBlendState::new(1);
+        if let Some(blend) = desc.blend.attachment_blend() {
+            color_blend_state = color_blend_state.blend(blend);
+        }
+
+        let mut depth_stencil_state = DepthStencilState::simple_depth_test();
+        if let Some(depth) = depth_stencil_state.depth.as_mut() {
+            depth.write_enable = StateMode::Fixed(desc.depth_write);
+        }
+
+        GraphicsPipeline::start()
+            .vertex_input_state(MeshVertex::per_vertex())
+            .vertex_shader(self.vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new().topology(desc.topology))
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .rasterization_state(rasterization_state)
+            .fragment_shader(self.fs.entry_point("main").unwrap(), ())
+            .color_blend_state(color_blend_state)
+            .depth_stencil_state(depth_stencil_state)
+            .render_pass(Subpass::from(self.render_pass.clone(), 0).unwrap())
+            .build_with_cache(self.cache.cache())
+            .build(self.device.clone())
+            .unwrap()
+    }
+}
@@ -0,0 +1,512 @@
+//PLY 模型加载，支持 ascii、binary_little_endian 和 binary_big_endian
+//有 face 元素时加载为三角形网格，没有时作为点云用点列表绘制
+//顶点支持 x/y/z、nx/ny/nz、red/green/blue/alpha 和 u/v（或 s/t）属性
+use std::fs;
+use std::path::Path;
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use crate::example::mesh::{compute_normals, Material, Mesh, MeshGroup, MeshVertex};
+use crate::example::scene::Scene;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PlyType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl PlyType {
+    fn parse(name: &str) -> Result<PlyType, String> {
+        match name {
+            "char" | "int8" => Ok(PlyType::I8),
+            "uchar" | "uint8" => Ok(PlyType::U8),
+            "short" | "int16" => Ok(PlyType::I16),
+            "ushort" | "uint16" => Ok(PlyType::U16),
+            "int" | "int32" => Ok(PlyType::I32),
+            "uint" | "uint32" => Ok(PlyType::U32),
+            "float" | "float32" => Ok(PlyType::F32),
+            "double" | "float64" => Ok(PlyType::F64),
+            _ => Err(format!("unknown property type {name}")),
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            PlyType::I8 | PlyType::U8 => 1,
+            PlyType::I16 | PlyType::U16 => 2,
+            PlyType::I32 | PlyType::U32 | PlyType::F32 => 4,
+            PlyType::F64 => 8,
+        }
+    }
+
+    //整数类型的颜色需要归一化到 0..1，浮点类型的颜色本身就在 0..1
+    fn color_scale(&self) -> f32 {
+        match self {
+            PlyType::U8 | PlyType::I8 => 255.0,
+            PlyType::U16 | PlyType::I16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum PlyProperty {
+    Scalar { name: String, kind: PlyType },
+    List { count: PlyType, item: PlyType },
+}
+
+#[derive(Debug)]
+struct PlyElement {
+    name: String,
+    count: usize,
+    properties: Vec<PlyProperty>,
+}
+
+//按照文件格式逐个读取数值
+struct PlyReader<'a> {
+    format: PlyFormat,
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> PlyReader<'a> {
+    fn read(&mut self, kind: PlyType) -> Result<f64, String> {
+        if self.format == PlyFormat::Ascii {
+            return self.read_ascii();
+        }
+
+        let size = kind.size();
+        let bytes = self
+            .data
+            .get(self.offset..self.offset + size)
+            .ok_or_else(|| String::from("unexpected end of file"))?;
+        self.offset += size;
+
+        let mut buf = [0u8; 8];
+        buf[..size].copy_from_slice(bytes);
+        if self.format == PlyFormat::BinaryBigEndian {
+            buf[..size].reverse();
+        }
+
+        Ok(match kind {
+            PlyType::I8 => buf[0] as i8 as f64,
+            PlyType::U8 => buf[0] as f64,
+            PlyType::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+            PlyType::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+            PlyType::F64 => f64::from_le_bytes(buf),
+        })
+    }
+
+    //列表的长度，负数、小数或者比剩余数据还多的长度说明文件损坏
+    fn read_count(&mut self, kind: PlyType, item: PlyType) -> Result<usize, String> {
+        let value = self.read(kind)?;
+        if value < 0.0 || value.fract() != 0.0 {
+            return Err(format!("invalid list length {value}"));
+        }
+        //二进制的每一项占 item.size() 字节，文本的每一项至少占一个字符
+        let item_size = if self.format == PlyFormat::Ascii { 1 } else { item.size() };
+        let remaining = (self.data.len() - self.offset) / item_size;
+        if value > remaining as f64 {
+            return Err(format!("list length {value} exceeds the remaining data"));
+        }
+        Ok(value as usize)
+    }
+
+    //顶点索引必须是非负整数
+    fn read_index(&mut self, kind: PlyType) -> Result<u32, String> {
+        let value = self.read(kind)?;
+        if value < 0.0 || value.fract() != 0.0 || value > u32::MAX as f64 {
+            return Err(format!("invalid vertex index {value}"));
+        }
+        Ok(value as u32)
+    }
+
+    fn read_ascii(&mut self) -> Result<f64, String> {
+        while self.offset < self.data.len() && self.data[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+        let start = self.offset;
+        while self.offset < self.data.len() && !self.data[self.offset].is_ascii_whitespace() {
+            self.offset += 1;
+        }
+        if start == self.offset {
+            return Err(String::from("unexpected end of file"));
+        }
+        let token = String::from_utf8_lossy(&self.data[start..self.offset]);
+        token.parse().map_err(|_| format!("invalid number {token}"))
+    }
+}
+
+pub fn load_ply(path: &Path) -> Result<Scene, String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    parse_ply(&data)
+        .and_then(|mesh| {
+            if mesh.vertices.is_empty() {
+                Err(String::from("no vertices"))
+            } else {
+                Ok(mesh)
+            }
+        })
+        .map(|mesh| {
+            //颜色来自顶点，材质使用白色，点云和带颜色的扫描数据不会被染成灰色
+            let material = Material {
+                base_color: [1.0; 4],
+                ..Default::default()
+            };
+            Scene::from_mesh(mesh, vec![material])
+        })
+        .map_err(|e| format!("failed to load {}: {e}", path.display()))
+}
+
+fn parse_ply(data: &[u8]) -> Result<Mesh, String> {
+    let (format, elements, body_offset) = parse_header(data)?;
+    let mut reader = PlyReader {
+        format,
+        data,
+        offset: body_offset,
+    };
+
+    let mut mesh = Mesh::default();
+    let mut has_normals = false;
+    let mut has_faces = false;
+
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                has_normals = ["nx", "ny", "nz"].iter().all(|name| find_property(element, name).is_some());
+                for _ in 0..element.count {
+                    mesh.vertices.push(read_vertex(&mut reader, element)?);
+                }
+            }
+            "face" => {
+                has_faces = element.count > 0;
+                for _ in 0..element.count {
+                    read_face(&mut reader, element, mesh.vertices.len(), &mut mesh.indices)?;
+                }
+            }
+            //其他元素（edge、材质等）按属性读取后丢弃
+            _ => {
+                for _ in 0..element.count {
+                    skip_record(&mut reader, element)?;
+                }
+            }
+        }
+    }
+
+    if has_faces {
+        mesh.topology = PrimitiveTopology::TriangleList;
+        if !has_normals {
+            let indices = mesh.indices.clone();
+            compute_normals(&mut mesh.vertices, &indices);
+        }
+    } else {
+        //点云：每个顶点画成一个点
+        mesh.topology = PrimitiveTopology::PointList;
+        mesh.indices = (0..mesh.vertices.len() as u32).collect();
+    }
+
+    mesh.groups.push(MeshGroup {
+        name: String::from(if has_faces { "mesh" } else { "points" }),
+        first_index: 0,
+        index_count: mesh.indices.len() as u32,
+        material: 0,
+    });
+
+    Ok(mesh)
+}
+
+//解析文件头，返回格式、元素列表和数据开始的位置
+fn parse_header(data: &[u8]) -> Result<(PlyFormat, Vec<PlyElement>, usize), String> {
+    const END_HEADER: &[u8] = b"end_header";
+    let end = data
+        .windows(END_HEADER.len())
+        .position(|window| window == END_HEADER)
+        .ok_or_else(|| String::from("missing end_header"))?;
+    //数据从 end_header 所在行的下一行开始（行尾可能是 \n 或 \r\n）
+    let body_offset = data[end..]
+        .iter()
+        .position(|byte| *byte == b'\n')
+        .map(|newline| end + newline + 1)
+        .ok_or_else(|| String::from("missing data after end_header"))?;
+
+    let header = String::from_utf8_lossy(&data[..end]);
+    let mut lines = header.lines();
+    if lines.next().map(str::trim) != Some("ply") {
+        return Err(String::from("not a PLY file"));
+    }
+
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in lines {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.as_slice() {
+            ["format", "ascii", ..] => format = Some(PlyFormat::Ascii),
+            ["format", "binary_little_endian", ..] => format = Some(PlyFormat::BinaryLittleEndian),
+            ["format", "binary_big_endian", ..] => format = Some(PlyFormat::BinaryBigEndian),
+            ["element", name, count] => elements.push(PlyElement {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count: {line}"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, _name] => elements
+                .last_mut()
+                .ok_or_else(|| format!("property outside of element: {line}"))?
+                .properties
+                .push(PlyProperty::List {
+                    count: PlyType::parse(count)?,
+                    item: PlyType::parse(item)?,
+                }),
+            ["property", kind, name] => elements
+                .last_mut()
+                .ok_or_else(|| format!("property outside of element: {line}"))?
+                .properties
+                .push(PlyProperty::Scalar {
+                    name: name.to_string(),
+                    kind: PlyType::parse(kind)?,
+                }),
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| String::from("missing format line"))?;
+    Ok((format, elements, body_offset))
+}
+
+fn find_property(element: &PlyElement, name: &str) -> Option<usize> {
+    element.properties.iter().position(|property| {
+        matches!(property, PlyProperty::Scalar { name: property_name, .. } if property_name == name)
+    })
+}
+
+fn read_vertex(reader: &mut PlyReader, element: &PlyElement) -> Result<MeshVertex, String> {
+    let mut vertex = MeshVertex {
+        position: [0.0; 3],
+        normal: [0.0; 3],
+        uv: [0.0; 2],
+        color: [1.0; 4],
+    };
+
+    for property in &element.properties {
+        match property {
+            PlyProperty::Scalar { name, kind } => {
+                let value = reader.read(*kind)? as f32;
+                match name.as_str() {
+                    "x" => vertex.position[0] = value,
+                    "y" => vertex.position[1] = value,
+                    "z" => vertex.position[2] = value,
+                    "nx" => vertex.normal[0] = value,
+                    "ny" => vertex.normal[1] = value,
+                    "nz" => vertex.normal[2] = value,
+                    "u" | "s" | "texture_u" => vertex.uv[0] = value,
+                    "v" | "t" | "texture_v" => vertex.uv[1] = 1.0 - value,
+                    "red" | "r" => vertex.color[0] = value / kind.color_scale(),
+                    "green" | "g" => vertex.color[1] = value / kind.color_scale(),
+                    "blue" | "b" => vertex.color[2] = value / kind.color_scale(),
+                    "alpha" | "a" => vertex.color[3] = value / kind.color_scale(),
+                    _ => {}
+                }
+            }
+            PlyProperty::List { count, item } => {
+                let count = reader.read_count(*count, *item)?;
+                for _ in 0..count {
+                    reader.read(*item)?;
+                }
+            }
+        }
+    }
+
+    Ok(vertex)
+}
+
+//读取一个面，多边形按扇形拆分成三角形
+fn read_face(reader: &mut PlyReader, element: &PlyElement, vertex_count: usize, indices: &mut Vec<u32>) -> Result<(), String> {
+    let mut polygon_read = false;
+
+    for property in &element.properties {
+        match property {
+            PlyProperty::List { count, item } => {
+                let count = reader.read_count(*count, *item)?;
+                //只有第一个列表属性是顶点索引（vertex_indices 或 vertex_index），其他列表（例如纹理坐标）读取后丢弃
+                if polygon_read {
+                    for _ in 0..count {
+                        reader.read(*item)?;
+                    }
+                    continue;
+                }
+                polygon_read = true;
+
+                let mut polygon = Vec::new();
+                for _ in 0..count {
+                    polygon.push(reader.read_index(*item)?);
+                }
+
+                if let Some(index) = polygon.iter().find(|index| **index as usize >= vertex_count) {
+                    return Err(format!("face references vertex {index}, but there are only {vertex_count} vertices"));
+                }
+                for i in 1..polygon.len().saturating_sub(1) {
+                    indices.extend([polygon[0], polygon[i], polygon[i + 1]]);
+                }
+            }
+            PlyProperty::Scalar { kind, .. } => {
+                reader.read(*kind)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn skip_record(reader: &mut PlyReader, element: &PlyElement) -> Result<(), String> {
+    for property in &element.properties {
+        match property {
+            PlyProperty::Scalar { kind, .. } => {
+                reader.read(*kind)?;
+            }
+            PlyProperty::List { count, item } => {
+                let count = reader.read_count(*count, *item)?;
+                for _ in 0..count {
+                    reader.read(*item)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE_HEADER: &str = "element vertex 3\nproperty float x\nproperty float y\nproperty float z\n";
+    const ASCII_TRIANGLE: &str = "0 0 0\n1 0 0\n0 1 0\n";
+
+    //文件头加上数据，header 是 format 和 end_header 之间的行
+    fn ply(format: &str, header: &str, body: &[u8]) -> Vec<u8> {
+        let mut data = format!("ply\nformat {format} 1.0\ncomment test\n{header}end_header\n").into_bytes();
+        data.extend(body);
+        data
+    }
+
+    fn ascii_face(face: &str) -> Vec<u8> {
+        let header = format!("{TRIANGLE_HEADER}element face 1\nproperty list uchar int vertex_indices\n");
+        ply("ascii", &header, format!("{ASCII_TRIANGLE}{face}\n").as_bytes())
+    }
+
+    #[test]
+    fn loads_ascii_faces() {
+        let header = "element vertex 4\nproperty float x\nproperty float y\nproperty float z\n\
+                      element face 1\nproperty list uchar int vertex_indices\n";
+        let mesh = parse_ply(&ply("ascii", header, b"0 0 0\n1 0 0\n1 1 0\n0 1 0\n4 0 1 2 3\n")).unwrap();
+
+        assert_eq!(mesh.topology, PrimitiveTopology::TriangleList);
+        //四边形按扇形拆分成两个三角形
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices[2].position, [1.0, 1.0, 0.0]);
+        //文件中没有法线，根据面计算
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+        assert_eq!(mesh.groups[0].name, "mesh");
+        assert_eq!(mesh.groups[0].index_count, 6);
+    }
+
+    #[test]
+    fn loads_binary_faces_and_skips_extra_lists() {
+        let header = format!(
+            "{TRIANGLE_HEADER}element face 1\nproperty list uchar uint vertex_indices\nproperty list uchar float texcoord\n"
+        );
+        let mut body = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 2.0, 0.0] {
+            body.extend(value.to_le_bytes());
+        }
+        body.push(3);
+        for index in [2u32, 1, 0] {
+            body.extend(index.to_le_bytes());
+        }
+        //第二个列表是纹理坐标，不是顶点索引
+        body.push(6);
+        for value in [0.5f32, 0.25, 1.0, 0.75, 0.125, 0.0] {
+            body.extend(value.to_le_bytes());
+        }
+
+        let mesh = parse_ply(&ply("binary_little_endian", &header, &body)).unwrap();
+        assert_eq!(mesh.indices, [2, 1, 0]);
+        assert_eq!(mesh.vertices[1].position, [2.0, 0.0, 0.0]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, -1.0]));
+    }
+
+    #[test]
+    fn loads_ascii_point_cloud_with_colors() {
+        let header = "element vertex 2\nproperty float x\nproperty float y\nproperty float z\n\
+                      property uchar red\nproperty uchar green\nproperty uchar blue\n";
+        let mesh = parse_ply(&ply("ascii", header, b"0 0 0 255 0 51\n1 2 3 0 255 102\n")).unwrap();
+
+        assert_eq!(mesh.topology, PrimitiveTopology::PointList);
+        assert_eq!(mesh.indices, [0, 1]);
+        assert_eq!(mesh.vertices[0].color, [1.0, 0.0, 0.2, 1.0]);
+        assert_eq!(mesh.vertices[1].color, [0.0, 1.0, 0.4, 1.0]);
+        assert_eq!(mesh.vertices[1].position, [1.0, 2.0, 3.0]);
+        assert_eq!(mesh.groups[0].name, "points");
+    }
+
+    #[test]
+    fn loads_big_endian_point_cloud_with_colors() {
+        let header = "element vertex 2\nproperty float x\nproperty float y\nproperty float z\n\
+                      property ushort red\nproperty ushort green\nproperty ushort blue\nproperty ushort alpha\n";
+        let mut body = Vec::new();
+        for (position, color) in [([1.5f32, -2.0, 0.25], [65535u16, 0, 0, 65535]), ([0.0, 0.0, 0.0], [0, 0, 65535, 0])] {
+            for value in position {
+                body.extend(value.to_be_bytes());
+            }
+            for value in color {
+                body.extend(value.to_be_bytes());
+            }
+        }
+
+        let mesh = parse_ply(&ply("binary_big_endian", header, &body)).unwrap();
+        assert_eq!(mesh.topology, PrimitiveTopology::PointList);
+        assert_eq!(mesh.vertices[0].position, [1.5, -2.0, 0.25]);
+        assert_eq!(mesh.vertices[0].color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(mesh.vertices[1].color, [0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn rejects_bad_vertex_indices() {
+        assert_eq!(parse_ply(&ascii_face("3 0 1 2")).unwrap().indices, [0, 1, 2]);
+        assert_eq!(
+            parse_ply(&ascii_face("3 0 1 5")).unwrap_err(),
+            "face references vertex 5, but there are only 3 vertices"
+        );
+        //负数转换成 u32 会变成0，不能通过范围检查
+        assert_eq!(parse_ply(&ascii_face("3 0 -1 2")).unwrap_err(), "invalid vertex index -1");
+        assert_eq!(parse_ply(&ascii_face("3 0 1.5 2")).unwrap_err(), "invalid vertex index 1.5");
+    }
+
+    #[test]
+    fn rejects_bad_list_lengths() {
+        assert_eq!(parse_ply(&ascii_face("-3 0 1 2")).unwrap_err(), "invalid list length -3");
+        assert_eq!(parse_ply(&ascii_face("2.5 0 1 2")).unwrap_err(), "invalid list length 2.5");
+
+        //损坏的二进制长度不能用来分配内存
+        let header = format!("{TRIANGLE_HEADER}element face 1\nproperty list uint uint vertex_indices\n");
+        let mut body = vec![0u8; 36];
+        body.extend(u32::MAX.to_le_bytes());
+        body.extend(0u32.to_le_bytes());
+        assert_eq!(
+            parse_ply(&ply("binary_little_endian", &header, &body)).unwrap_err(),
+            "list length 4294967295 exceeds the remaining data"
+        );
+    }
+}
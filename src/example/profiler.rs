@@ -0,0 +1,489 @@
+//GPU 性能分析
+//在录制命令缓冲区时用时间戳查询包围命名的区间，可以同时收集管线统计（顶点数、图元数、着色器调用次数），
+//帧执行完毕后读回结果换算成毫秒，按区间名累计统计，也可以导出为 Chrome trace 用 chrome://tracing 或 Perfetto 查看
+//和交换链图像一样，每个同时在执行的帧使用自己的一组查询（槽位），等待这一帧的栅栏以后才能读取结果
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::device::Device;
+use vulkano::query::{
+    QueryControlFlags, QueryPipelineStatisticFlags, QueryPool, QueryPoolCreateInfo, QueryResultFlags, QueryType,
+};
+use vulkano::sync::PipelineStage;
+
+//收集的管线统计，查询结果按标志位从低到高排列
+const STATISTICS: [QueryPipelineStatisticFlags; 6] = [
+    QueryPipelineStatisticFlags::INPUT_ASSEMBLY_VERTICES,
+    QueryPipelineStatisticFlags::INPUT_ASSEMBLY_PRIMITIVES,
+    QueryPipelineStatisticFlags::VERTEX_SHADER_INVOCATIONS,
+    QueryPipelineStatisticFlags::CLIPPING_INVOCATIONS,
+    QueryPipelineStatisticFlags::CLIPPING_PRIMITIVES,
+    QueryPipelineStatisticFlags::FRAGMENT_SHADER_INVOCATIONS,
+];
+
+//一个区间的管线统计，需要设备启用 pipeline_statistics_query 特性
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PipelineStatistics {
+    pub input_vertices: u64,
+    pub input_primitives: u64,
+    pub vertex_shader_invocations: u64,
+    //进入裁剪阶段的图元和裁剪后输出的图元
+    pub clipping_invocations: u64,
+    pub clipping_primitives: u64,
+    pub fragment_shader_invocations: u64,
+}
+
+impl PipelineStatistics {
+    fn from_results(values: &[u64]) -> PipelineStatistics {
+        PipelineStatistics {
+            input_vertices: values[0],
+            input_primitives: values[1],
+            vertex_shader_invocations: values[2],
+            clipping_invocations: values[3],
+            clipping_primitives: values[4],
+            fragment_shader_invocations: values[5],
+        }
+    }
+
+    fn add(&mut self, other: &PipelineStatistics) {
+        self.input_vertices += other.input_vertices;
+        self.input_primitives += other.input_primitives;
+        self.vertex_shader_invocations += other.vertex_shader_invocations;
+        self.clipping_invocations += other.clipping_invocations;
+        self.clipping_primitives += other.clipping_primitives;
+        self.fragment_shader_invocations += other.fragment_shader_invocations;
+    }
+
+    //摘要和 Chrome trace 中使用的名字
+    pub fn fields(&self) -> [(&'static str, u64); 6] {
+        [
+            ("input_vertices", self.input_vertices),
+            ("input_primitives", self.input_primitives),
+            ("vertex_shader_invocations", self.vertex_shader_invocations),
+            ("clipping_invocations", self.clipping_invocations),
+            ("clipping_primitives", self.clipping_primitives),
+            ("fragment_shader_invocations", self.fragment_shader_invocations),
+        ]
+    }
+}
+
+//一帧中一个区间的结果
+#[derive(Clone, Debug)]
+pub struct ScopeTiming {
+    pub name: String,
+    //嵌套深度，最外层为0
+    pub depth: usize,
+    //相对于第一帧第一个时间戳的开始时间
+    pub start_ms: f64,
+    pub duration_ms: f32,
+    pub statistics: Option<PipelineStatistics>,
+}
+
+//同名区间在所有帧中的累计
+#[derive(Clone, Debug)]
+pub struct ScopeStats {
+    pub name: String,
+    pub count: u32,
+    pub total_ms: f64,
+    pub min_ms: f32,
+    pub max_ms: f32,
+    //有管线统计的帧数和它们的统计之和
+    pub statistics_count: u32,
+    pub statistics: PipelineStatistics,
+}
+
+impl ScopeStats {
+    fn new(name: &str) -> ScopeStats {
+        ScopeStats {
+            name: name.to_string(),
+            count: 0,
+            total_ms: 0.0,
+            min_ms: f32::MAX,
+            max_ms: 0.0,
+            statistics_count: 0,
+            statistics: PipelineStatistics::default(),
+        }
+    }
+
+    pub fn mean_ms(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.total_ms / self.count as f64) as f32
+        }
+    }
+}
+
+//录制时的区间，statistics 是管线统计查询的编号
+struct RecordedScope {
+    name: String,
+    depth: usize,
+    statistics: Option<u32>,
+}
+
+//一个槽位的查询池和这一帧录制的区间，每个区间使用两个时间戳
+struct ProfilerFrame {
+    timestamps: Arc<QueryPool>,
+    statistics: Option<Arc<QueryPool>>,
+    scopes: Vec<RecordedScope>,
+    statistics_count: u32,
+    frame_index: u64,
+    //已经录制完毕、还没有读取结果
+    pending: bool,
+}
+
+impl ProfilerFrame {
+    fn new(device: &Arc<Device>, max_scopes: u32, statistics_supported: bool) -> ProfilerFrame {
+        let timestamps = QueryPool::new(
+            device.clone(),
+            QueryPoolCreateInfo {
+                query_count: max_scopes * 2,
+                ..QueryPoolCreateInfo::query_type(QueryType::Timestamp)
+            },
+        )
+        .expect("failed to create query pool");
+
+        let statistics = statistics_supported.then(|| {
+            let flags = STATISTICS
+                .into_iter()
+                .fold(QueryPipelineStatisticFlags::empty(), |flags, flag| flags | flag);
+            QueryPool::new(
+                device.clone(),
+                QueryPoolCreateInfo {
+                    query_count: max_scopes,
+                    ..QueryPoolCreateInfo::query_type(QueryType::PipelineStatistics(flags))
+                },
+            )
+            .expect("failed to create query pool")
+        });
+
+        ProfilerFrame {
+            timestamps,
+            statistics,
+            scopes: Vec::new(),
+            statistics_count: 0,
+            frame_index: 0,
+            pending: false,
+        }
+    }
+}
+
+pub struct GpuProfiler {
+    //每个时间戳单位的纳秒数
+    timestamp_period: f32,
+    //时间戳只有低 timestamp_valid_bits 位有效
+    timestamp_mask: u64,
+    //每帧最多的区间数，超出的区间不记录
+    max_scopes: u32,
+    //队列族不支持时间戳查询时为空，所有方法什么都不做
+    frames: Vec<ProfilerFrame>,
+    //正在录制的槽位，以及还没结束的区间（超出 max_scopes 的区间为 None）
+    recording: Option<usize>,
+    open: Vec<Option<usize>>,
+    //同一时间只能有一个管线统计查询
+    statistics_open: bool,
+    frame_index: u64,
+    stats: Vec<ScopeStats>,
+    //调用 start_trace 以后每个区间的结果和它所在的帧
+    trace: Option<Vec<(u64, ScopeTiming)>>,
+    trace_origin: Option<u64>,
+}
+
+impl GpuProfiler {
+    //slots 是同时在执行的帧数，窗口中是交换链图像数，离屏渲染等待每一帧完成时为1
+    //设备启用了 pipeline_statistics_query 特性时才收集管线统计
+    pub fn new(device: Arc<Device>, queue_family_index: u32, slots: usize, max_scopes: u32) -> GpuProfiler {
+        let physical_device = device.physical_device().clone();
+        let valid_bits = physical_device.queue_family_properties()[queue_family_index as usize].timestamp_valid_bits;
+        let statistics_supported = device.enabled_features().pipeline_statistics_query;
+
+        let frames = match valid_bits {
+            Some(_) => (0..slots)
+                .map(|_| ProfilerFrame::new(&device, max_scopes, statistics_supported))
+                .collect(),
+            None => {
+                println!("the queue family does not support timestamp queries, GPU profiling is disabled");
+                Vec::new()
+            }
+        };
+        let timestamp_mask = match valid_bits {
+            Some(bits) if bits < 64 => (1u64 << bits) - 1,
+            _ => u64::MAX,
+        };
+
+        GpuProfiler {
+            timestamp_period: physical_device.properties().timestamp_period,
+            timestamp_mask,
+            max_scopes,
+            frames,
+            recording: None,
+            open: Vec::new(),
+            statistics_open: false,
+            frame_index: 0,
+            stats: Vec::new(),
+            trace: None,
+            trace_origin: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    //之后读回的每个区间都保存下来，用 write_chrome_trace 导出
+    pub fn start_trace(&mut self) {
+        self.trace = Some(Vec::new());
+    }
+
+    //在命令缓冲区的开头调用，查询池的重置需要在渲染通道之外
+    pub fn begin_frame(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, slot: usize) {
+        let Some(frame) = self.frames.get_mut(slot) else {
+            return;
+        };
+        frame.scopes.clear();
+        frame.statistics_count = 0;
+        frame.pending = false;
+        frame.frame_index = self.frame_index;
+        self.frame_index += 1;
+
+        unsafe {
+            builder.reset_query_pool(frame.timestamps.clone(), 0..self.max_scopes * 2).unwrap();
+            if let Some(statistics) = &frame.statistics {
+                builder.reset_query_pool(statistics.clone(), 0..self.max_scopes).unwrap();
+            }
+        }
+        self.recording = Some(slot);
+        self.open.clear();
+        self.statistics_open = false;
+    }
+
+    //区间可以嵌套，每个 begin_scope 对应一个 end_scope
+    pub fn begin_scope(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, name: &str) {
+        self.begin(builder, name, false);
+    }
+
+    //同时收集管线统计，已经有一个收集统计的区间没有结束时只记录时间
+    //在渲染通道内开始的区间需要在同一个子通道内结束
+    pub fn begin_scope_with_statistics(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        name: &str,
+    ) {
+        self.begin(builder, name, true);
+    }
+
+    pub fn end_scope(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let Some(slot) = self.recording else {
+            return;
+        };
+        let Some(index) = self.open.pop().expect("end_scope without a matching begin_scope") else {
+            return;
+        };
+        let frame = &mut self.frames[slot];
+        unsafe {
+            if let (Some(query), Some(pool)) = (frame.scopes[index].statistics, &frame.statistics) {
+                builder.end_query(pool.clone(), query).unwrap();
+                self.statistics_open = false;
+            }
+            builder
+                .write_timestamp(frame.timestamps.clone(), index as u32 * 2 + 1, PipelineStage::BottomOfPipe)
+                .unwrap();
+        }
+    }
+
+    //在命令缓冲区的结尾调用，所有区间都需要已经结束
+    pub fn end_frame(&mut self) {
+        if let Some(slot) = self.recording.take() {
+            assert!(self.open.is_empty(), "a GPU profiler scope was not ended");
+            self.frames[slot].pending = true;
+        }
+    }
+
+    //槽位上一次录制的帧执行完毕后调用，返回这一帧各个区间的结果，并累计到统计和 trace 中
+    //没有录制过或者结果还不可用时返回 None
+    pub fn collect(&mut self, slot: usize) -> Option<Vec<ScopeTiming>> {
+        let frame = self.frames.get_mut(slot)?;
+        if !std::mem::take(&mut frame.pending) || frame.scopes.is_empty() {
+            return None;
+        }
+
+        let mut timestamps = vec![0u64; frame.scopes.len() * 2];
+        let available = frame
+            .timestamps
+            .queries_range(0..timestamps.len() as u32)?
+            .get_results(&mut timestamps, QueryResultFlags::default())
+            .ok()?;
+        if !available {
+            return None;
+        }
+
+        let mut statistics = vec![0u64; frame.statistics_count as usize * STATISTICS.len()];
+        if let Some(pool) = frame.statistics.as_ref().filter(|_| frame.statistics_count > 0) {
+            let available = pool
+                .queries_range(0..frame.statistics_count)?
+                .get_results(&mut statistics, QueryResultFlags::default())
+                .ok()?;
+            if !available {
+                return None;
+            }
+        }
+
+        let origin = *self.trace_origin.get_or_insert(timestamps[0]);
+        let to_ms = |ticks: u64| (ticks & self.timestamp_mask) as f64 * self.timestamp_period as f64 / 1_000_000.0;
+        let timings: Vec<ScopeTiming> = frame
+            .scopes
+            .iter()
+            .enumerate()
+            .map(|(i, scope)| ScopeTiming {
+                name: scope.name.clone(),
+                depth: scope.depth,
+                start_ms: to_ms(timestamps[i * 2].wrapping_sub(origin)),
+                duration_ms: to_ms(timestamps[i * 2 + 1].wrapping_sub(timestamps[i * 2])) as f32,
+                statistics: scope.statistics.map(|query| {
+                    let first = query as usize * STATISTICS.len();
+                    PipelineStatistics::from_results(&statistics[first..first + STATISTICS.len()])
+                }),
+            })
+            .collect();
+
+        let frame_index = frame.frame_index;
+        for timing in &timings {
+            self.accumulate(timing);
+            if let Some(trace) = self.trace.as_mut() {
+                trace.push((frame_index, timing.clone()));
+            }
+        }
+        Some(timings)
+    }
+
+    //按第一次出现的顺序排列
+    pub fn stats(&self) -> &[ScopeStats] {
+        &self.stats
+    }
+
+    //每个区间一行：次数、平均、最短和最长时间，有管线统计时加上每帧的平均值
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for stats in &self.stats {
+            write!(
+                summary,
+                "{}: {} frames, mean {:.3} ms, min {:.3} ms, max {:.3} ms",
+                stats.name,
+                stats.count,
+                stats.mean_ms(),
+                stats.min_ms,
+                stats.max_ms,
+            )
+            .unwrap();
+            if stats.statistics_count > 0 {
+                for (name, value) in stats.statistics.fields() {
+                    write!(summary, ", {name} {}", value / stats.statistics_count as u64).unwrap();
+                }
+            }
+            summary.push('\n');
+        }
+        summary
+    }
+
+    //Chrome trace 格式：每个区间是一个完整事件（ph 为 X），时间单位是微秒，管线统计放在 args 中
+    pub fn write_chrome_trace(&self, path: &Path) -> Result<(), String> {
+        let Some(trace) = &self.trace else {
+            return Err("GPU tracing was not started".to_string());
+        };
+
+        let mut json = String::from("{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n");
+        json.push_str("{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":0,\"args\":{\"name\":\"GPU\"}}");
+        for (frame, timing) in trace {
+            write!(
+                json,
+                ",\n{{\"name\":\"{}\",\"cat\":\"gpu\",\"ph\":\"X\",\"pid\":0,\"tid\":0,\"ts\":{:.3},\"dur\":{:.3},\"args\":{{\"frame\":{frame}",
+                escape_json(&timing.name),
+                timing.start_ms * 1000.0,
+                timing.duration_ms as f64 * 1000.0,
+            )
+            .unwrap();
+            if let Some(statistics) = timing.statistics {
+                for (name, value) in statistics.fields() {
+                    write!(json, ",\"{name}\":{value}").unwrap();
+                }
+            }
+            json.push_str("}}");
+        }
+        json.push_str("\n]}\n");
+
+        fs::write(path, json).map_err(|e| format!("failed to write {}: {e}", path.display()))
+    }
+
+    fn begin(&mut self, builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, name: &str, statistics: bool) {
+        let Some(slot) = self.recording else {
+            return;
+        };
+        let frame = &mut self.frames[slot];
+        if frame.scopes.len() as u32 >= self.max_scopes {
+            self.open.push(None);
+            return;
+        }
+
+        let index = frame.scopes.len();
+        unsafe {
+            builder
+                .write_timestamp(frame.timestamps.clone(), index as u32 * 2, PipelineStage::TopOfPipe)
+                .unwrap();
+        }
+        let statistics = match &frame.statistics {
+            Some(pool) if statistics && !self.statistics_open => {
+                let query = frame.statistics_count;
+                frame.statistics_count += 1;
+                unsafe {
+                    builder.begin_query(pool.clone(), query, QueryControlFlags::default()).unwrap();
+                }
+                self.statistics_open = true;
+                Some(query)
+            }
+            _ => None,
+        };
+
+        frame.scopes.push(RecordedScope {
+            name: name.to_string(),
+            depth: self.open.len(),
+            statistics,
+        });
+        self.open.push(Some(index));
+    }
+
+    fn accumulate(&mut self, timing: &ScopeTiming) {
+        let index = match self.stats.iter().position(|stats| stats.name == timing.name) {
+            Some(index) => index,
+            None => {
+                self.stats.push(ScopeStats::new(&timing.name));
+                self.stats.len() - 1
+            }
+        };
+        let stats = &mut self.stats[index];
+        stats.count += 1;
+        stats.total_ms += timing.duration_ms as f64;
+        stats.min_ms = stats.min_ms.min(timing.duration_ms);
+        stats.max_ms = stats.max_ms.max(timing.duration_ms);
+        if let Some(statistics) = &timing.statistics {
+            stats.statistics_count += 1;
+            stats.statistics.add(statistics);
+        }
+    }
+}
+
+//区间名中的引号、反斜杠和控制字符需要转义
+pub fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            ch if (ch as u32) < 0x20 => write!(escaped, "\\u{:04x}", ch as u32).unwrap(),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
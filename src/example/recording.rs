@@ -0,0 +1,298 @@
+//输入录制和回放
+//录制文件是文本格式，每行一个事件：<帧号> <秒数> <事件> <参数...>，秒数是距离开始录制的时间，# 开头的行是注释。
+//  button <按键名> down|up    按键和鼠标按钮，按键名和按键绑定文件相同
+//  motion <dx> <dy>            鼠标移动
+//  wheel <行数>                滚轮
+//  cursor <x> <y>              光标位置
+//  modifiers <位>              修饰键，winit ModifiersState 的位
+//  focus 0|1                   窗口焦点
+//  resize <宽> <高>            窗口大小（物理像素）
+//  scale <缩放比例>            窗口的缩放比例，移到另一个显示器时会变化
+//  frame <帧时间>              一帧结束，前面的事件属于这一帧
+//回放时不打开窗口，按帧把事件交给 HeadlessWindow，它和窗口走同一条每帧的更新和绘制路径（HUD、调试线条、截图键都会重现），
+//只是交换链换成了离屏图像；帧时间使用录制的值而不是真实时间，最后一帧的画面保存到输出文件
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+use winit::event::ModifiersState;
+use crate::example::input::{binding_name, parse_binding, InputBindings, InputEvent};
+use crate::example::render_mesh::RenderOptions;
+use crate::example::scene::load_scene;
+use crate::example::window_manager::HeadlessWindow;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RecordedEvent {
+    Input(InputEvent),
+    Resized([u32; 2]),
+    ScaleFactorChanged(f32),
+}
+
+//事件和它发生时距离开始录制的秒数
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedEvent {
+    pub time: f32,
+    pub event: RecordedEvent,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RecordedFrame {
+    pub number: u64,
+    //帧结束时距离开始录制的秒数
+    pub time: f32,
+    pub delta_time: f32,
+    pub events: Vec<TimedEvent>,
+}
+
+pub struct InputRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> Result<InputRecorder, String> {
+        let file = File::create(path).map_err(|e| format!("failed to create {}: {e}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        let _ = writeln!(writer, "# my_winit input recording");
+        Ok(InputRecorder {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, frame: u64, event: &RecordedEvent) {
+        let time = self.start.elapsed().as_secs_f32();
+        self.record_at(frame, time, event);
+    }
+
+    //事件循环退出时不会返回，每帧都写入文件，避免丢失最后的事件
+    pub fn end_frame(&mut self, frame: u64, delta_time: f32) {
+        let time = self.start.elapsed().as_secs_f32();
+        self.end_frame_at(frame, time, delta_time);
+    }
+
+    //使用指定的时间而不是真实时间写入事件
+    pub fn record_at(&mut self, frame: u64, time: f32, event: &RecordedEvent) {
+        let Some(text) = format_event(event) else {
+            return;
+        };
+        let _ = writeln!(self.writer, "{frame} {time} {text}");
+    }
+
+    pub fn end_frame_at(&mut self, frame: u64, time: f32, delta_time: f32) {
+        let _ = writeln!(self.writer, "{frame} {time} frame {delta_time}");
+        let _ = self.writer.flush();
+    }
+}
+
+//f32 的 Display 输出能精确解析回原来的值，回放得到的相机和录制时完全相同
+fn format_event(event: &RecordedEvent) -> Option<String> {
+    let text = match event {
+        RecordedEvent::Input(InputEvent::Button { binding, pressed }) => {
+            format!("button {} {}", binding_name(*binding)?, if *pressed { "down" } else { "up" })
+        }
+        RecordedEvent::Input(InputEvent::MouseMotion { delta }) => format!("motion {} {}", delta[0], delta[1]),
+        RecordedEvent::Input(InputEvent::MouseWheel { lines }) => format!("wheel {lines}"),
+        RecordedEvent::Input(InputEvent::CursorMoved { position }) => format!("cursor {} {}", position[0], position[1]),
+        RecordedEvent::Input(InputEvent::Modifiers(modifiers)) => format!("modifiers {}", modifiers.bits()),
+        RecordedEvent::Input(InputEvent::Focused(focused)) => format!("focus {}", *focused as u8),
+        RecordedEvent::Resized(size) => format!("resize {} {}", size[0], size[1]),
+        RecordedEvent::ScaleFactorChanged(scale_factor) => format!("scale {scale_factor}"),
+    };
+    Some(text)
+}
+
+pub fn load_recording(path: &Path) -> Result<Vec<RecordedFrame>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    parse_recording(&text).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+pub fn parse_recording(text: &str) -> Result<Vec<RecordedFrame>, String> {
+    let mut frames = Vec::new();
+    let mut current = RecordedFrame::default();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = || format!("line {}: invalid event: {line}", i + 1);
+
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let [frame, time, kind, args @ ..] = tokens.as_slice() else {
+            return Err(error());
+        };
+        let number = |index: usize| args.get(index).and_then(|value| value.parse::<f32>().ok()).ok_or_else(error);
+        current.number = frame.parse().map_err(|_| error())?;
+        let time = time.parse::<f32>().map_err(|_| error())?;
+
+        let input = match *kind {
+            "frame" => {
+                current.time = time;
+                current.delta_time = number(0)?;
+                frames.push(std::mem::take(&mut current));
+                continue;
+            }
+            "resize" => {
+                let size = [number(0)? as u32, number(1)? as u32];
+                current.events.push(TimedEvent { time, event: RecordedEvent::Resized(size) });
+                continue;
+            }
+            "scale" => {
+                current.events.push(TimedEvent { time, event: RecordedEvent::ScaleFactorChanged(number(0)?) });
+                continue;
+            }
+            "button" => {
+                let binding = args.first().and_then(|name| parse_binding(name)).ok_or_else(error)?;
+                let pressed = match args.get(1) {
+                    Some(&"down") => true,
+                    Some(&"up") => false,
+                    _ => return Err(error()),
+                };
+                InputEvent::Button { binding, pressed }
+            }
+            "motion" => InputEvent::MouseMotion { delta: [number(0)?, number(1)?] },
+            "wheel" => InputEvent::MouseWheel { lines: number(0)? },
+            "cursor" => InputEvent::CursorMoved { position: [number(0)?, number(1)?] },
+            "modifiers" => {
+                let bits = args.first().and_then(|value| value.parse().ok()).ok_or_else(error)?;
+                InputEvent::Modifiers(ModifiersState::from_bits_truncate(bits))
+            }
+            "focus" => InputEvent::Focused(number(0)? != 0.0),
+            _ => return Err(error()),
+        };
+        current.events.push(TimedEvent { time, event: RecordedEvent::Input(input) });
+    }
+
+    //程序在一帧中间退出时，最后的事件没有 frame 行，当作帧时间为0、在最后一个事件时结束的一帧
+    if let Some(last) = current.events.last() {
+        current.time = last.time;
+        frames.push(current);
+    }
+    Ok(frames)
+}
+
+//回放录制的输入并把最后一帧的画面保存到 output
+pub fn replay_recording(model: &Path, recording: &Path, output: &Path, options: RenderOptions, bindings: InputBindings) {
+    let scene = match load_scene(model) {
+        Ok(scene) => scene,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+    let frames = match load_recording(recording) {
+        Ok(frames) => frames,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+
+    //录制开始时的 Resized 事件会设置窗口真正的大小
+    let mut window = HeadlessWindow::new(&scene, options, bindings, [1024, 1024]);
+    let mut drawn = false;
+    for (i, frame) in frames.iter().enumerate() {
+        for event in &frame.events {
+            window.handle_event(&event.event);
+        }
+        if i + 1 == frames.len() {
+            window.request_capture(output.to_path_buf());
+        }
+        drawn = window.frame(frame.delta_time);
+    }
+    if !drawn {
+        println!("the window is minimized at the end of the recording, {} was not saved", output.display());
+    }
+    window.finish();
+    println!("replayed {} frames", frames.len());
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::event::MouseButton;
+    use crate::example::input::Binding;
+    use super::*;
+
+    #[test]
+    fn recording_round_trips() {
+        let path = std::env::temp_dir().join(format!("my_winit-recording-{}.rec", std::process::id()));
+        let at = |time: f32, event: RecordedEvent| TimedEvent { time, event };
+        let frames = vec![
+            RecordedFrame {
+                number: 0,
+                time: 0.001,
+                delta_time: 0.0,
+                events: vec![
+                    at(0.000_25, RecordedEvent::Resized([1280, 720])),
+                    at(0.000_5, RecordedEvent::ScaleFactorChanged(1.5)),
+                ],
+            },
+            RecordedFrame {
+                number: 1,
+                time: 0.017_666_668,
+                delta_time: 0.016_666_668,
+                events: vec![
+                    at(0.002, RecordedEvent::Input(InputEvent::Focused(true))),
+                    at(0.0105, RecordedEvent::Input(InputEvent::Button { binding: parse_binding("W").unwrap(), pressed: true })),
+                    at(0.0105, RecordedEvent::Input(InputEvent::Button { binding: Binding::Mouse(MouseButton::Left), pressed: true })),
+                    at(0.011, RecordedEvent::Input(InputEvent::Modifiers(ModifiersState::SHIFT | ModifiersState::CTRL))),
+                    at(0.012, RecordedEvent::Input(InputEvent::CursorMoved { position: [640.25, 360.5] })),
+                    at(0.012, RecordedEvent::Input(InputEvent::MouseMotion { delta: [-3.125, 0.1] })),
+                    at(0.015, RecordedEvent::Input(InputEvent::MouseWheel { lines: -1.0 })),
+                ],
+            },
+            RecordedFrame {
+                number: 2,
+                time: 51.2,
+                delta_time: 0.033_333_335,
+                events: vec![
+                    at(51.18, RecordedEvent::Input(InputEvent::Button { binding: parse_binding("W").unwrap(), pressed: false })),
+                    at(51.19, RecordedEvent::Resized([0, 0])),
+                ],
+            },
+        ];
+
+        let mut recorder = InputRecorder::create(&path).unwrap();
+        for frame in &frames {
+            for event in &frame.events {
+                recorder.record_at(frame.number, event.time, &event.event);
+            }
+            recorder.end_frame_at(frame.number, frame.time, frame.delta_time);
+        }
+        drop(recorder);
+
+        let loaded = load_recording(&path);
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(loaded.unwrap(), frames);
+    }
+
+    #[test]
+    fn timestamps_are_parsed_per_event() {
+        let frames = parse_recording("3 1.25 focus 1\n3 1.5 wheel 2\n3 1.75 frame 0.5\n").unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].number, 3);
+        assert_eq!(frames[0].time, 1.75);
+        let times: Vec<f32> = frames[0].events.iter().map(|event| event.time).collect();
+        assert_eq!(times, [1.25, 1.5]);
+    }
+
+    #[test]
+    fn unfinished_frame_is_kept() {
+        let frames = parse_recording("# comment\n\n0 0.1 focus 1\n0 0.2 frame 0.5\n1 0.3 wheel 2\n").unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].delta_time, 0.5);
+        assert_eq!(frames[1].number, 1);
+        assert_eq!(frames[1].time, 0.3);
+        assert_eq!(frames[1].delta_time, 0.0);
+        let wheel = RecordedEvent::Input(InputEvent::MouseWheel { lines: 2.0 });
+        assert_eq!(frames[1].events, vec![TimedEvent { time: 0.3, event: wheel }]);
+    }
+
+    #[test]
+    fn invalid_lines_are_rejected() {
+        for text in ["0", "0 0.1", "0 frame 0.1", "x 0.1 frame 0.1", "0 soon frame 0.1", "0 0.1 jump 1", "0 0.1 button W sideways", "0 0.1 button NoSuchKey down", "0 0.1 motion 1"] {
+            assert!(parse_recording(text).is_err(), "{text}");
+        }
+    }
+}
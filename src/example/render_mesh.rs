@@ -0,0 +1,647 @@
+//网格渲染
+//把场景上传到GPU，并用网格管线绘制到窗口或离屏图像上
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use cgmath::{Deg, EuclideanSpace, Matrix4, Point3, Rad, Vector3, Vector4};
+use image::{ImageBuffer, Rgba, RgbaImage};
+use vulkano::buffer::{BufferUsage, Subbuffer};
+use vulkano::buffer::allocator::SubbufferAllocator;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, CopyImageToBufferInfo, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, Features, Queue};
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{AttachmentImage, ImageDimensions, ImageUsage, ImmutableImage, MipmapsCount};
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::Framebuffer;
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use vulkano::sync;
+use vulkano::sync::GpuFuture;
+use crate::example::buffer::{choose_device, create_buffer_allocator, create_device_with_features, create_instance, create_iter_buffer, create_memory_allocator, get_queue};
+use crate::example::camera::get_bounding_sphere;
+use crate::example::hdr::{FormatConverter, ReadbackFormat, ReadbackImage};
+use crate::example::input::InputBindings;
+use crate::example::mesh::{MeshGroup, MeshVertex};
+use crate::example::mesh_glsl::vs;
+use crate::example::pipeline_desc::{BlendMode, MeshPipelines, PipelineDesc};
+use crate::example::scene::{load_scene, Scene};
+use crate::example::text::{load_text_font, TextLabel, TextRenderer};
+use crate::example::window_config::WindowConfig;
+use crate::example::windows::{create_scene_window, create_uniform_buffer_allocator, get_descriptor_set, get_framebuffers, get_render_pass, get_uniform_subbuffer};
+
+//渲染选项，窗口和离屏渲染共用
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    //点云的点大小（逻辑像素），换算成物理像素后大于1需要设备支持 large_points
+    pub point_size: f32,
+    //在模型上叠加线框
+    pub wireframe: bool,
+    //窗口的缩放比例（物理像素 / 逻辑像素），高分屏上点的大小和线宽按它放大
+    //离屏渲染时可以设置成2，得到和高分屏窗口相同的画面
+    pub scale_factor: f32,
+    //在场景上叠加调试线条（网格、坐标轴、包围盒）
+    pub debug: bool,
+    //叠加在画面上的文字，以及使用的字体，没有指定字体时使用系统字体
+    pub labels: Vec<TextLabel>,
+    pub font: Option<PathBuf>,
+    //让场景随 time uniform 旋转、颜色闪烁，默认窗口的三角形使用
+    pub animate: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            point_size: 2.0,
+            wireframe: false,
+            scale_factor: 1.0,
+            debug: false,
+            labels: Vec::new(),
+            font: None,
+            animate: false,
+        }
+    }
+}
+
+//线框覆盖层的颜色，半透明叠加在模型上
+pub const WIREFRAME_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+
+//已上传到GPU的网格
+pub struct GpuMesh {
+    pub vertex_buffer: Subbuffer<[MeshVertex]>,
+    pub index_buffer: Subbuffer<[u32]>,
+    pub groups: Vec<MeshGroup>,
+    pub topology: PrimitiveTopology,
+    //包围盒中心，半透明排序时使用
+    pub center: [f32; 3],
+}
+
+//材质参数通过推送常量传入，贴图通过 set = 1 的描述符集绑定
+pub struct GpuMaterial {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 3],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub blend: BlendMode,
+    pub descriptor_set: Arc<PersistentDescriptorSet>,
+}
+
+//已上传到GPU的场景，节点层级已经展开成绘制列表
+//空网格无法创建缓冲区，对应的位置为 None
+pub struct GpuScene {
+    pub meshes: Vec<Option<GpuMesh>>,
+    pub materials: Vec<GpuMaterial>,
+    pub draws: Vec<(usize, Matrix4<f32>)>,
+    pub bounds: ([f32; 3], [f32; 3]),
+}
+
+//把场景上传到GPU，贴图的上传命令会被录制到 builder 中，调用者需要在绘制前执行它
+pub fn upload_scene(
+    scene: &Scene,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+) -> GpuScene {
+    let meshes = scene
+        .meshes
+        .iter()
+        .map(|mesh| (!mesh.indices.is_empty()).then(|| GpuMesh {
+            vertex_buffer: create_iter_buffer(
+                memory_allocator.clone(),
+                BufferUsage::VERTEX_BUFFER,
+                MemoryUsage::Upload,
+                mesh.vertices.clone(),
+            ),
+            index_buffer: create_iter_buffer(
+                memory_allocator.clone(),
+                BufferUsage::INDEX_BUFFER,
+                MemoryUsage::Upload,
+                mesh.indices.clone(),
+            ),
+            groups: mesh.groups.clone(),
+            topology: mesh.topology,
+            center: {
+                let (min, max) = mesh.bounds();
+                [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0, (min[2] + max[2]) / 2.0]
+            },
+        }))
+        .collect();
+
+    let sampler = Sampler::new(
+        pipeline.device().clone(),
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [SamplerAddressMode::Repeat; 3],
+            ..Default::default()
+        },
+    ).unwrap();
+
+    let layout = pipeline.layout().set_layouts().get(1).unwrap();
+    let materials = scene
+        .materials
+        .iter()
+        .map(|material| {
+            let base_color_texture = load_texture(
+                &memory_allocator,
+                material.base_color_texture.as_deref(),
+                Format::R8G8B8A8_SRGB,
+                builder,
+            );
+            let metallic_roughness_texture = load_texture(
+                &memory_allocator,
+                material.metallic_roughness_texture.as_deref(),
+                Format::R8G8B8A8_UNORM,
+                builder,
+            );
+            let descriptor_set = PersistentDescriptorSet::new(
+                descriptor_set_allocator,
+                layout.clone(),
+                [
+                    WriteDescriptorSet::image_view_sampler(0, base_color_texture, sampler.clone()),
+                    WriteDescriptorSet::image_view_sampler(1, metallic_roughness_texture, sampler.clone()),
+                ],
+            ).unwrap();
+            GpuMaterial {
+                base_color: material.base_color,
+                emissive: material.emissive,
+                metallic: material.metallic,
+                roughness: material.roughness,
+                blend: material.blend,
+                descriptor_set,
+            }
+        })
+        .collect();
+
+    GpuScene {
+        meshes,
+        materials,
+        draws: scene.draws(),
+        bounds: scene.bounds(),
+    }
+}
+
+//上传贴图，没有贴图时使用1x1的白色贴图，这样着色器中不需要分支
+pub fn load_texture(
+    memory_allocator: &StandardMemoryAllocator,
+    image: Option<&RgbaImage>,
+    format: Format,
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+) -> Arc<ImageView<ImmutableImage>> {
+    let (width, height, pixels) = match image {
+        Some(image) => (image.width(), image.height(), image.as_raw().clone()),
+        None => (1, 1, vec![255u8; 4]),
+    };
+
+    let image = ImmutableImage::from_iter(
+        memory_allocator,
+        pixels,
+        ImageDimensions::Dim2d {
+            width,
+            height,
+            array_layers: 1,
+        },
+        MipmapsCount::One,
+        format,
+        builder,
+    ).unwrap();
+
+    ImageView::new_default(image).unwrap()
+}
+
+//渲染器需要的设备特性，只开启设备支持的部分
+pub fn get_render_features(physical_device: &PhysicalDevice) -> Features {
+    let supported = physical_device.supported_features();
+    Features {
+        large_points: supported.large_points,
+        fill_mode_non_solid: supported.fill_mode_non_solid,
+        wide_lines: supported.wide_lines,
+        //GpuProfiler 的管线统计查询
+        pipeline_statistics_query: supported.pipeline_statistics_query,
+        ..Features::empty()
+    }
+}
+
+//一次绘制：网格中的一个分组，以及绘制它使用的管线
+struct SceneDraw<'a> {
+    mesh: &'a GpuMesh,
+    model: Matrix4<f32>,
+    group: &'a MeshGroup,
+    desc: PipelineDesc,
+}
+
+//录制整个场景的绘制命令
+//每个网格使用 desc 描述的管线（拓扑换成网格自己的拓扑），每个分组使用自己的材质。
+//不透明的分组先画，半透明的分组再按到相机的距离从远到近排序画，同一个网格内的三角形不排序。
+//overlay_color 不为 None 时用这个颜色不加光照地画所有分组，混合方式由 desc 决定，用于线框等覆盖层
+pub fn draw_scene(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipelines: &mut MeshPipelines,
+    desc: &PipelineDesc,
+    gpu_scene: &GpuScene,
+    uniform_set: Arc<PersistentDescriptorSet>,
+    view: &Matrix4<f32>,
+    overlay_color: Option<[f32; 4]>,
+) {
+    let mut opaque = Vec::new();
+    let mut transparent = Vec::new();
+
+    for (mesh, model) in &gpu_scene.draws {
+        let Some(gpu_mesh) = &gpu_scene.meshes[*mesh] else {
+            continue;
+        };
+        for group in &gpu_mesh.groups {
+            if group.index_count == 0 {
+                continue;
+            }
+            let draw = SceneDraw {
+                mesh: gpu_mesh,
+                model: *model,
+                group,
+                desc: PipelineDesc {
+                    topology: gpu_mesh.topology,
+                    ..*desc
+                },
+            };
+
+            let blend = gpu_scene.materials[group.material].blend;
+            if overlay_color.is_some() || blend.is_opaque() {
+                opaque.push(draw);
+            } else {
+                //观察空间中相机朝 -Z 方向看，z 越小离相机越远
+                let center = gpu_mesh.center;
+                let depth = (view * model * Vector4::new(center[0], center[1], center[2], 1.0)).z;
+                let desc = PipelineDesc::transparent(gpu_mesh.topology, blend);
+                transparent.push((depth, SceneDraw { desc, ..draw }));
+            }
+        }
+    }
+    transparent.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let draws = opaque
+        .into_iter()
+        .chain(transparent.into_iter().map(|(_, draw)| draw));
+
+    let mut bound_desc = None;
+    let mut bound_mesh: Option<&GpuMesh> = None;
+    for draw in draws {
+        let pipeline = pipelines.get(&draw.desc);
+        if bound_desc != Some(draw.desc) {
+            bound_desc = Some(draw.desc);
+            builder
+                .bind_pipeline_graphics(pipeline.clone())
+                .bind_descriptor_sets(
+                    PipelineBindPoint::Graphics,
+                    pipeline.layout().clone(),
+                    0,
+                    uniform_set.clone(),
+                );
+        }
+        if !matches!(bound_mesh, Some(mesh) if std::ptr::eq(mesh, draw.mesh)) {
+            bound_mesh = Some(draw.mesh);
+            builder
+                .bind_vertex_buffers(0, draw.mesh.vertex_buffer.clone())
+                .bind_index_buffer(draw.mesh.index_buffer.clone());
+        }
+
+        let material = &gpu_scene.materials[draw.group.material];
+        builder
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                pipeline.layout().clone(),
+                1,
+                material.descriptor_set.clone(),
+            )
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                vs::PushConstants {
+                    model: draw.model.into(),
+                    base_color: overlay_color.unwrap_or(material.base_color),
+                    emissive: [material.emissive[0], material.emissive[1], material.emissive[2], 0.0],
+                    metallic_roughness: [material.metallic, material.roughness],
+                    unlit: if overlay_color.is_some() { 1.0 } else { 0.0 },
+                },
+            )
+            .draw_indexed(draw.group.index_count, 1, draw.group.first_index, 0, 0)
+            .unwrap();
+    }
+}
+
+//绘制场景，开启线框时再叠加一遍线框
+//view 是观察矩阵乘世界矩阵，用于半透明排序
+pub fn draw_scene_with_options(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipelines: &mut MeshPipelines,
+    gpu_scene: &GpuScene,
+    uniform_set: Arc<PersistentDescriptorSet>,
+    view: &Matrix4<f32>,
+    options: &RenderOptions,
+) {
+    draw_scene(builder, pipelines, &PipelineDesc::default(), gpu_scene, uniform_set.clone(), view, None);
+
+    //点云没有面，线框模式对它们没有意义，但绘制一遍也不会出错
+    //线宽按缩放比例加宽，高分屏上的线框和普通屏幕上一样粗，设备不支持 wide_lines 时保持1像素
+    let mut wireframe = PipelineDesc::wireframe(PrimitiveTopology::TriangleList);
+    let scaled = PipelineDesc {
+        line_width: options.scale_factor.round().max(1.0),
+        ..wireframe
+    };
+    if pipelines.supports(&scaled) {
+        wireframe = scaled;
+    }
+    if options.wireframe && pipelines.supports(&wireframe) {
+        draw_scene(builder, pipelines, &wireframe, gpu_scene, uniform_set, view, Some(WIREFRAME_COLOR));
+    }
+}
+
+//uniform 数据中的观察矩阵乘世界矩阵
+pub fn get_view_matrix(uniforms: &vs::Data) -> Matrix4<f32> {
+    Matrix4::from(uniforms.view) * Matrix4::from(uniforms.world)
+}
+
+//根据包围盒摆放相机，让整个网格都出现在画面中，并绕Y轴缓慢旋转
+pub fn get_mesh_uniforms(bounds: ([f32; 3], [f32; 3]), aspect_ratio: f32, elapsed: f32, options: &RenderOptions) -> vs::Data {
+    let (center, radius) = get_bounding_sphere(bounds);
+
+    let world = Matrix4::from_angle_y(Rad(elapsed * 0.5)) * Matrix4::from_translation(-center.to_vec());
+    let view = Matrix4::look_at_rh(
+        Point3::new(0.0, 0.0, radius * 2.5),
+        Point3::origin(),
+        Vector3::unit_y(),
+    );
+    let proj = get_projection(aspect_ratio, radius * 0.01, radius * 10.0);
+
+    vs::Data {
+        world: world.into(),
+        view: view.into(),
+        proj: proj.into(),
+        time: elapsed,
+        point_size: options.point_size,
+        scale_factor: options.scale_factor,
+        animate: if options.animate { 1.0 } else { 0.0 },
+    }
+}
+
+//cgmath 的透视矩阵是 OpenGL 约定（Y 向上，深度 -1..1），这里转换成 Vulkan 约定（Y 向下，深度 0..1）
+pub fn get_projection(aspect_ratio: f32, near: f32, far: f32) -> Matrix4<f32> {
+    #[rustfmt::skip]
+    let correction = Matrix4::new(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, -1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    );
+    correction * cgmath::perspective(Deg(45.0), aspect_ratio, near, far)
+}
+
+//render-mesh 模式：加载模型文件，有输出路径时离屏渲染成PNG，否则在窗口中显示
+pub fn render_mesh(
+    path: &Path,
+    output: Option<&Path>,
+    options: RenderOptions,
+    bindings: InputBindings,
+    window: WindowConfig,
+    record: Option<&Path>,
+    profile: Option<&Path>,
+) {
+    let scene = match load_scene(path) {
+        Ok(scene) => scene,
+        Err(e) => {
+            println!("{e}");
+            return;
+        }
+    };
+
+    match output {
+        Some(output) => {
+            let image = render_scene_to_image(&scene, 1024, 1024, &options);
+            match image.save(output).map_err(|e| format!("failed to save {}: {e}", output.display())) {
+                Ok(()) => println!("saved {}", output.display()),
+                Err(e) => println!("{e}"),
+            }
+        }
+        None => create_scene_window(scene, options, bindings, window, record, profile),
+    }
+}
+
+//离屏渲染场景，相机自动摆放
+pub fn render_scene_to_image(scene: &Scene, width: u32, height: u32, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    render_scene_with_uniforms(scene, width, height, options, |bounds, aspect_ratio| {
+        get_mesh_uniforms(bounds, aspect_ratio, 0.0, options)
+    })
+}
+
+//离屏渲染场景，get_uniforms 根据场景包围盒和宽高比返回 uniform 数据
+pub fn render_scene_with_uniforms(
+    scene: &Scene,
+    width: u32,
+    height: u32,
+    options: &RenderOptions,
+    get_uniforms: impl FnOnce(([f32; 3], [f32; 3]), f32) -> vs::Data,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let mut renderer = SceneRenderer::new(scene, width, height);
+    let image = renderer.render(get_uniforms(renderer.bounds(), renderer.aspect_ratio()), options);
+    renderer.save_cache();
+    image
+}
+
+//离屏渲染器，和 operator_vertex 一样渲染到图像上再复制到缓冲区
+//设备、管线和上传的场景只创建一次，导出动画时可以连续渲染很多帧
+pub struct SceneRenderer {
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    uniform_buffer_allocator: SubbufferAllocator,
+    pipelines: MeshPipelines,
+    pipeline: Arc<GraphicsPipeline>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    //第一次有文字要画时才加载字体，text_loaded 保证只尝试一次
+    text: Option<TextRenderer>,
+    text_loaded: bool,
+    gpu_scene: GpuScene,
+    image: Arc<AttachmentImage>,
+    framebuffer: Arc<Framebuffer>,
+    buffer: Subbuffer<[u8]>,
+    converter: FormatConverter,
+    width: u32,
+    height: u32,
+}
+
+impl SceneRenderer {
+    //颜色附件使用 sRGB 格式，读回的数据可以直接保存为PNG
+    pub fn new(scene: &Scene, width: u32, height: u32) -> SceneRenderer {
+        SceneRenderer::with_format(scene, width, height, Format::R8G8B8A8_SRGB)
+    }
+
+    //使用其他格式的颜色附件，比如 hdr 的浮点格式，这时用 render_readback 读回
+    pub fn with_format(scene: &Scene, width: u32, height: u32, format: Format) -> SceneRenderer {
+        let instance = create_instance();
+        let physical_device = choose_device(instance);
+        let queue_index = get_queue(physical_device.clone());
+        let features = get_render_features(&physical_device);
+        let (device, mut queues) = create_device_with_features(physical_device.clone(), queue_index, features);
+        let queue = queues.next().unwrap();
+        let memory_allocator = Arc::new(create_memory_allocator(device.clone()));
+
+        //SAMPLED 用于 FormatConverter 读取
+        let image = AttachmentImage::with_usage(
+            &memory_allocator,
+            [width, height],
+            format,
+            ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC | ImageUsage::SAMPLED,
+        ).unwrap();
+
+        let render_pass = get_render_pass(device.clone(), format);
+        let framebuffer: Arc<Framebuffer> = get_framebuffers(&[image.clone()], &render_pass, &memory_allocator).remove(0);
+        let mut pipelines = MeshPipelines::new(device.clone(), render_pass.clone());
+        let pipeline = pipelines.get(&PipelineDesc::default());
+        let converter = FormatConverter::new(device.clone(), memory_allocator.clone(), pipelines.cache());
+
+        let command_buffer_allocator = create_buffer_allocator(device.clone());
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+        let uniform_buffer_allocator = create_uniform_buffer_allocator(memory_allocator.clone());
+
+        //上传场景和贴图，并等待上传完成
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue_index,
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+        let gpu_scene = upload_scene(scene, memory_allocator.clone(), &descriptor_set_allocator, &pipeline, &mut uploads);
+        sync::now(device.clone())
+            .then_execute(queue.clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        //只有 render 使用，复制的是 4 字节一个像素的格式
+        let buffer = create_iter_buffer(
+            memory_allocator.clone(),
+            BufferUsage::TRANSFER_DST,
+            MemoryUsage::Download,
+            (0..width * height * 4).map(|_| 0u8),
+        );
+
+        SceneRenderer {
+            device,
+            queue,
+            command_buffer_allocator,
+            descriptor_set_allocator,
+            uniform_buffer_allocator,
+            pipelines,
+            pipeline,
+            memory_allocator,
+            text: None,
+            text_loaded: false,
+            gpu_scene,
+            image,
+            framebuffer,
+            buffer,
+            converter,
+            width,
+            height,
+        }
+    }
+
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        self.gpu_scene.bounds
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    //渲染一帧并等待完成，只适用于 R8G8B8A8 格式的颜色附件
+    pub fn render(&mut self, uniforms: vs::Data, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let mut builder = self.record_scene(uniforms, options);
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(self.image.clone(), self.buffer.clone()))
+            .unwrap();
+        self.submit(builder);
+
+        let buffer_content = self.buffer.read().unwrap();
+        ImageBuffer::<Rgba<u8>, _>::from_raw(self.width, self.height, buffer_content.to_vec()).unwrap()
+    }
+
+    //渲染一帧，在 GPU 上把颜色附件转换成 format 读回，颜色先乘以 exposure；适用于任何格式的颜色附件
+    pub fn render_readback(&mut self, uniforms: vs::Data, options: &RenderOptions, format: ReadbackFormat, exposure: f32) -> ReadbackImage {
+        let mut builder = self.record_scene(uniforms, options);
+        let view = ImageView::new_default(self.image.clone()).unwrap();
+        let buffer = self.converter.record(&mut builder, view, format, exposure);
+        self.submit(builder);
+
+        let content = buffer.read().unwrap();
+        ReadbackImage::decode(&content, self.width, self.height, format)
+    }
+
+    //录制渲染通道，返回的命令缓冲区还可以继续录制读回的命令
+    fn record_scene(&mut self, uniforms: vs::Data, options: &RenderOptions) -> AutoCommandBufferBuilder<PrimaryAutoCommandBuffer> {
+        let view = get_view_matrix(&uniforms);
+        let uniform_subbuffer = get_uniform_subbuffer(&self.uniform_buffer_allocator, uniforms);
+        let descriptor_set = get_descriptor_set(&self.descriptor_set_allocator, &self.pipeline, uniform_subbuffer);
+
+        let mut builder = AutoCommandBufferBuilder::primary(
+            &self.command_buffer_allocator,
+            self.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).unwrap();
+
+        if !options.labels.is_empty() && !self.text_loaded {
+            self.text_loaded = true;
+            self.text = load_text_font(options.font.as_deref()).map(|font| {
+                TextRenderer::new(font, self.framebuffer.render_pass().clone(), self.memory_allocator.clone(), self.pipelines.cache())
+            });
+        }
+        //文字图集需要在渲染通道开始之前上传
+        let text = self
+            .text
+            .as_mut()
+            .and_then(|text| text.prepare(&mut builder, &options.labels, options.scale_factor));
+
+        builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into()), Some(1f32.into())],
+                    ..RenderPassBeginInfo::framebuffer(self.framebuffer.clone())
+                },
+                SubpassContents::Inline,
+            )
+            .unwrap()
+            .set_viewport(0, [Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [self.width as f32, self.height as f32],
+                depth_range: 0.0..1.0,
+            }]);
+        draw_scene_with_options(&mut builder, &mut self.pipelines, &self.gpu_scene, descriptor_set, &view, options);
+        if let (Some(renderer), Some(text)) = (&self.text, text) {
+            renderer.draw(&mut builder, text, [self.width as f32, self.height as f32]);
+        }
+        builder.end_render_pass().unwrap();
+        builder
+    }
+
+    fn submit(&self, builder: AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>) {
+        let command_buffer = builder.build().unwrap();
+
+        let future = sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        future.wait(None).unwrap();
+    }
+
+    pub fn save_cache(&self) {
+        self.pipelines.save_cache();
+    }
+}
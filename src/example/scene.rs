@@ -0,0 +1,111 @@
+//场景：节点层级 + 网格 + 材质
+//节点保存相对父节点的变换，绘制时展开成一组 (网格, 世界变换)
+use std::path::Path;
+use cgmath::{Matrix4, SquareMatrix, Vector4};
+use crate::example::gltf_scene::load_gltf;
+use crate::example::mesh::{load_obj, Material, Mesh};
+use crate::example::ply_mesh::load_ply;
+use crate::example::stl_mesh::load_stl;
+
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    pub name: String,
+    //相对父节点的变换
+    pub transform: Matrix4<f32>,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Scene {
+    pub nodes: Vec<SceneNode>,
+    //根节点在 nodes 中的索引
+    pub roots: Vec<usize>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+}
+
+impl Scene {
+    //只有一个网格的场景，OBJ、STL、PLY 等没有层级的格式使用
+    pub fn from_mesh(mesh: Mesh, materials: Vec<Material>) -> Scene {
+        Scene {
+            nodes: vec![SceneNode {
+                name: mesh.groups.first().map(|group| group.name.clone()).unwrap_or_default(),
+                transform: Matrix4::identity(),
+                children: Vec::new(),
+                mesh: Some(0),
+            }],
+            roots: vec![0],
+            meshes: vec![mesh],
+            materials,
+        }
+    }
+
+    //展开节点层级，返回每个需要绘制的网格以及它的世界变换
+    pub fn draws(&self) -> Vec<(usize, Matrix4<f32>)> {
+        let mut draws = Vec::new();
+        //用显式的栈代替递归，避免层级很深的文件导致栈溢出
+        let mut stack: Vec<(usize, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|root| (*root, Matrix4::identity()))
+            .collect();
+
+        while let Some((node_index, parent_transform)) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let transform = parent_transform * node.transform;
+            if let Some(mesh) = node.mesh {
+                draws.push((mesh, transform));
+            }
+            for child in &node.children {
+                stack.push((*child, transform));
+            }
+        }
+
+        draws
+    }
+
+    //整个场景在世界空间中的包围盒
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+
+        for (mesh, transform) in self.draws() {
+            let (mesh_min, mesh_max) = self.meshes[mesh].bounds();
+            //变换包围盒的8个角点
+            for corner in 0..8 {
+                let point = transform * Vector4::new(
+                    if corner & 1 == 0 { mesh_min[0] } else { mesh_max[0] },
+                    if corner & 2 == 0 { mesh_min[1] } else { mesh_max[1] },
+                    if corner & 4 == 0 { mesh_min[2] } else { mesh_max[2] },
+                    1.0,
+                );
+                for i in 0..3 {
+                    min[i] = min[i].min(point[i]);
+                    max[i] = max[i].max(point[i]);
+                }
+            }
+        }
+
+        if min[0] > max[0] {
+            return ([0.0; 3], [0.0; 3]);
+        }
+        (min, max)
+    }
+}
+
+//根据扩展名选择加载器
+pub fn load_scene(path: &Path) -> Result<Scene, String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_lowercase());
+
+    match extension.as_deref() {
+        Some("obj") => load_obj(path),
+        Some("gltf") | Some("glb") => load_gltf(path),
+        Some("stl") => load_stl(path),
+        Some("ply") => load_ply(path),
+        _ => Err(format!("unsupported model format: {}", path.display())),
+    }
+}
@@ -0,0 +1,154 @@
+//截图：把呈现到窗口的图像复制到回读缓冲区，GPU 执行完以后在后台线程转换格式并保存为PNG
+//复制命令录制在这一帧的命令缓冲区中，之后每帧检查一次缓冲区是否可以读取，不会等待GPU
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+use image::RgbaImage;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CopyImageToBufferInfo, PrimaryAutoCommandBuffer};
+use vulkano::format::Format;
+use vulkano::image::ImageAccess;
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
+
+struct PendingCapture {
+    buffer: Subbuffer<[u8]>,
+    width: u32,
+    height: u32,
+    format: Format,
+    path: PathBuf,
+}
+
+pub struct ScreenCapture {
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    //下一帧需要保存的路径
+    requests: Vec<PathBuf>,
+    //已经录制复制命令，等待GPU执行完成
+    pending: Vec<PendingCapture>,
+    saving: Vec<JoinHandle<()>>,
+}
+
+impl ScreenCapture {
+    pub fn new(memory_allocator: Arc<StandardMemoryAllocator>) -> ScreenCapture {
+        ScreenCapture {
+            memory_allocator,
+            requests: Vec::new(),
+            pending: Vec::new(),
+            saving: Vec::new(),
+        }
+    }
+
+    //请求在下一帧截图
+    pub fn request(&mut self, path: PathBuf) {
+        self.requests.push(path);
+    }
+
+    //有请求时录制复制命令，需要在渲染通道结束之后调用，图像需要带有 TRANSFER_SRC 用途
+    pub fn record(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image: Arc<dyn ImageAccess>,
+    ) {
+        if self.requests.is_empty() {
+            return;
+        }
+        let format = image.format();
+        if bytes_per_pixel(format).is_none() {
+            println!("screenshots of {format:?} images are not supported");
+            self.requests.clear();
+            return;
+        }
+
+        let [width, height] = image.dimensions().width_height();
+        let buffer = Buffer::new_slice::<u8>(
+            &self.memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                usage: MemoryUsage::Download,
+                ..Default::default()
+            },
+            width as u64 * height as u64 * 4,
+        ).expect("failed to create screenshot buffer");
+
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+            .unwrap();
+
+        //同一帧的多个请求共用一次复制
+        for path in self.requests.drain(..) {
+            self.pending.push(PendingCapture {
+                buffer: buffer.clone(),
+                width,
+                height,
+                format,
+                path,
+            });
+        }
+    }
+
+    //GPU 还在使用的缓冲区读取会失败，留到下一帧再检查
+    pub fn poll(&mut self) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let pixels = match self.pending[i].buffer.read() {
+                Ok(data) => data.to_vec(),
+                Err(_) => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let capture = self.pending.swap_remove(i);
+            self.saving.push(thread::spawn(move || {
+                let image = to_rgba_image(pixels, capture.width, capture.height, capture.format).unwrap();
+                match image.save(&capture.path) {
+                    Ok(()) => println!("saved {}", capture.path.display()),
+                    Err(e) => println!("failed to save {}: {e}", capture.path.display()),
+                }
+            }));
+        }
+        self.saving.retain(|handle| !handle.is_finished());
+    }
+
+    //退出前调用，调用前需要等待GPU执行完所有命令，这里等待后台线程保存完成
+    pub fn finish(&mut self) {
+        self.poll();
+        for capture in self.pending.drain(..) {
+            println!("screenshot {} was not captured", capture.path.display());
+        }
+        for handle in self.saving.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+//支持的格式每个像素都是4字节
+fn bytes_per_pixel(format: Format) -> Option<usize> {
+    match format {
+        Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM | Format::R8G8B8A8_SRGB | Format::R8G8B8A8_UNORM => Some(4),
+        _ => None,
+    }
+}
+
+//把回读的像素转换成 RGBA8
+//sRGB 格式中存储的已经是 sRGB 编码的值，UNORM 格式中存储的值会被显示器当作 sRGB 显示，两种情况都直接保存字节。
+//交换链的 alpha 通常没有意义（合成时被忽略），保存为不透明
+pub fn to_rgba_image(mut pixels: Vec<u8>, width: u32, height: u32, format: Format) -> Option<RgbaImage> {
+    bytes_per_pixel(format)?;
+    let bgra = matches!(format, Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM);
+    for pixel in pixels.chunks_exact_mut(4) {
+        if bgra {
+            pixel.swap(0, 2);
+        }
+        pixel[3] = 255;
+    }
+    RgbaImage::from_raw(width, height, pixels)
+}
+
+//当前目录下的 screenshot-<时间戳>.png
+pub fn get_screenshot_path() -> PathBuf {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    PathBuf::from(format!("screenshot-{}-{:03}.png", now.as_secs(), now.subsec_millis()))
+}
@@ -0,0 +1,493 @@
+//软件光栅化
+//纯 Rust 的 CPU 渲染器，语义和 Vulkan 图形管线相同：顶点着色器输出裁剪空间位置和要插值的属性，
+//三角形按 0 <= z <= w 裁剪，属性经过透视校正插值后交给片段着色器，
+//NDC 的 Y 向下，像素中心在 +0.5 处，顶点对齐到 1/256 像素，覆盖判断使用左上填充规则，颜色按 UNORM 四舍五入写入 RGBA8
+//没有 Vulkan 驱动时作为后备渲染器，也可以作为独立的参考实现检查 GPU 的输出
+use cgmath::{Matrix4, Vector4};
+use image::{Rgba, RgbaImage};
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::rasterization::{CullMode, FrontFace};
+use crate::example::graphics_pipeline::{triangle_vertices, MyVertex, VERTEX_IMAGE_SIZE};
+use crate::example::mesh::{Material, Mesh};
+
+//顶点位置对齐的精度，和常见 GPU 的 8 位亚像素精度相同
+const SUBPIXEL_STEPS: f64 = 256.0;
+
+//顶点着色器的输出：裁剪空间的位置和 N 个要插值的浮点属性
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipVertex<const N: usize> {
+    pub position: [f32; 4],
+    pub varyings: [f32; N],
+}
+
+//对应图形管线的光栅化、深度和混合状态
+#[derive(Clone, Copy, Debug)]
+pub struct RasterState {
+    pub cull_mode: CullMode,
+    pub front_face: FrontFace,
+    //深度测试使用 Less，和 DepthStencilState::simple_depth_test 相同
+    pub depth_test: bool,
+    pub depth_write: bool,
+    //和 ColorBlendState::blend_alpha 相同的 alpha 混合
+    pub blend: bool,
+}
+
+impl Default for RasterState {
+    fn default() -> Self {
+        RasterState {
+            cull_mode: CullMode::None,
+            front_face: FrontFace::CounterClockwise,
+            depth_test: false,
+            depth_write: false,
+            blend: false,
+        }
+    }
+}
+
+impl RasterState {
+    //带深度测试的不透明绘制
+    pub fn depth_tested() -> RasterState {
+        RasterState {
+            depth_test: true,
+            depth_write: true,
+            ..Default::default()
+        }
+    }
+}
+
+//投影到屏幕以后的顶点，属性已经除以 w
+#[derive(Clone, Copy)]
+struct ScreenVertex<const N: usize> {
+    x: f64,
+    y: f64,
+    depth: f32,
+    inv_w: f32,
+    varyings: [f32; N],
+}
+
+//颜色附件（RGBA8 UNORM）和深度附件
+pub struct SoftwareRasterizer {
+    color: RgbaImage,
+    depth: Vec<f32>,
+}
+
+impl SoftwareRasterizer {
+    //颜色清除为透明黑色，深度清除为1
+    pub fn new(width: u32, height: u32) -> SoftwareRasterizer {
+        SoftwareRasterizer {
+            color: RgbaImage::new(width, height),
+            depth: vec![1.0; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.color.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.color.height()
+    }
+
+    //和渲染通道开始时的清除值相同
+    pub fn clear(&mut self, color: [f32; 4]) {
+        let color = to_unorm(color);
+        for pixel in self.color.pixels_mut() {
+            *pixel = color;
+        }
+        self.depth.fill(1.0);
+    }
+
+    pub fn image(&self) -> &RgbaImage {
+        &self.color
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        self.color
+    }
+
+    //对应 draw(vertices.len(), 1, 0, 0)，每三个顶点一个三角形
+    pub fn draw<V, const N: usize>(
+        &mut self,
+        vertices: &[V],
+        state: &RasterState,
+        vertex_shader: impl Fn(&V) -> ClipVertex<N>,
+        fragment_shader: impl Fn(&[f32; N]) -> [f32; 4],
+    ) {
+        let indices: Vec<u32> = (0..vertices.len() as u32).collect();
+        self.draw_indexed(vertices, &indices, state, vertex_shader, fragment_shader);
+    }
+
+    //对应 draw_indexed，每个顶点只执行一次顶点着色器，每三个索引一个三角形
+    pub fn draw_indexed<V, const N: usize>(
+        &mut self,
+        vertices: &[V],
+        indices: &[u32],
+        state: &RasterState,
+        vertex_shader: impl Fn(&V) -> ClipVertex<N>,
+        fragment_shader: impl Fn(&[f32; N]) -> [f32; 4],
+    ) {
+        let shaded: Vec<ClipVertex<N>> = vertices.iter().map(vertex_shader).collect();
+        for triangle in indices.chunks_exact(3) {
+            let corners = [
+                shaded[triangle[0] as usize],
+                shaded[triangle[1] as usize],
+                shaded[triangle[2] as usize],
+            ];
+            //裁剪后的多边形按扇形分成三角形
+            let polygon = clip_polygon(&corners);
+            for i in 1..polygon.len().saturating_sub(1) {
+                self.rasterize_triangle([polygon[0], polygon[i], polygon[i + 1]], state, &fragment_shader);
+            }
+        }
+    }
+
+    //网格的三角形列表，不加光照：颜色是顶点颜色乘分组材质的基础颜色，transform 是投影矩阵 * 观察矩阵 * 世界矩阵
+    //点和线拓扑的网格不绘制
+    pub fn draw_mesh(&mut self, mesh: &Mesh, materials: &[Material], transform: Matrix4<f32>, state: &RasterState) {
+        if mesh.topology != PrimitiveTopology::TriangleList {
+            println!("the software rasterizer only draws triangle lists, skipping a {:?} mesh", mesh.topology);
+            return;
+        }
+        let default_material = Material::default();
+        for group in &mesh.groups {
+            let base_color = materials.get(group.material).unwrap_or(&default_material).base_color;
+            let first = group.first_index as usize;
+            let indices = &mesh.indices[first..first + group.index_count as usize];
+            self.draw_indexed(
+                &mesh.vertices,
+                indices,
+                state,
+                |vertex| {
+                    let [x, y, z] = vertex.position;
+                    ClipVertex {
+                        position: (transform * Vector4::new(x, y, z, 1.0)).into(),
+                        varyings: vertex.color,
+                    }
+                },
+                |color| std::array::from_fn(|i| color[i] * base_color[i]),
+            );
+        }
+    }
+
+    fn rasterize_triangle<const N: usize>(
+        &mut self,
+        corners: [ClipVertex<N>; 3],
+        state: &RasterState,
+        fragment_shader: &impl Fn(&[f32; N]) -> [f32; 4],
+    ) {
+        let width = self.width();
+        let height = self.height();
+        let Some([a, b, c]) = project(&corners, width, height) else {
+            return;
+        };
+
+        //Vulkan 在帧缓冲坐标（Y 向下）中按面积的符号判断正反面，面积为正时是逆时针
+        let cross = edge(&a, &b, c.x, c.y);
+        if cross == 0.0 {
+            return;
+        }
+        let counter_clockwise = cross < 0.0;
+        let front_facing = counter_clockwise == (state.front_face == FrontFace::CounterClockwise);
+        let culled = match state.cull_mode {
+            CullMode::None => false,
+            CullMode::Front => front_facing,
+            CullMode::Back => !front_facing,
+            CullMode::FrontAndBack => true,
+        };
+        if culled {
+            return;
+        }
+        //统一成 cross > 0 的顺序，三角形内部的边函数都不小于0
+        let (b, c, area) = if cross > 0.0 { (b, c, cross) } else { (c, b, -cross) };
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as u32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as u32;
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as i64).min(width as i64 - 1);
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as i64).min(height as i64 - 1);
+        if max_x < 0 || max_y < 0 {
+            return;
+        }
+        let top_left = [is_top_left(&b, &c), is_top_left(&c, &a), is_top_left(&a, &b)];
+
+        for y in min_y..=max_y as u32 {
+            let py = y as f64 + 0.5;
+            for x in min_x..=max_x as u32 {
+                let px = x as f64 + 0.5;
+                let weights = [edge(&b, &c, px, py), edge(&c, &a, px, py), edge(&a, &b, px, py)];
+                let covered = weights
+                    .iter()
+                    .zip(top_left)
+                    .all(|(weight, top_left)| *weight > 0.0 || (*weight == 0.0 && top_left));
+                if !covered {
+                    continue;
+                }
+                let [l0, l1, l2] = weights.map(|weight| (weight / area) as f32);
+
+                //深度在屏幕空间线性插值，属性透视校正插值
+                let depth = l0 * a.depth + l1 * b.depth + l2 * c.depth;
+                let index = y as usize * width as usize + x as usize;
+                if !(0.0..=1.0).contains(&depth) || (state.depth_test && depth >= self.depth[index]) {
+                    continue;
+                }
+                let inv_w = l0 * a.inv_w + l1 * b.inv_w + l2 * c.inv_w;
+                let varyings: [f32; N] = std::array::from_fn(|i| {
+                    (l0 * a.varyings[i] + l1 * b.varyings[i] + l2 * c.varyings[i]) / inv_w
+                });
+
+                let color = fragment_shader(&varyings);
+                if state.depth_write {
+                    self.depth[index] = depth;
+                }
+                let pixel = self.color.get_pixel_mut(x, y);
+                *pixel = if state.blend {
+                    let destination = pixel.0.map(|value| value as f32 / 255.0);
+                    let alpha = color[3];
+                    to_unorm(std::array::from_fn(|i| color[i] * alpha + destination[i] * (1.0 - alpha)))
+                } else {
+                    to_unorm(color)
+                };
+            }
+        }
+    }
+}
+
+//Sutherland-Hodgman 裁剪，保留 z >= 0 和 z <= w 的部分，属性线性插值
+fn clip_polygon<const N: usize>(corners: &[ClipVertex<N>; 3]) -> Vec<ClipVertex<N>> {
+    let mut polygon = corners.to_vec();
+    let planes: [fn(&[f32; 4]) -> f32; 2] = [|p| p[2], |p| p[3] - p[2]];
+    for distance in planes {
+        let mut clipped = Vec::with_capacity(polygon.len() + 1);
+        for i in 0..polygon.len() {
+            let current = polygon[i];
+            let next = polygon[(i + 1) % polygon.len()];
+            let d0 = distance(&current.position);
+            let d1 = distance(&next.position);
+            if d0 >= 0.0 {
+                clipped.push(current);
+            }
+            if (d0 >= 0.0) != (d1 >= 0.0) {
+                let t = d0 / (d0 - d1);
+                clipped.push(ClipVertex {
+                    position: std::array::from_fn(|j| current.position[j] + (next.position[j] - current.position[j]) * t),
+                    varyings: std::array::from_fn(|j| current.varyings[j] + (next.varyings[j] - current.varyings[j]) * t),
+                });
+            }
+        }
+        polygon = clipped;
+    }
+    polygon
+}
+
+//透视除法和视口变换，w 不为正时返回 None
+fn project<const N: usize>(corners: &[ClipVertex<N>; 3], width: u32, height: u32) -> Option<[ScreenVertex<N>; 3]> {
+    let mut projected = [None; 3];
+    for (corner, projected) in corners.iter().zip(projected.iter_mut()) {
+        let [x, y, z, w] = corner.position;
+        if w <= f32::EPSILON {
+            return None;
+        }
+        let inv_w = 1.0 / w;
+        let snap = |value: f64| (value * SUBPIXEL_STEPS).round() / SUBPIXEL_STEPS;
+        *projected = Some(ScreenVertex {
+            x: snap((x * inv_w) as f64 * 0.5 * width as f64 + 0.5 * width as f64),
+            y: snap((y * inv_w) as f64 * 0.5 * height as f64 + 0.5 * height as f64),
+            depth: z * inv_w,
+            inv_w,
+            varyings: corner.varyings.map(|value| value * inv_w),
+        });
+    }
+    Some(projected.map(Option::unwrap))
+}
+
+//点 p 在 a→b 哪一侧，三个边函数的和是三角形面积的两倍
+fn edge<const N: usize>(a: &ScreenVertex<N>, b: &ScreenVertex<N>, px: f64, py: f64) -> f64 {
+    (b.x - a.x) * (py - a.y) - (b.y - a.y) * (px - a.x)
+}
+
+//左上填充规则：正好落在边上的像素只属于上边（水平且内部在下面）和左边（内部在右边）
+fn is_top_left<const N: usize>(a: &ScreenVertex<N>, b: &ScreenVertex<N>) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+    (dy == 0.0 && dx > 0.0) || dy < 0.0
+}
+
+fn to_unorm(color: [f32; 4]) -> Rgba<u8> {
+    Rgba(color.map(|value| (value.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+//operator_vertex 的软件版本：蓝色背景上的红色三角形
+pub fn render_vertex_software() -> RgbaImage {
+    let mut rasterizer = SoftwareRasterizer::new(VERTEX_IMAGE_SIZE, VERTEX_IMAGE_SIZE);
+    rasterizer.clear([0.0, 0.0, 1.0, 1.0]);
+    rasterizer.draw(
+        &triangle_vertices(),
+        &RasterState::default(),
+        |vertex: &MyVertex| ClipVertex {
+            position: [vertex.position[0], vertex.position[1], 0.0, 1.0],
+            varyings: [],
+        },
+        |_| [1.0, 0.0, 0.0, 1.0],
+    );
+    rasterizer.into_image()
+}
+
+//operator_image 的软件版本：清除成蓝色的图像
+pub fn render_image_software() -> RgbaImage {
+    let mut rasterizer = SoftwareRasterizer::new(1024, 1024);
+    rasterizer.clear([0.0, 0.0, 1.0, 1.0]);
+    rasterizer.into_image()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [f32; 4] = [1.0; 4];
+
+    //NDC 中的二维三角形，z = 0，w = 1
+    fn ndc_vertex(position: &[f32; 2]) -> ClipVertex<0> {
+        ClipVertex { position: [position[0], position[1], 0.0, 1.0], varyings: [] }
+    }
+
+    //每个像素是否被三角形覆盖
+    fn coverage(size: u32, triangle: &[[f32; 2]; 3], state: &RasterState) -> Vec<bool> {
+        let mut rasterizer = SoftwareRasterizer::new(size, size);
+        rasterizer.draw(triangle, state, ndc_vertex, |_| WHITE);
+        rasterizer.image().pixels().map(|pixel| pixel[3] != 0).collect()
+    }
+
+    fn covered_count(size: u32, triangles: &[[[f32; 2]; 3]]) -> Vec<u32> {
+        let mut counts = vec![0; (size * size) as usize];
+        for triangle in triangles {
+            for (count, covered) in counts.iter_mut().zip(coverage(size, triangle, &RasterState::default())) {
+                *count += covered as u32;
+            }
+        }
+        counts
+    }
+
+    #[test]
+    fn shared_diagonal_edges_cover_each_pixel_once() {
+        //4x4 的图像上，两条对角线都经过像素中心
+        let counts = covered_count(4, &[
+            [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0]],
+            [[-1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]],
+        ]);
+        assert!(counts.iter().all(|count| *count == 1), "{counts:?}");
+
+        let counts = covered_count(4, &[
+            [[1.0, -1.0], [-1.0, 1.0], [-1.0, -1.0]],
+            [[1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]],
+        ]);
+        assert!(counts.iter().all(|count| *count == 1), "{counts:?}");
+    }
+
+    #[test]
+    fn shared_vertical_edge_covers_each_pixel_once() {
+        //共享的边在屏幕 x = 2.5 处，正好经过第 2 列像素的中心
+        let counts = covered_count(4, &[
+            [[-1.0, -1.0], [0.25, -1.0], [0.25, 1.0]],
+            [[0.25, -1.0], [1.0, 0.0], [0.25, 1.0]],
+        ]);
+        assert!(counts.iter().all(|count| *count <= 1), "{counts:?}");
+        for y in 0..4 {
+            assert_eq!(counts[y * 4 + 2], 1, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn cull_mode_uses_vulkan_winding() {
+        //Y 向下的帧缓冲中，屏幕上顺时针的三角形在 Vulkan 中是 Clockwise
+        let clockwise = [[-1.0, -1.0], [1.0, -1.0], [-1.0, 1.0]];
+        let counter_clockwise = [[-1.0, -1.0], [-1.0, 1.0], [1.0, -1.0]];
+        let drawn = |triangle: &[[f32; 2]; 3], cull_mode, front_face| {
+            let state = RasterState { cull_mode, front_face, ..Default::default() };
+            coverage(4, triangle, &state).contains(&true)
+        };
+
+        for triangle in [&clockwise, &counter_clockwise] {
+            assert!(drawn(triangle, CullMode::None, FrontFace::CounterClockwise));
+            assert!(drawn(triangle, CullMode::None, FrontFace::Clockwise));
+            assert!(!drawn(triangle, CullMode::FrontAndBack, FrontFace::CounterClockwise));
+        }
+        assert!(!drawn(&clockwise, CullMode::Back, FrontFace::CounterClockwise));
+        assert!(drawn(&clockwise, CullMode::Back, FrontFace::Clockwise));
+        assert!(drawn(&clockwise, CullMode::Front, FrontFace::CounterClockwise));
+        assert!(!drawn(&clockwise, CullMode::Front, FrontFace::Clockwise));
+        assert!(drawn(&counter_clockwise, CullMode::Back, FrontFace::CounterClockwise));
+        assert!(!drawn(&counter_clockwise, CullMode::Back, FrontFace::Clockwise));
+        assert!(!drawn(&counter_clockwise, CullMode::Front, FrontFace::CounterClockwise));
+        assert!(drawn(&counter_clockwise, CullMode::Front, FrontFace::Clockwise));
+    }
+
+    fn clip_vertex(z: f32, varying: f32) -> ClipVertex<1> {
+        ClipVertex { position: [0.0, 0.0, z, 1.0], varyings: [varying] }
+    }
+
+    #[test]
+    fn clip_polygon_keeps_triangles_inside_the_depth_range() {
+        let inside = [clip_vertex(0.0, 0.0), clip_vertex(0.5, 1.0), clip_vertex(1.0, 2.0)];
+        assert_eq!(clip_polygon(&inside), inside.to_vec());
+
+        let behind = [clip_vertex(-0.5, 0.0), clip_vertex(-0.1, 1.0), clip_vertex(-1.0, 2.0)];
+        assert!(clip_polygon(&behind).is_empty());
+        let beyond = [clip_vertex(1.5, 0.0), clip_vertex(1.1, 1.0), clip_vertex(2.0, 2.0)];
+        assert!(clip_polygon(&beyond).is_empty());
+    }
+
+    #[test]
+    fn clip_polygon_cuts_at_the_near_and_far_planes() {
+        //一个顶点在近平面后面：三角形变成四边形，新顶点在 z = 0 上，属性按同样的比例插值
+        let polygon = clip_polygon(&[clip_vertex(-1.0, 0.0), clip_vertex(0.5, 1.0), clip_vertex(0.5, 1.0)]);
+        assert_eq!(polygon.len(), 4);
+        assert!(polygon.iter().all(|vertex| vertex.position[2] > -1e-6 && vertex.position[2] <= 1.0));
+        let cut: Vec<_> = polygon.iter().filter(|vertex| vertex.position[2].abs() < 1e-6).collect();
+        assert_eq!(cut.len(), 2);
+        for vertex in cut {
+            assert!((vertex.varyings[0] - 2.0 / 3.0).abs() < 1e-6);
+        }
+
+        //一个顶点在远平面后面
+        let polygon = clip_polygon(&[clip_vertex(2.0, 0.0), clip_vertex(0.5, 1.0), clip_vertex(0.0, 1.0)]);
+        assert_eq!(polygon.len(), 4);
+        assert!(polygon.iter().all(|vertex| vertex.position[2] < vertex.position[3] + 1e-6));
+        assert_eq!(polygon.iter().filter(|vertex| (vertex.position[2] - 1.0).abs() < 1e-6).count(), 2);
+    }
+
+    #[test]
+    fn varyings_are_perspective_correct() {
+        //覆盖整张 8x8 图像的三角形，屏幕坐标是 (0, 0)、(16, 0)、(0, 16)，第二个顶点 w = 2，属性为 1，其余顶点 w = 1，属性为 0
+        let corners = [
+            ClipVertex { position: [-1.0, -1.0, 0.0, 1.0], varyings: [0.0] },
+            ClipVertex { position: [6.0, -2.0, 0.0, 2.0], varyings: [1.0] },
+            ClipVertex { position: [-1.0, 3.0, 0.0, 1.0], varyings: [0.0] },
+        ];
+        let mut rasterizer = SoftwareRasterizer::new(8, 8);
+        rasterizer.draw(&corners, &RasterState::default(), |corner| *corner, |value| [value[0], 0.0, 0.0, 1.0]);
+
+        for x in 0..8 {
+            //屏幕空间的重心坐标 l1 = px / 16，透视校正后是 (l1 / 2) / (1 - l1 / 2)
+            let l1 = (x as f32 + 0.5) / 16.0;
+            let expected = (l1 / 2.0) / (1.0 - l1 / 2.0);
+            let actual = rasterizer.image().get_pixel(x, 0)[0];
+            assert!(actual.abs_diff((expected * 255.0).round() as u8) <= 1, "pixel {x}: {actual}, expected {expected}");
+        }
+        //线性插值在最后一列会得到 0.47，透视校正的结果是 0.31
+        assert!(rasterizer.image().get_pixel(7, 0)[0] < 90);
+    }
+
+    #[test]
+    fn render_vertex_software_draws_a_red_triangle_on_blue() {
+        let image = render_vertex_software();
+        assert_eq!(image.dimensions(), (VERTEX_IMAGE_SIZE, VERTEX_IMAGE_SIZE));
+        let red = Rgba([255, 0, 0, 255]);
+        let blue = Rgba([0, 0, 255, 255]);
+        //三角形在屏幕上的顶点是 (256, 256)、(512, 768)、(768, 384)
+        for (x, y) in [(512, 469), (300, 300), (512, 700), (700, 400)] {
+            assert_eq!(*image.get_pixel(x, y), red, "({x}, {y})");
+        }
+        for (x, y) in [(10, 10), (1000, 1000), (512, 200), (300, 600), (800, 384)] {
+            assert_eq!(*image.get_pixel(x, y), blue, "({x}, {y})");
+        }
+    }
+}
@@ -0,0 +1,200 @@
+//STL 模型加载，支持二进制和 ASCII 两种格式
+//文件中存储的法线经常是错误的或者全为0，这里总是根据顶点重新计算面法线
+use std::fs;
+use std::path::Path;
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use crate::example::mesh::{face_normal, Material, Mesh, MeshGroup, MeshVertex, WHITE};
+use crate::example::scene::Scene;
+
+pub fn load_stl(path: &Path) -> Result<Scene, String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    let name = path.file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let mesh = parse_stl(&data, name).map_err(|e| format!("failed to load {}: {e}", path.display()))?;
+    Ok(Scene::from_mesh(mesh, vec![Material::default()]))
+}
+
+fn parse_stl(data: &[u8], name: String) -> Result<Mesh, String> {
+    let triangles = if is_binary_stl(data) {
+        read_binary_stl(data)?
+    } else {
+        read_ascii_stl(data)?
+    };
+    if triangles.is_empty() {
+        return Err(String::from("no faces"));
+    }
+
+    //每个三角形使用独立的三个顶点，这样面法线不会被相邻的面平均，显示为平直着色
+    let mut mesh = Mesh {
+        topology: PrimitiveTopology::TriangleList,
+        ..Default::default()
+    };
+    for [a, b, c] in triangles {
+        let normal = face_normal(a, b, c, true);
+        for position in [a, b, c] {
+            mesh.indices.push(mesh.vertices.len() as u32);
+            mesh.vertices.push(MeshVertex {
+                position,
+                normal,
+                uv: [0.0; 2],
+                color: WHITE,
+            });
+        }
+    }
+    mesh.groups.push(MeshGroup {
+        name,
+        first_index: 0,
+        index_count: mesh.indices.len() as u32,
+        material: 0,
+    });
+
+    Ok(mesh)
+}
+
+//二进制 STL 也可能以 "solid" 开头，所以只用文件长度判断：80字节头 + 4字节三角形数量 + 每个三角形50字节
+//长度对不上的文件都按 ASCII 读取，不是 STL 的文件会因为没有面而报错
+fn is_binary_stl(data: &[u8]) -> bool {
+    if data.len() < 84 {
+        return false;
+    }
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    data.len() == 84 + count * 50
+}
+
+fn read_binary_stl(data: &[u8]) -> Result<Vec<[[f32; 3]; 3]>, String> {
+    let count = u32::from_le_bytes([data[80], data[81], data[82], data[83]]) as usize;
+    if data.len() < 84 + count * 50 {
+        return Err(format!("expected {count} triangles, file is truncated"));
+    }
+
+    let read_f32 = |offset: usize| {
+        f32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+    };
+    let read_vec3 = |offset: usize| [read_f32(offset), read_f32(offset + 4), read_f32(offset + 8)];
+
+    Ok((0..count)
+        .map(|i| {
+            //每个三角形：法线(12字节) + 3个顶点(36字节) + 属性(2字节)
+            let offset = 84 + i * 50 + 12;
+            [read_vec3(offset), read_vec3(offset + 12), read_vec3(offset + 24)]
+        })
+        .collect())
+}
+
+//ASCII 格式：facet normal ... / outer loop / vertex x y z (x3) / endloop / endfacet
+fn read_ascii_stl(data: &[u8]) -> Result<Vec<[[f32; 3]; 3]>, String> {
+    let text = String::from_utf8_lossy(data);
+    let mut triangles = Vec::new();
+    let mut vertices: Vec<[f32; 3]> = Vec::with_capacity(3);
+
+    for line in text.lines() {
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("vertex") => {
+                let mut position = [0.0; 3];
+                for value in position.iter_mut() {
+                    *value = tokens
+                        .next()
+                        .and_then(|token| token.parse().ok())
+                        .ok_or_else(|| format!("invalid vertex line: {line}"))?;
+                }
+                vertices.push(position);
+            }
+            Some("endloop") => {
+                //多于3个顶点的面按扇形拆分成三角形
+                for i in 1..vertices.len().saturating_sub(1) {
+                    triangles.push([vertices[0], vertices[i], vertices[i + 1]]);
+                }
+                vertices.clear();
+            }
+            _ => {}
+        }
+    }
+
+    Ok(triangles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_STL: &str = "solid square
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid square
+";
+
+    //80字节头以 solid 开头的二进制 STL，有些导出工具就是这样写的
+    fn binary_stl(triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut data = b"solid exported by a CAD tool".to_vec();
+        data.resize(80, b' ');
+        data.extend((triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            //文件中的法线故意写成0，加载时会重新计算
+            data.extend([0u8; 12]);
+            for value in triangle.iter().flatten() {
+                data.extend(value.to_le_bytes());
+            }
+            data.extend([0u8; 2]);
+        }
+        data
+    }
+
+    fn positions(mesh: &Mesh) -> Vec<[f32; 3]> {
+        mesh.vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    #[test]
+    fn loads_ascii_stl() {
+        let mesh = parse_stl(ASCII_STL.as_bytes(), String::from("square")).unwrap();
+        assert_eq!(mesh.topology, PrimitiveTopology::TriangleList);
+        assert_eq!(
+            positions(&mesh),
+            [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(mesh.indices, [0, 1, 2, 3, 4, 5]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == [0.0, 0.0, 1.0]));
+        assert_eq!(mesh.groups.len(), 1);
+        assert_eq!(mesh.groups[0].name, "square");
+        assert_eq!(mesh.groups[0].index_count, 6);
+    }
+
+    #[test]
+    fn loads_binary_stl_starting_with_solid() {
+        let data = binary_stl(&[[[0.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]]]);
+        assert!(data.starts_with(b"solid"));
+        assert!(is_binary_stl(&data));
+
+        let mesh = parse_stl(&data, String::new()).unwrap();
+        assert_eq!(positions(&mesh), [[0.0, 0.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]]);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal == [1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn ascii_without_solid_header_is_not_binary() {
+        //长度超过84字节、但不以 solid 开头的 ASCII 文件
+        let text = ASCII_STL.trim_start_matches("solid square\n");
+        assert!(text.len() >= 84);
+        assert!(!is_binary_stl(text.as_bytes()));
+        assert_eq!(parse_stl(text.as_bytes(), String::new()).unwrap().vertices.len(), 6);
+    }
+
+    #[test]
+    fn truncated_binary_stl_is_rejected() {
+        let mut data = binary_stl(&[[[0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]; 2]);
+        data.truncate(data.len() - 10);
+        assert!(!is_binary_stl(&data));
+        assert_eq!(parse_stl(&data, String::new()).unwrap_err(), "no faces");
+    }
+}
@@ -0,0 +1,448 @@
+//文字渲染
+//用 fontdue 在CPU上光栅化 TTF/OTF 字形，放进一张单通道的字形图集纹理，每个字形画成一个带纹理的四边形。
+//字形在第一次用到时才加入图集，中文这样的大字符集不需要预先生成；图集满了以后清空，只保留当前帧用到的字形
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use fontdue::{Font, FontSettings};
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
+use vulkano::buffer::{BufferContents, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CopyBufferToImageInfo, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::DeviceOwned;
+use vulkano::format::Format;
+use vulkano::image::view::ImageView;
+use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::cache::PipelineCache;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::Vertex;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use crate::example::buffer::create_iter_buffer;
+use crate::example::mesh::WHITE;
+use crate::example::text_glsl::{fs, vs};
+
+const ATLAS_SIZE: u32 = 1024;
+//字形之间留1像素空白，线性过滤时不会采样到相邻的字形
+const GLYPH_PADDING: u32 = 1;
+
+//没有指定字体时依次尝试的系统字体，前面的都支持中文
+const SYSTEM_FONTS: [&str; 8] = [
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\simhei.ttf",
+    "/System/Library/Fonts/PingFang.ttc",
+    "/System/Library/Fonts/STHeiti Medium.ttc",
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+//一段文字，位置和字号都是逻辑像素，绘制时乘缩放比例
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextLabel {
+    //UTF-8 文字，\n 换行
+    pub text: String,
+    //左上角为原点，Left 时是文字的左上角，Center 和 Right 时是每行顶部的中点和右端
+    pub position: [f32; 2],
+    pub size: f32,
+    pub color: [f32; 4],
+    pub align: TextAlign,
+}
+
+impl TextLabel {
+    //16像素白色左对齐的文字
+    pub fn new(text: impl Into<String>, position: [f32; 2]) -> TextLabel {
+        TextLabel {
+            text: text.into(),
+            position,
+            size: 16.0,
+            color: WHITE,
+            align: TextAlign::Left,
+        }
+    }
+}
+
+#[derive(BufferContents, Vertex, Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct TextVertex {
+    #[format(R32G32_SFLOAT)]
+    pub position: [f32; 2],
+    #[format(R32G32_SFLOAT)]
+    pub uv: [f32; 2],
+    #[format(R32G32B32A32_SFLOAT)]
+    pub color: [f32; 4],
+}
+
+//图集中的一个字形，空格等没有图像的字形宽高为0
+#[derive(Clone, Copy, Debug)]
+struct Glyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    //字形图像左下角相对于笔位置和基线的偏移，Y 向上
+    xmin: f32,
+    ymin: f32,
+    advance: f32,
+}
+
+//CPU 端的字形图集，字形按行（shelf）从左到右排列，一行满了换到下一行
+pub struct GlyphAtlas {
+    font: Font,
+    pixels: Vec<u8>,
+    //按字符和像素字号缓存
+    glyphs: HashMap<(char, u32), Glyph>,
+    cursor: [u32; 2],
+    row_height: u32,
+    //图集有变化，还没有上传到GPU
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    pub fn new(font: Font) -> GlyphAtlas {
+        GlyphAtlas {
+            font,
+            pixels: vec![0; (ATLAS_SIZE * ATLAS_SIZE) as usize],
+            glyphs: HashMap::new(),
+            cursor: [0, 0],
+            row_height: 0,
+            dirty: true,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.glyphs.clear();
+        self.cursor = [0, 0];
+        self.row_height = 0;
+        self.dirty = true;
+    }
+
+    //把文字排版成三角形顶点（每个字形两个三角形），位置是物理像素
+    //图集放不下新的字形时返回 false
+    pub fn layout(&mut self, label: &TextLabel, scale_factor: f32, vertices: &mut Vec<TextVertex>) -> bool {
+        //按整数像素字号光栅化，缩放后的字形和原生字号一样清晰
+        let size = (label.size * scale_factor).round().max(1.0) as u32;
+        let px = size as f32;
+        let (ascent, line_height) = self
+            .font
+            .horizontal_line_metrics(px)
+            .map(|metrics| (metrics.ascent, metrics.new_line_size))
+            .unwrap_or((px, px * 1.2));
+        let origin = [label.position[0] * scale_factor, label.position[1] * scale_factor];
+
+        for (line_index, line) in label.text.lines().enumerate() {
+            //先算出这一行的宽度才能对齐
+            let mut glyphs = Vec::new();
+            let mut pen = 0.0;
+            let mut previous = None;
+            for ch in line.chars() {
+                if let Some(previous) = previous {
+                    pen += self.font.horizontal_kern(previous, ch, px).unwrap_or(0.0);
+                }
+                let Some(glyph) = self.glyph(ch, size) else {
+                    return false;
+                };
+                glyphs.push((pen, glyph));
+                pen += glyph.advance;
+                previous = Some(ch);
+            }
+
+            let x = match label.align {
+                TextAlign::Left => origin[0],
+                TextAlign::Center => origin[0] - pen / 2.0,
+                TextAlign::Right => origin[0] - pen,
+            };
+            let baseline = (origin[1] + ascent + line_height * line_index as f32).round();
+
+            for (offset, glyph) in glyphs {
+                if glyph.width == 0 || glyph.height == 0 {
+                    continue;
+                }
+                //对齐到整数像素，避免字形被线性过滤模糊
+                let x0 = (x + offset).round() + glyph.xmin;
+                let y0 = baseline - glyph.ymin - glyph.height as f32;
+                let x1 = x0 + glyph.width as f32;
+                let y1 = y0 + glyph.height as f32;
+                let u0 = glyph.x as f32 / ATLAS_SIZE as f32;
+                let v0 = glyph.y as f32 / ATLAS_SIZE as f32;
+                let u1 = (glyph.x + glyph.width) as f32 / ATLAS_SIZE as f32;
+                let v1 = (glyph.y + glyph.height) as f32 / ATLAS_SIZE as f32;
+
+                let vertex = |position: [f32; 2], uv: [f32; 2]| TextVertex {
+                    position,
+                    uv,
+                    color: label.color,
+                };
+                vertices.extend([
+                    vertex([x0, y0], [u0, v0]),
+                    vertex([x1, y0], [u1, v0]),
+                    vertex([x0, y1], [u0, v1]),
+                    vertex([x0, y1], [u0, v1]),
+                    vertex([x1, y0], [u1, v0]),
+                    vertex([x1, y1], [u1, v1]),
+                ]);
+            }
+        }
+        true
+    }
+
+    //取得字形，第一次用到时光栅化并放进图集，图集放不下时返回 None
+    fn glyph(&mut self, ch: char, size: u32) -> Option<Glyph> {
+        if let Some(glyph) = self.glyphs.get(&(ch, size)) {
+            return Some(*glyph);
+        }
+
+        let (metrics, bitmap) = self.font.rasterize(ch, size as f32);
+        let width = metrics.width as u32;
+        let height = metrics.height as u32;
+
+        if self.cursor[0] + width + GLYPH_PADDING > ATLAS_SIZE {
+            self.cursor = [0, self.cursor[1] + self.row_height];
+            self.row_height = 0;
+        }
+        if self.cursor[0] + width + GLYPH_PADDING > ATLAS_SIZE || self.cursor[1] + height + GLYPH_PADDING > ATLAS_SIZE {
+            return None;
+        }
+
+        let [x, y] = self.cursor;
+        for row in 0..height as usize {
+            let source = &bitmap[row * width as usize..(row + 1) * width as usize];
+            let start = (y as usize + row) * ATLAS_SIZE as usize + x as usize;
+            self.pixels[start..start + width as usize].copy_from_slice(source);
+        }
+        self.cursor[0] += width + GLYPH_PADDING;
+        self.row_height = self.row_height.max(height + GLYPH_PADDING);
+        if width > 0 && height > 0 {
+            self.dirty = true;
+        }
+
+        let glyph = Glyph {
+            x,
+            y,
+            width,
+            height,
+            xmin: metrics.xmin as f32,
+            ymin: metrics.ymin as f32,
+            advance: metrics.advance_width,
+        };
+        self.glyphs.insert((ch, size), glyph);
+        Some(glyph)
+    }
+}
+
+//排版完成、已经写入顶点缓冲区的文字
+pub struct PreparedText {
+    vertex_buffer: Subbuffer<[TextVertex]>,
+}
+
+//文字的管线、图集纹理和每帧的顶点缓冲区，窗口和离屏渲染共用
+pub struct TextRenderer {
+    atlas: GlyphAtlas,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    image: Arc<StorageImage>,
+    pipeline: Arc<GraphicsPipeline>,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    vertex_buffer_allocator: SubbufferAllocator,
+}
+
+impl TextRenderer {
+    //render_pass 需要和场景使用的相同，cache 使用 MeshPipelines 的管线缓存，随它一起保存
+    pub fn new(
+        font: Font,
+        render_pass: Arc<RenderPass>,
+        memory_allocator: Arc<StandardMemoryAllocator>,
+        cache: Arc<PipelineCache>,
+    ) -> TextRenderer {
+        let device = render_pass.device().clone();
+
+        let image = StorageImage::with_usage(
+            &memory_allocator,
+            ImageDimensions::Dim2d {
+                width: ATLAS_SIZE,
+                height: ATLAS_SIZE,
+                array_layers: 1,
+            },
+            Format::R8_UNORM,
+            ImageUsage::SAMPLED | ImageUsage::TRANSFER_DST,
+            ImageCreateFlags::empty(),
+            [],
+        ).unwrap();
+
+        let vs = vs::load(device.clone()).expect("failed to create shader module");
+        let fs = fs::load(device.clone()).expect("failed to create shader module");
+
+        //文字画在场景上面，不做深度测试
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_state(TextVertex::per_vertex())
+            .vertex_shader(vs.entry_point("main").unwrap(), ())
+            .input_assembly_state(InputAssemblyState::new())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .fragment_shader(fs.entry_point("main").unwrap(), ())
+            .color_blend_state(ColorBlendState::new(1).blend_alpha())
+            .render_pass(Subpass::from(render_pass, 0).unwrap())
+            .build_with_cache(cache)
+            .build(device.clone())
+            .unwrap();
+
+        let sampler = Sampler::new(
+            device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        ).unwrap();
+
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device);
+        let descriptor_set = PersistentDescriptorSet::new(
+            &descriptor_set_allocator,
+            pipeline.layout().set_layouts().get(0).unwrap().clone(),
+            [WriteDescriptorSet::image_view_sampler(
+                0,
+                ImageView::new_default(image.clone()).unwrap(),
+                sampler,
+            )],
+        ).unwrap();
+
+        let vertex_buffer_allocator = SubbufferAllocator::new(
+            memory_allocator.clone(),
+            SubbufferAllocatorCreateInfo {
+                buffer_usage: BufferUsage::VERTEX_BUFFER,
+                memory_usage: MemoryUsage::Upload,
+                ..Default::default()
+            },
+        );
+
+        TextRenderer {
+            atlas: GlyphAtlas::new(font),
+            memory_allocator,
+            image,
+            pipeline,
+            descriptor_set,
+            vertex_buffer_allocator,
+        }
+    }
+
+    //在渲染通道开始之前调用：排版文字，有新字形时把图集复制到纹理
+    pub fn prepare(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        labels: &[TextLabel],
+        scale_factor: f32,
+    ) -> Option<PreparedText> {
+        if labels.is_empty() {
+            return None;
+        }
+
+        let mut vertices = Vec::new();
+        if !self.layout(labels, scale_factor, &mut vertices) {
+            //图集满了，清空以后只放这一帧用到的字形
+            self.atlas.clear();
+            vertices.clear();
+            if !self.layout(labels, scale_factor, &mut vertices) {
+                println!("the glyph atlas is too small for the text in this frame");
+            }
+        }
+
+        if self.atlas.dirty {
+            self.atlas.dirty = false;
+            let buffer = create_iter_buffer(
+                self.memory_allocator.clone(),
+                BufferUsage::TRANSFER_SRC,
+                MemoryUsage::Upload,
+                self.atlas.pixels.iter().copied(),
+            );
+            builder
+                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(buffer, self.image.clone()))
+                .unwrap();
+        }
+
+        if vertices.is_empty() {
+            return None;
+        }
+        let vertex_buffer = self
+            .vertex_buffer_allocator
+            .allocate_slice::<TextVertex>(vertices.len() as u64)
+            .unwrap();
+        vertex_buffer.write().unwrap().copy_from_slice(&vertices);
+        Some(PreparedText { vertex_buffer })
+    }
+
+    //在渲染通道之内、场景画完之后调用，viewport_size 是物理像素
+    pub fn draw(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        prepared: PreparedText,
+        viewport_size: [f32; 2],
+    ) {
+        let vertex_count = prepared.vertex_buffer.len() as u32;
+        builder
+            .bind_pipeline_graphics(self.pipeline.clone())
+            .bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                self.pipeline.layout().clone(),
+                0,
+                self.descriptor_set.clone(),
+            )
+            .push_constants(
+                self.pipeline.layout().clone(),
+                0,
+                vs::PushConstants { viewport_size },
+            )
+            .bind_vertex_buffers(0, prepared.vertex_buffer)
+            .draw(vertex_count, 1, 0, 0)
+            .unwrap();
+    }
+
+    fn layout(&mut self, labels: &[TextLabel], scale_factor: f32, vertices: &mut Vec<TextVertex>) -> bool {
+        labels.iter().all(|label| self.atlas.layout(label, scale_factor, vertices))
+    }
+}
+
+pub fn load_font(path: &Path) -> Result<Font, String> {
+    let data = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    Font::from_bytes(data, FontSettings::default()).map_err(|e| format!("failed to load font {}: {e}", path.display()))
+}
+
+//系统中第一个存在的常见字体
+pub fn find_system_font() -> Option<PathBuf> {
+    SYSTEM_FONTS.iter().map(PathBuf::from).find(|path| path.is_file())
+}
+
+//加载指定的字体，没有指定时使用系统字体，失败时打印原因并返回 None
+pub fn load_text_font(path: Option<&Path>) -> Option<Font> {
+    let path = match path {
+        Some(path) => path.to_path_buf(),
+        None => match find_system_font() {
+            Some(path) => path,
+            None => {
+                println!("no font found, use --font to choose a TTF/OTF file");
+                return None;
+            }
+        },
+    };
+    match load_font(&path) {
+        Ok(font) => Some(font),
+        Err(e) => {
+            println!("{e}");
+            None
+        }
+    }
+}
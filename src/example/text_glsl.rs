@@ -0,0 +1,48 @@
+//文字着色器，顶点位置是像素坐标（左上角为原点），图集只有一个通道，存储字形的覆盖率
+pub mod vs {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 position;
+            layout(location = 1) in vec2 uv;
+            layout(location = 2) in vec4 color;
+
+            layout(location = 0) out vec2 v_uv;
+            layout(location = 1) out vec4 v_color;
+
+            layout(push_constant) uniform PushConstants {
+                vec2 viewport_size;
+            } push;
+
+            void main() {
+                v_uv = uv;
+                v_color = color;
+                //Vulkan 的 NDC 中 Y 向下，像素坐标可以直接换算
+                gl_Position = vec4(position / push.viewport_size * 2.0 - 1.0, 0.0, 1.0);
+            }
+        ",
+    }
+}
+
+pub mod fs {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        src: r"
+            #version 460
+
+            layout(location = 0) in vec2 v_uv;
+            layout(location = 1) in vec4 v_color;
+
+            layout(location = 0) out vec4 f_color;
+
+            layout(set = 0, binding = 0) uniform sampler2D atlas;
+
+            void main() {
+                float coverage = texture(atlas, v_uv).r;
+                f_color = vec4(v_color.rgb, v_color.a * coverage);
+            }
+        ",
+    }
+}
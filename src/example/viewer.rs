@@ -0,0 +1,150 @@
+//场景查看器的每帧逻辑：输入、相机和渲染选项
+//窗口的事件循环和输入回放都通过它处理输入，所以回放和当时的窗口得到相同的相机状态
+use winit::event::{DeviceEvent, WindowEvent};
+use crate::example::camera::{get_bounding_sphere, get_camera_uniforms, Camera, CameraMode};
+use crate::example::debug_draw::DebugDraw;
+use crate::example::input::{Action, InputBindings, InputEvent, InputState};
+use crate::example::mesh_glsl::vs;
+use crate::example::recording::{InputRecorder, RecordedEvent};
+use crate::example::render_mesh::RenderOptions;
+
+pub struct Viewer {
+    pub input: InputState,
+    pub bindings: InputBindings,
+    pub camera: Camera,
+    pub options: RenderOptions,
+    pub bounds: ([f32; 3], [f32; 3]),
+    //累计的帧时间，用作着色器中的时间，回放时由录制的帧时间累加得到
+    pub elapsed: f32,
+    pub frame: u64,
+    //按下截图键后为 true，由窗口取走并执行
+    pub screenshot_requested: bool,
+    //按下新窗口键后为 true，由窗口管理器取走并打开新窗口
+    pub window_requested: bool,
+    //按下全屏键后为 true，由窗口管理器取走并切换全屏
+    pub fullscreen_requested: bool,
+    //每帧重新生成的调试线条，options.debug 为 false 时为空
+    pub debug_draw: DebugDraw,
+    //显示帧率和帧时间，只对窗口有效
+    pub hud: bool,
+    recorder: Option<InputRecorder>,
+}
+
+impl Viewer {
+    pub fn new(bounds: ([f32; 3], [f32; 3]), options: RenderOptions, bindings: InputBindings) -> Viewer {
+        Viewer {
+            input: InputState::default(),
+            bindings,
+            camera: Camera::framing(bounds),
+            options,
+            bounds,
+            elapsed: 0.0,
+            frame: 0,
+            screenshot_requested: false,
+            window_requested: false,
+            fullscreen_requested: false,
+            debug_draw: DebugDraw::new(),
+            hud: false,
+            recorder: None,
+        }
+    }
+
+    //之后收到的所有事件都写入录制文件
+    pub fn start_recording(&mut self, recorder: InputRecorder) {
+        self.recorder = Some(recorder);
+    }
+
+    pub fn handle_event(&mut self, event: &RecordedEvent) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.record(self.frame, event);
+        }
+        match event {
+            RecordedEvent::Input(input) => self.input.apply(input),
+            RecordedEvent::ScaleFactorChanged(scale_factor) => self.options.scale_factor = *scale_factor,
+            RecordedEvent::Resized(_) => {}
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Resized(size) => {
+                self.handle_event(&RecordedEvent::Resized([size.width, size.height]));
+            }
+            //新的窗口大小之后会通过 Resized 传入
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.handle_event(&RecordedEvent::ScaleFactorChanged(*scale_factor as f32));
+            }
+            _ => {}
+        }
+        if let Some(input) = InputEvent::from_window_event(event) {
+            self.handle_event(&RecordedEvent::Input(input));
+        }
+    }
+
+    pub fn handle_device_event(&mut self, event: &DeviceEvent) {
+        if let Some(input) = InputEvent::from_device_event(event) {
+            self.handle_event(&RecordedEvent::Input(input));
+        }
+    }
+
+    //这一帧的事件处理完毕，执行动作并更新相机
+    pub fn update(&mut self, delta_time: f32, wireframe_supported: bool) {
+        if self.input.was_action_pressed(&self.bindings, Action::ToggleWireframe) {
+            if wireframe_supported {
+                self.options.wireframe = !self.options.wireframe;
+            } else {
+                println!("wireframe mode needs the fill_mode_non_solid device feature");
+            }
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::ToggleCamera) {
+            self.camera.toggle_mode();
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::ResetCamera) {
+            self.camera = Camera::framing(self.bounds);
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::Screenshot) {
+            self.screenshot_requested = true;
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::NewWindow) {
+            self.window_requested = true;
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::ToggleFullscreen) {
+            self.fullscreen_requested = true;
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::ToggleDebug) {
+            self.options.debug = !self.options.debug;
+        }
+        if self.input.was_action_pressed(&self.bindings, Action::ToggleHud) {
+            self.hud = !self.hud;
+        }
+        self.camera.update(&self.input, &self.bindings, delta_time);
+        self.input.end_frame();
+
+        self.debug_draw.clear();
+        if self.options.debug {
+            self.draw_debug();
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.end_frame(self.frame, delta_time);
+        }
+        self.elapsed += delta_time;
+        self.frame += 1;
+    }
+
+    //场景下方的地面网格、原点的坐标轴、场景包围盒，轨道相机还会画出目标点
+    fn draw_debug(&mut self) {
+        let (center, radius) = get_bounding_sphere(self.bounds);
+        let (min, max) = self.bounds;
+        self.debug_draw.grid([center.x, min[1], center.z], radius * 4.0, 16, [0.5, 0.5, 0.5, 0.5]);
+        self.debug_draw.axes([0.0; 3], radius * 0.5);
+        self.debug_draw.aabb(min, max, [1.0, 1.0, 0.0, 1.0]);
+        if self.camera.mode == CameraMode::Orbit {
+            self.debug_draw.sphere(self.camera.target.into(), radius * 0.02, [1.0, 0.0, 1.0, 1.0]);
+        }
+    }
+
+    pub fn get_uniforms(&self, aspect_ratio: f32) -> vs::Data {
+        get_camera_uniforms(&self.camera, self.bounds, aspect_ratio, self.elapsed, &self.options)
+    }
+}
@@ -0,0 +1,285 @@
+//窗口配置：标题、大小、是否可调整大小、边框、全屏模式、显示器和图标，可以从配置文件加载
+use std::path::{Path, PathBuf};
+use winit::dpi::{LogicalSize, PhysicalSize};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::monitor::MonitorHandle;
+use winit::window::{Fullscreen, Icon, Window, WindowBuilder};
+use crate::example::config::load_config;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum FullscreenMode {
+    #[default]
+    Windowed,
+    //无边框全屏，窗口覆盖整个显示器，不改变显示模式
+    Borderless,
+    //独占全屏，切换到显示器分辨率最高、刷新率最高的显示模式
+    Exclusive,
+}
+
+impl FullscreenMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FullscreenMode::Windowed => "windowed",
+            FullscreenMode::Borderless => "borderless",
+            FullscreenMode::Exclusive => "exclusive",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<FullscreenMode> {
+        [FullscreenMode::Windowed, FullscreenMode::Borderless, FullscreenMode::Exclusive]
+            .into_iter()
+            .find(|mode| mode.name() == name)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowConfig {
+    pub title: String,
+    //窗口客户区的逻辑大小
+    pub size: [u32; 2],
+    pub min_size: Option<[u32; 2]>,
+    pub max_size: Option<[u32; 2]>,
+    pub resizable: bool,
+    //是否显示标题栏和边框
+    pub decorations: bool,
+    pub fullscreen: FullscreenMode,
+    //全屏时使用的显示器序号，None 使用主显示器
+    pub monitor: Option<usize>,
+    pub icon: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: "my_winit".to_string(),
+            size: [800, 600],
+            min_size: None,
+            max_size: None,
+            resizable: true,
+            decorations: true,
+            fullscreen: FullscreenMode::Windowed,
+            monitor: None,
+            icon: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    //从配置文件加载，文件中没有出现的设置使用默认值
+    pub fn load(path: &Path) -> Result<WindowConfig, String> {
+        let mut config = WindowConfig::default();
+        for entry in load_config(path)? {
+            let invalid = || format!("{}: line {}: invalid value for {}: {}", path.display(), entry.line, entry.key, entry.value);
+            let value = entry.value.as_str();
+            match entry.key.as_str() {
+                "title" => config.title = value.to_string(),
+                "size" => config.size = parse_size(value).ok_or_else(invalid)?,
+                "min_size" => config.min_size = Some(parse_size(value).ok_or_else(invalid)?),
+                "max_size" => config.max_size = Some(parse_size(value).ok_or_else(invalid)?),
+                "resizable" => config.resizable = parse_bool(value).ok_or_else(invalid)?,
+                "decorations" => config.decorations = parse_bool(value).ok_or_else(invalid)?,
+                "fullscreen" => config.fullscreen = FullscreenMode::from_name(value).ok_or_else(invalid)?,
+                "monitor" => config.monitor = Some(value.parse().map_err(|_| invalid())?),
+                "icon" => config.icon = Some(PathBuf::from(value)),
+                key => return Err(format!("{}: line {}: unknown setting {key}", path.display(), entry.line)),
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn builder<T>(&self, event_loop: &EventLoopWindowTarget<T>) -> WindowBuilder {
+        let mut builder = WindowBuilder::new()
+            .with_title(self.title.clone())
+            .with_inner_size(LogicalSize::new(self.size[0], self.size[1]))
+            .with_resizable(self.resizable)
+            .with_decorations(self.decorations)
+            .with_fullscreen(get_fullscreen(self.fullscreen, get_monitor(event_loop, self.monitor)));
+        if let Some([width, height]) = self.min_size {
+            builder = builder.with_min_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some([width, height]) = self.max_size {
+            builder = builder.with_max_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some(path) = &self.icon {
+            match load_icon(path) {
+                Ok(icon) => builder = builder.with_window_icon(Some(icon)),
+                Err(e) => println!("{e}"),
+            }
+        }
+        builder
+    }
+}
+
+//按序号选择显示器，序号无效时使用主显示器，有些平台（Wayland）没有主显示器，这时使用第一个
+pub fn get_monitor<T>(event_loop: &EventLoopWindowTarget<T>, index: Option<usize>) -> Option<MonitorHandle> {
+    if let Some(index) = index {
+        match event_loop.available_monitors().nth(index) {
+            Some(monitor) => return Some(monitor),
+            None => println!("monitor {index} not found, using the primary monitor"),
+        }
+    }
+    event_loop
+        .primary_monitor()
+        .or_else(|| event_loop.available_monitors().next())
+}
+
+pub fn get_fullscreen(mode: FullscreenMode, monitor: Option<MonitorHandle>) -> Option<Fullscreen> {
+    match mode {
+        FullscreenMode::Windowed => None,
+        FullscreenMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+        FullscreenMode::Exclusive => {
+            let video_mode = monitor.and_then(|monitor| {
+                monitor.video_modes().max_by_key(|video_mode| {
+                    let size = video_mode.size();
+                    (size.width * size.height, video_mode.refresh_rate_millihertz(), video_mode.bit_depth())
+                })
+            });
+            match video_mode {
+                Some(video_mode) => Some(Fullscreen::Exclusive(video_mode)),
+                //找不到显示器或者显示器没有报告显示模式时退回无边框全屏
+                None => Some(Fullscreen::Borderless(None)),
+            }
+        }
+    }
+}
+
+//在窗口模式和配置的全屏模式之间切换，配置为 Windowed 时使用无边框全屏
+//使用配置中的显示器，没有配置或者序号无效时使用窗口当前所在的显示器
+//窗口大小变化后交换链需要重新创建
+pub fn toggle_fullscreen(window: &Window, config: &WindowConfig) {
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+        return;
+    }
+    let mode = match config.fullscreen {
+        FullscreenMode::Windowed => FullscreenMode::Borderless,
+        mode => mode,
+    };
+    let monitor = config.monitor.and_then(|index| {
+        let monitor = window.available_monitors().nth(index);
+        if monitor.is_none() {
+            println!("monitor {index} not found, using the current monitor");
+        }
+        monitor
+    });
+    window.set_fullscreen(get_fullscreen(mode, monitor.or_else(|| window.current_monitor())));
+}
+
+//窗口客户区的逻辑大小，交换链和视口使用的是物理大小 window.inner_size()
+pub fn get_logical_size(window: &Window) -> LogicalSize<f64> {
+    window.inner_size().to_logical(window.scale_factor())
+}
+
+//把逻辑大小换算成这个窗口上的物理像素
+pub fn to_physical_size(window: &Window, size: LogicalSize<f64>) -> PhysicalSize<u32> {
+    size.to_physical(window.scale_factor())
+}
+
+pub fn load_icon(path: &Path) -> Result<Icon, String> {
+    let image = image::open(path)
+        .map_err(|e| format!("failed to load icon {}: {e}", path.display()))?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).map_err(|e| format!("invalid icon {}: {e}", path.display()))
+}
+
+//"1280x720"
+fn parse_size(value: &str) -> Option<[u32; 2]> {
+    let (width, height) = value.split_once('x')?;
+    Some([width.trim().parse().ok()?, height.trim().parse().ok()?])
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    //写一个临时的窗口配置文件并加载
+    fn load_window_config(name: &str, text: &str) -> Result<WindowConfig, String> {
+        let path = std::env::temp_dir().join(format!("my_winit-{name}-{}.cfg", std::process::id()));
+        fs::write(&path, text).unwrap();
+        let config = WindowConfig::load(&path);
+        let _ = fs::remove_file(&path);
+        config
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1280x720"), Some([1280, 720]));
+        assert_eq!(parse_size("1280 x 720"), Some([1280, 720]));
+        assert_eq!(parse_size("0x0"), Some([0, 0]));
+        for value in ["", "1280", "1280x", "x720", "-1x720", "1280*720", "1280X720", "1280x720x3", "1e3x720"] {
+            assert_eq!(parse_size(value), None, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn booleans() {
+        for value in ["true", "yes", "on", "1"] {
+            assert_eq!(parse_bool(value), Some(true), "{value}");
+        }
+        for value in ["false", "no", "off", "0"] {
+            assert_eq!(parse_bool(value), Some(false), "{value}");
+        }
+        for value in ["", "True", "y", "2", "enabled"] {
+            assert_eq!(parse_bool(value), None, "{value:?}");
+        }
+    }
+
+    #[test]
+    fn load_all_settings() {
+        let text = "# window\ntitle = Demo window\nsize = 1280x720\nmin_size = 640x360\nmax_size = 1920 x 1080\n\
+            resizable = no\ndecorations = off\nfullscreen = exclusive\nmonitor = 1\nicon = assets/icon.png\n";
+        let config = load_window_config("window-all", text).unwrap();
+        assert_eq!(
+            config,
+            WindowConfig {
+                title: "Demo window".to_string(),
+                size: [1280, 720],
+                min_size: Some([640, 360]),
+                max_size: Some([1920, 1080]),
+                resizable: false,
+                decorations: false,
+                fullscreen: FullscreenMode::Exclusive,
+                monitor: Some(1),
+                icon: Some(PathBuf::from("assets/icon.png")),
+            }
+        );
+    }
+
+    #[test]
+    fn missing_settings_use_defaults() {
+        let config = load_window_config("window-defaults", "size = 1024x768\n").unwrap();
+        assert_eq!(config, WindowConfig { size: [1024, 768], ..WindowConfig::default() });
+    }
+
+    #[test]
+    fn unknown_setting_is_an_error() {
+        let error = load_window_config("window-unknown", "title = Demo\nvsync = on\n").unwrap_err();
+        assert!(error.ends_with(": line 2: unknown setting vsync"), "{error}");
+    }
+
+    #[test]
+    fn invalid_values_are_errors() {
+        let cases = [
+            ("size = 1280\n", "line 1: invalid value for size: 1280"),
+            ("min_size = 640x\n", "line 1: invalid value for min_size: 640x"),
+            ("\nresizable = maybe\n", "line 2: invalid value for resizable: maybe"),
+            ("decorations = 2\n", "line 1: invalid value for decorations: 2"),
+            ("fullscreen = Borderless\n", "line 1: invalid value for fullscreen: Borderless"),
+            ("monitor = -1\n", "line 1: invalid value for monitor: -1"),
+        ];
+        for (text, expected) in cases {
+            let error = load_window_config("window-invalid", text).unwrap_err();
+            assert!(error.ends_with(expected), "{error}");
+        }
+    }
+}
@@ -0,0 +1,822 @@
+//多窗口：所有窗口共用一个设备、队列、管线和已上传的场景，每个窗口有自己的表面、交换链、帧缓冲和查看器
+//事件按 WindowId 分发给对应的窗口，关闭一个窗口不会退出程序，最后一个窗口关闭时才退出
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::allocator::SubbufferAllocator;
+use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, ImageUsage, SwapchainImage};
+use vulkano::instance::{Instance, InstanceCreateInfo};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::input_assembly::PrimitiveTopology;
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::GraphicsPipeline;
+use vulkano::render_pass::{Framebuffer, RenderPass};
+use vulkano::swapchain::{self, AcquireError, PresentMode, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError, SwapchainPresentInfo};
+use vulkano::sync::future::FenceSignalFuture;
+use vulkano::sync::{self, FlushError, GpuFuture};
+use vulkano::VulkanLibrary;
+use vulkano_win::VkSurfaceBuild;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoopWindowTarget};
+use winit::window::{Window, WindowId};
+use crate::example::buffer::{choose_device, create_instance, create_memory_allocator, get_queue};
+use crate::example::debug_draw::{get_view_projection, DebugDrawRenderer};
+use crate::example::hud::{draw_frame_graph, get_hud_label, FrameStats, HudInfo};
+use crate::example::input::InputBindings;
+use crate::example::pipeline_desc::{MeshPipelines, PipelineDesc};
+use crate::example::profiler::GpuProfiler;
+use crate::example::recording::{InputRecorder, RecordedEvent};
+use crate::example::render_mesh::{get_render_features, get_view_matrix, upload_scene, GpuScene, RenderOptions};
+use crate::example::scene::Scene;
+use crate::example::screenshot::{get_screenshot_path, ScreenCapture};
+use crate::example::text::{load_text_font, TextLabel, TextRenderer};
+use crate::example::viewer::Viewer;
+use crate::example::window_config::{toggle_fullscreen, FullscreenMode, WindowConfig};
+use crate::example::windows::{
+    create_uniform_buffer_allocator, get_command_buffer, Overlays, get_descriptor_set, get_framebuffers, get_render_pass,
+    get_uniform_subbuffer, select_physical_device,
+};
+
+//get_command_buffer 每帧记录的区间数不超过这个数
+const PROFILER_SCOPES: u32 = 16;
+
+//所有窗口共用的设备和渲染资源，输入回放的 HeadlessWindow 也使用它
+//所有交换链使用同一种颜色格式，这样一个渲染通道和它创建的管线可以用于所有窗口
+struct RenderContext {
+    instance: Arc<Instance>,
+    physical_device: Arc<PhysicalDevice>,
+    device: Arc<Device>,
+    queue: Arc<Queue>,
+    memory_allocator: Arc<StandardMemoryAllocator>,
+    command_buffer_allocator: StandardCommandBufferAllocator,
+    uniform_buffer_allocator: SubbufferAllocator,
+    descriptor_set_allocator: StandardDescriptorSetAllocator,
+    image_format: Format,
+    render_pass: Arc<RenderPass>,
+    pipelines: MeshPipelines,
+    pipeline: Arc<GraphicsPipeline>,
+    debug_renderer: DebugDrawRenderer,
+    text_renderer: Option<TextRenderer>,
+    text_loaded: bool,
+    gpu_scene: GpuScene,
+    wireframe_supported: bool,
+}
+
+impl RenderContext {
+    //创建设备并上传场景，image_format 是所有交换链（或回放的离屏图像）使用的颜色格式
+    fn new(
+        instance: Arc<Instance>,
+        physical_device: Arc<PhysicalDevice>,
+        queue_family_index: u32,
+        device_extensions: DeviceExtensions,
+        image_format: Format,
+        scene: &Scene,
+    ) -> RenderContext {
+        let (device, mut queues) = Device::new(
+            physical_device.clone(),
+            DeviceCreateInfo {
+                queue_create_infos: vec![QueueCreateInfo {
+                    queue_family_index,
+                    ..Default::default()
+                }],
+                enabled_extensions: device_extensions,
+                enabled_features: get_render_features(&physical_device),
+                ..Default::default()
+            },
+        ).expect("failed to create device");
+
+        let queue = queues.next().unwrap();
+
+        let memory_allocator = Arc::new(create_memory_allocator(device.clone()));
+        let render_pass = get_render_pass(device.clone(), image_format);
+
+        let command_buffer_allocator =
+            StandardCommandBufferAllocator::new(device.clone(), Default::default());
+
+        //uniform缓冲区分配器
+        //SubbufferAllocator 是一个环形分配器：每帧分配一块新的子缓冲区，GPU仍在使用的旧数据不会被覆盖，
+        //当引用它的命令缓冲区执行完毕后，对应的内存会被自动回收，因此每帧更新不需要等待GPU
+        let uniform_buffer_allocator = create_uniform_buffer_allocator(memory_allocator.clone());
+        let descriptor_set_allocator = StandardDescriptorSetAllocator::new(device.clone());
+
+        //管线按描述创建并缓存，切换线框模式时不需要重新创建
+        let mut pipelines = MeshPipelines::new(device.clone(), render_pass.clone());
+        let pipeline = pipelines.get(&PipelineDesc::default());
+        let debug_renderer = DebugDrawRenderer::new(render_pass.clone(), memory_allocator.clone(), pipelines.cache());
+
+        //上传场景和贴图，并等待上传完成
+        let mut uploads = AutoCommandBufferBuilder::primary(
+            &command_buffer_allocator,
+            queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+        .unwrap();
+        let gpu_scene = upload_scene(scene, memory_allocator.clone(), &descriptor_set_allocator, &pipeline, &mut uploads);
+        sync::now(device.clone())
+            .then_execute(queue.clone(), uploads.build().unwrap())
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap()
+            .wait(None)
+            .unwrap();
+
+        let wireframe_supported = pipelines.supports(&PipelineDesc::wireframe(PrimitiveTopology::TriangleList));
+
+        RenderContext {
+            instance,
+            physical_device,
+            device,
+            queue,
+            memory_allocator,
+            command_buffer_allocator,
+            uniform_buffer_allocator,
+            descriptor_set_allocator,
+            image_format,
+            render_pass,
+            pipelines,
+            pipeline,
+            debug_renderer,
+            text_renderer: None,
+            text_loaded: false,
+            gpu_scene,
+            wireframe_supported,
+        }
+    }
+}
+
+//每个窗口自己的交换链和帧状态
+//不同窗口的提交链类型相同，装箱以后才能放在结构体中
+struct WindowState {
+    window: Arc<Window>,
+    swapchain: Arc<Swapchain>,
+    framebuffers: Vec<Arc<Framebuffer>>,
+    viewport: Viewport,
+    window_resized: bool,
+    recreate_swapchain: bool,
+    fences: Vec<Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>>,
+    previous_fence_i: usize,
+    viewer: Viewer,
+    screen_capture: ScreenCapture,
+    screenshots_supported: bool,
+    //HUD 显示的帧时间，隐藏时也持续记录
+    stats: FrameStats,
+    //每个交换链图像一个槽位，队列不支持时间戳查询时不记录
+    profiler: GpuProfiler,
+    //窗口关闭时把 GPU trace 写到这个文件
+    trace_path: Option<PathBuf>,
+}
+
+pub struct WindowManager {
+    context: RenderContext,
+    windows: HashMap<WindowId, WindowState>,
+    main_window: WindowId,
+    //新窗口使用同样的配置，但不全屏
+    config: WindowConfig,
+    //DeviceEvent 不属于任何窗口，只交给有焦点的窗口
+    focused: Option<WindowId>,
+    last_frame_time: Instant,
+}
+
+impl WindowManager {
+    //创建主窗口，并用它的表面选择物理设备和交换链格式
+    pub fn new<T>(
+        event_loop: &EventLoopWindowTarget<T>,
+        config: WindowConfig,
+        scene: &Scene,
+        options: RenderOptions,
+        bindings: InputBindings,
+    ) -> WindowManager {
+        let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
+        let required_extensions = vulkano_win::required_extensions(&library);
+
+        let instance = Instance::new(library, InstanceCreateInfo {
+            enabled_extensions: required_extensions,
+            ..Default::default()
+        }).expect("failed to create instance");
+
+        let surface = config.builder(event_loop).build_vk_surface(event_loop, instance.clone()).unwrap();
+
+        let device_extensions = DeviceExtensions {
+            khr_swapchain: true,
+            ..DeviceExtensions::empty()
+        };
+
+        let (physical_device, queue_family_index) = select_physical_device(
+            &instance,
+            &surface,
+            &device_extensions,
+        );
+
+        let image_format = physical_device
+            .surface_formats(&surface, Default::default())
+            .unwrap()[0]
+            .0;
+
+        let context = RenderContext::new(instance, physical_device, queue_family_index, device_extensions, image_format, scene);
+
+        let viewer = Viewer::new(context.gpu_scene.bounds, options, bindings);
+        let state = WindowState::new(&context, surface, viewer)
+            .expect("the main window does not support the chosen swapchain format");
+        let main_window = state.window.id();
+
+        WindowManager {
+            context,
+            windows: HashMap::from([(main_window, state)]),
+            main_window,
+            config,
+            focused: Some(main_window),
+            last_frame_time: Instant::now(),
+        }
+    }
+
+    //把主窗口收到的输入事件录制到文件，可以用 replay 回放
+    pub fn start_recording(&mut self, path: &Path) {
+        let state = self.windows.get_mut(&self.main_window).unwrap();
+        match InputRecorder::create(path) {
+            Ok(recorder) => {
+                state.viewer.start_recording(recorder);
+                //回放时需要知道窗口一开始的大小和缩放比例
+                let size = state.window.inner_size();
+                state.viewer.handle_event(&RecordedEvent::Resized([size.width, size.height]));
+                state.viewer.handle_event(&RecordedEvent::ScaleFactorChanged(state.window.scale_factor() as f32));
+            }
+            Err(e) => println!("{e}"),
+        }
+    }
+
+    //记录主窗口每一帧的 GPU 区间，窗口关闭时写入 Chrome trace 文件并打印每个区间的统计
+    pub fn start_profiling(&mut self, path: &Path) {
+        let state = self.windows.get_mut(&self.main_window).unwrap();
+        if state.profiler.is_enabled() {
+            state.profiler.start_trace();
+            state.trace_path = Some(path.to_path_buf());
+        }
+    }
+
+    //打开一个新窗口，它使用和主窗口相同的设备、管线和场景
+    //新窗口的表面不支持共用的交换链格式时返回 None
+    pub fn open_window<T>(&mut self, event_loop: &EventLoopWindowTarget<T>, viewer: Viewer) -> Option<WindowId> {
+        let config = WindowConfig {
+            fullscreen: FullscreenMode::Windowed,
+            ..self.config.clone()
+        };
+        let surface = config.builder(event_loop).build_vk_surface(event_loop, self.context.instance.clone()).unwrap();
+        match WindowState::new(&self.context, surface, viewer) {
+            Some(state) => {
+                let id = state.window.id();
+                self.windows.insert(id, state);
+                Some(id)
+            }
+            None => {
+                println!("the new window does not support the {:?} swapchain format", self.context.image_format);
+                None
+            }
+        }
+    }
+
+    //关闭一个窗口，等待它的帧执行完毕和截图保存完成
+    pub fn close_window(&mut self, id: WindowId) {
+        if let Some(mut state) = self.windows.remove(&id) {
+            state.finish();
+        }
+        if self.focused == Some(id) {
+            self.focused = None;
+        }
+    }
+
+    //处理事件循环中的一个事件，最后一个窗口关闭后退出事件循环
+    pub fn handle_event<T>(&mut self, event: Event<'_, T>, event_loop: &EventLoopWindowTarget<T>, control_flow: &mut ControlFlow) {
+        match event {
+            Event::WindowEvent { window_id, event } => {
+                let Some(state) = self.windows.get_mut(&window_id) else {
+                    return;
+                };
+                state.viewer.handle_window_event(&event);
+                match event {
+                    WindowEvent::CloseRequested => {
+                        self.close_window(window_id);
+                        //事件循环不会返回，退出前保存管线缓存
+                        if self.windows.is_empty() {
+                            self.context.pipelines.save_cache();
+                            *control_flow = ControlFlow::Exit;
+                        }
+                    }
+                    //窗口移到缩放比例不同的显示器上时物理大小也会变化，交换链需要重新创建
+                    WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. } => {
+                        state.window_resized = true;
+                    }
+                    WindowEvent::Focused(true) => {
+                        self.focused = Some(window_id);
+                    }
+                    WindowEvent::Focused(false) if self.focused == Some(window_id) => {
+                        self.focused = None;
+                    }
+                    _ => {}
+                }
+            }
+            Event::DeviceEvent { event, .. } => {
+                if let Some(state) = self.focused.and_then(|id| self.windows.get_mut(&id)) {
+                    state.viewer.handle_device_event(&event);
+                }
+            }
+            //这一帧的事件处理完毕，根据输入更新每个窗口的相机
+            Event::MainEventsCleared => {
+                let now = Instant::now();
+                let delta_time = (now - self.last_frame_time).as_secs_f32();
+                self.last_frame_time = now;
+
+                let mut new_windows = Vec::new();
+                for state in self.windows.values_mut() {
+                    state.stats.push_frame(delta_time * 1000.0);
+                    state.viewer.update(delta_time, self.context.wireframe_supported);
+                    state.take_screenshot_request();
+                    if std::mem::take(&mut state.viewer.fullscreen_requested) {
+                        toggle_fullscreen(&state.window, &self.config);
+                        //切换前后窗口大小可能相同，但独占全屏的交换链也需要重新创建
+                        state.window_resized = true;
+                    }
+                    //新窗口从请求它的窗口的相机和选项开始
+                    if std::mem::take(&mut state.viewer.window_requested) {
+                        let mut viewer = Viewer::new(
+                            state.viewer.bounds,
+                            state.viewer.options.clone(),
+                            state.viewer.bindings.clone(),
+                        );
+                        viewer.camera = state.viewer.camera.clone();
+                        new_windows.push(viewer);
+                    }
+                }
+                for viewer in new_windows {
+                    self.open_window(event_loop, viewer);
+                }
+            }
+            //处理无效的交换链和窗口大小调整，然后每个窗口绘制一帧
+            //所有窗口都最小化时不再轮询，等到有事件（例如窗口恢复时的 Resized）再继续
+            Event::RedrawEventsCleared => {
+                let mut drawn = false;
+                for state in self.windows.values_mut() {
+                    drawn |= state.draw(&mut self.context);
+                }
+                if !self.windows.is_empty() {
+                    *control_flow = if drawn { ControlFlow::Poll } else { ControlFlow::Wait };
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl WindowState {
+    fn new(context: &RenderContext, surface: Arc<Surface>, mut viewer: Viewer) -> Option<WindowState> {
+        let supported = context
+            .physical_device
+            .surface_formats(&surface, Default::default())
+            .unwrap()
+            .iter()
+            .any(|(format, _)| *format == context.image_format);
+        if !supported {
+            return None;
+        }
+
+        let window = surface
+            .object()
+            .unwrap()
+            .clone()
+            .downcast::<Window>()
+            .unwrap();
+        //之后的变化通过 ScaleFactorChanged 事件更新
+        viewer.options.scale_factor = window.scale_factor() as f32;
+
+        let (swapchain, images, screenshots_supported) = create_swapchain(context, surface, &window);
+        let framebuffers = get_framebuffers(&images, &context.render_pass, &context.memory_allocator);
+
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: window.inner_size().into(),
+            depth_range: 0.0..1.0,
+        };
+        let profiler = GpuProfiler::new(context.device.clone(), context.queue.queue_family_index(), images.len(), PROFILER_SCOPES);
+
+        Some(WindowState {
+            window,
+            swapchain,
+            framebuffers,
+            viewport,
+            window_resized: false,
+            recreate_swapchain: false,
+            fences: vec![None; images.len()],
+            previous_fence_i: 0,
+            viewer,
+            screen_capture: ScreenCapture::new(context.memory_allocator.clone()),
+            screenshots_supported,
+            stats: FrameStats::new(),
+            profiler,
+            trace_path: None,
+        })
+    }
+
+    fn take_screenshot_request(&mut self) {
+        if std::mem::take(&mut self.viewer.screenshot_requested) {
+            if self.screenshots_supported {
+                self.screen_capture.request(get_screenshot_path());
+            } else {
+                println!("the swapchain images do not support TRANSFER_SRC, screenshots are unavailable");
+            }
+        }
+    }
+
+    //窗口最小化时大小为0，不能创建交换链，暂停绘制并返回 false
+    fn draw(&mut self, context: &mut RenderContext) -> bool {
+        let new_dimensions = self.window.inner_size();
+        if new_dimensions.width == 0 || new_dimensions.height == 0 {
+            return false;
+        }
+
+        if self.window_resized || self.recreate_swapchain {
+            self.recreate_swapchain = false;
+
+            let (new_swapchain, new_images) = match self.swapchain.recreate(SwapchainCreateInfo {
+                image_extent: new_dimensions.into(), // here, "image_extend" will correspond to the window dimensions
+                ..self.swapchain.create_info()
+            }) {
+                Ok(r) => r,
+                // This error tends to happen when the user is manually resizing the window.
+                // Simply restarting the loop is the easiest way to fix this issue.
+                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return true,
+                Err(e) => panic!("failed to recreate swapchain: {e}"),
+            };
+            self.swapchain = new_swapchain;
+            self.framebuffers = get_framebuffers(&new_images, &context.render_pass, &context.memory_allocator);
+
+            //视口是动态状态，窗口大小变化时只需要更新它，不需要重新创建管线
+            if self.window_resized {
+                self.window_resized = false;
+                self.viewport.dimensions = new_dimensions.into();
+            }
+        }
+
+        //获取和呈现
+        let (image_i, suboptimal, acquire_future) =
+            match swapchain::acquire_next_image(self.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    self.recreate_swapchain = true;
+                    return true;
+                }
+                Err(e) => panic!("failed to acquire next image: {e}"),
+            };
+        if suboptimal {
+            self.recreate_swapchain = true;
+        }
+
+        // wait for the fence related to this image to finish
+        // normally this would be the oldest fence, that most likely have already finished
+        if let Some(image_fence) = &self.fences[image_i as usize] {
+            image_fence.wait(None).unwrap();
+        }
+        //CPU 帧时间不包括上面等待 GPU 的时间
+        let cpu_start = Instant::now();
+        //这个图像上一次的帧已经执行完毕，可以读取它的时间戳
+        if let Some(timings) = self.profiler.collect(image_i as usize) {
+            if let Some(frame) = timings.iter().find(|timing| timing.name == "frame") {
+                self.stats.push_gpu(frame.duration_ms);
+            }
+        }
+
+        let command_buffer = record_frame(
+            context,
+            &mut self.viewer,
+            &self.stats,
+            FrameTarget {
+                framebuffer: &self.framebuffers[image_i as usize],
+                viewport: &self.viewport,
+                present_mode: Some(self.swapchain.present_mode()),
+                slot: image_i as usize,
+            },
+            &mut self.screen_capture,
+            &mut self.profiler,
+        );
+
+        let previous_future = match self.fences[self.previous_fence_i].clone() {
+            // Create a NowFuture
+            None => {
+                let mut now = sync::now(context.device.clone());
+                now.cleanup_finished();
+
+                now.boxed()
+            }
+            // Use the existing FenceSignalFuture
+            Some(fence) => fence.boxed(),
+        };
+
+        let future = previous_future
+            .join(acquire_future)
+            .then_execute(context.queue.clone(), command_buffer)
+            .unwrap()
+            .then_swapchain_present(
+                context.queue.clone(),
+                SwapchainPresentInfo::swapchain_image_index(self.swapchain.clone(), image_i)
+            )
+            .boxed()
+            .then_signal_fence_and_flush();
+
+        self.fences[image_i as usize] = match future {
+            Ok(value) => Some(Arc::new(value)),
+            Err(FlushError::OutOfDate) => {
+                self.recreate_swapchain = true;
+                None
+            }
+            Err(e) => {
+                println!("Failed to flush future: {e}");
+                None
+            }
+        };
+
+        self.previous_fence_i = image_i as usize;
+        self.stats.push_cpu(cpu_start.elapsed().as_secs_f32() * 1000.0);
+        self.screen_capture.poll();
+        true
+    }
+
+    //窗口关闭前等待它提交的帧执行完毕，交换链和表面才能安全地销毁
+    fn finish(&mut self) {
+        for fence in self.fences.iter().flatten() {
+            fence.wait(None).unwrap();
+        }
+        self.screen_capture.finish();
+
+        if let Some(path) = self.trace_path.take() {
+            for slot in 0..self.fences.len() {
+                self.profiler.collect(slot);
+            }
+            print!("{}", self.profiler.summary());
+            match self.profiler.write_chrome_trace(&path) {
+                Ok(()) => println!("saved {}", path.display()),
+                Err(e) => println!("{e}"),
+            }
+        }
+    }
+}
+
+//没有窗口的查看器，输入回放使用
+//交换链换成一张离屏图像，每帧和窗口一样更新查看器、用 record_frame 录制命令缓冲区并提交，HUD、调试线条和截图都和窗口中相同
+//提交以后等待 GPU 执行完毕，所以只需要一个帧槽位
+pub struct HeadlessWindow {
+    context: RenderContext,
+    //窗口的物理大小，由录制的 Resized 事件更新
+    size: [u32; 2],
+    framebuffer: Arc<Framebuffer>,
+    viewport: Viewport,
+    viewer: Viewer,
+    screen_capture: ScreenCapture,
+    stats: FrameStats,
+    profiler: GpuProfiler,
+}
+
+impl HeadlessWindow {
+    //颜色图像使用和离屏渲染相同的 sRGB 格式
+    pub fn new(scene: &Scene, options: RenderOptions, bindings: InputBindings, size: [u32; 2]) -> HeadlessWindow {
+        let instance = create_instance();
+        let physical_device = choose_device(instance.clone());
+        let queue_family_index = get_queue(physical_device.clone());
+        let context = RenderContext::new(
+            instance,
+            physical_device,
+            queue_family_index,
+            DeviceExtensions::empty(),
+            Format::R8G8B8A8_SRGB,
+            scene,
+        );
+
+        let viewer = Viewer::new(context.gpu_scene.bounds, options, bindings);
+        let (framebuffer, viewport) = create_offscreen_target(&context, [size[0].max(1), size[1].max(1)]);
+        let profiler = GpuProfiler::new(context.device.clone(), queue_family_index, 1, PROFILER_SCOPES);
+
+        HeadlessWindow {
+            screen_capture: ScreenCapture::new(context.memory_allocator.clone()),
+            context,
+            size,
+            framebuffer,
+            viewport,
+            viewer,
+            stats: FrameStats::new(),
+            profiler,
+        }
+    }
+
+    //Resized 改变离屏图像的大小，所有事件都交给查看器
+    pub fn handle_event(&mut self, event: &RecordedEvent) {
+        if let RecordedEvent::Resized(size) = event {
+            self.size = *size;
+        }
+        self.viewer.handle_event(event);
+    }
+
+    //和窗口的 MainEventsCleared、RedrawEventsCleared 相同：更新查看器并绘制一帧，帧时间使用录制的值
+    //没有窗口可以切换全屏或打开新窗口，这两个请求直接丢弃；窗口最小化（大小为0）时不绘制并返回 false
+    pub fn frame(&mut self, delta_time: f32) -> bool {
+        self.stats.push_frame(delta_time * 1000.0);
+        self.viewer.update(delta_time, self.context.wireframe_supported);
+        if std::mem::take(&mut self.viewer.screenshot_requested) {
+            self.screen_capture.request(get_screenshot_path());
+        }
+        self.viewer.fullscreen_requested = false;
+        self.viewer.window_requested = false;
+        self.draw()
+    }
+
+    //在下一次绘制的帧中截图
+    pub fn request_capture(&mut self, path: PathBuf) {
+        self.screen_capture.request(path);
+    }
+
+    fn draw(&mut self) -> bool {
+        if self.size[0] == 0 || self.size[1] == 0 {
+            return false;
+        }
+        if self.framebuffer.extent() != self.size {
+            (self.framebuffer, self.viewport) = create_offscreen_target(&self.context, self.size);
+        }
+
+        let cpu_start = Instant::now();
+        let command_buffer = record_frame(
+            &mut self.context,
+            &mut self.viewer,
+            &self.stats,
+            FrameTarget {
+                framebuffer: &self.framebuffer,
+                viewport: &self.viewport,
+                present_mode: None,
+                slot: 0,
+            },
+            &mut self.screen_capture,
+            &mut self.profiler,
+        );
+        let future = sync::now(self.context.device.clone())
+            .then_execute(self.context.queue.clone(), command_buffer)
+            .unwrap()
+            .then_signal_fence_and_flush()
+            .unwrap();
+        self.stats.push_cpu(cpu_start.elapsed().as_secs_f32() * 1000.0);
+        future.wait(None).unwrap();
+
+        if let Some(timings) = self.profiler.collect(0) {
+            if let Some(frame) = timings.iter().find(|timing| timing.name == "frame") {
+                self.stats.push_gpu(frame.duration_ms);
+            }
+        }
+        self.screen_capture.poll();
+        true
+    }
+
+    //等待截图保存完成并保存管线缓存
+    pub fn finish(mut self) {
+        self.screen_capture.finish();
+        self.context.pipelines.save_cache();
+    }
+}
+
+//回放使用的离屏颜色图像，和交换链图像一样可以复制出来截图
+fn create_offscreen_target(context: &RenderContext, size: [u32; 2]) -> (Arc<Framebuffer>, Viewport) {
+    let image = AttachmentImage::with_usage(
+        &context.memory_allocator,
+        size,
+        context.image_format,
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC,
+    ).unwrap();
+    let framebuffer = get_framebuffers(&[image], &context.render_pass, &context.memory_allocator).remove(0);
+    let viewport = Viewport {
+        origin: [0.0, 0.0],
+        dimensions: [size[0] as f32, size[1] as f32],
+        depth_range: 0.0..1.0,
+    };
+    (framebuffer, viewport)
+}
+
+//一帧要绘制到的帧缓冲
+struct FrameTarget<'a> {
+    framebuffer: &'a Arc<Framebuffer>,
+    viewport: &'a Viewport,
+    //HUD 显示的呈现模式，离屏渲染时为 None
+    present_mode: Option<PresentMode>,
+    //GpuProfiler 的槽位
+    slot: usize,
+}
+
+//录制一帧：HUD、文字、uniform 数据和场景的命令缓冲区，窗口和无窗口的输入回放共用
+fn record_frame(
+    context: &mut RenderContext,
+    viewer: &mut Viewer,
+    stats: &FrameStats,
+    target: FrameTarget,
+    screen_capture: &mut ScreenCapture,
+    profiler: &mut GpuProfiler,
+) -> PrimaryAutoCommandBuffer {
+    //HUD 的文字跟在命令行指定的文字后面，曲线和调试线条一起画
+    let hud_labels: Vec<TextLabel>;
+    let labels = if viewer.hud {
+        let scale_factor = viewer.options.scale_factor;
+        let logical_size = [target.viewport.dimensions[0] / scale_factor, target.viewport.dimensions[1] / scale_factor];
+        let info = HudInfo {
+            device_name: &context.physical_device.properties().device_name,
+            present_mode: target.present_mode,
+            resolution: target.framebuffer.extent(),
+        };
+        draw_frame_graph(&mut viewer.debug_draw, stats, logical_size);
+        hud_labels = viewer
+            .options
+            .labels
+            .iter()
+            .cloned()
+            .chain([get_hud_label(stats, &info, logical_size)])
+            .collect();
+        &hud_labels
+    } else {
+        &viewer.options.labels
+    };
+
+    //第一次有文字要画时才加载字体，所有窗口共用一个字形图集
+    if !labels.is_empty() && !context.text_loaded {
+        context.text_loaded = true;
+        context.text_renderer = load_text_font(viewer.options.font.as_deref()).map(|font| {
+            TextRenderer::new(font, context.render_pass.clone(), context.memory_allocator.clone(), context.pipelines.cache())
+        });
+    }
+
+    //每帧写入新的uniform数据，并创建引用它的描述符集
+    let aspect_ratio = target.viewport.dimensions[0] / target.viewport.dimensions[1];
+    let uniforms = viewer.get_uniforms(aspect_ratio);
+    let view = get_view_matrix(&uniforms);
+    let view_proj = get_view_projection(&uniforms);
+    let uniform_subbuffer = get_uniform_subbuffer(&context.uniform_buffer_allocator, uniforms);
+    let descriptor_set = get_descriptor_set(
+        &context.descriptor_set_allocator,
+        &context.pipeline,
+        uniform_subbuffer,
+    );
+
+    get_command_buffer(
+        &context.command_buffer_allocator,
+        &context.queue,
+        &mut context.pipelines,
+        target.framebuffer,
+        target.viewport,
+        &context.gpu_scene,
+        descriptor_set,
+        &view,
+        &viewer.options,
+        Overlays {
+            debug_renderer: &context.debug_renderer,
+            debug_draw: &viewer.debug_draw,
+            view_proj,
+            text_renderer: context.text_renderer.as_mut(),
+            labels,
+        },
+        screen_capture,
+        profiler,
+        target.slot,
+    )
+}
+
+//为窗口的表面创建交换链，第三个返回值表示交换链图像能否用于截图
+fn create_swapchain(
+    context: &RenderContext,
+    surface: Arc<Surface>,
+    window: &Window,
+) -> (Arc<Swapchain>, Vec<Arc<SwapchainImage>>, bool) {
+    let caps = context
+        .physical_device
+        .surface_capabilities(&surface, Default::default())
+        .expect("failed to get surface capabilities");
+
+    let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
+
+    //截图需要从交换链图像复制数据
+    let image_usage = if caps.supported_usage_flags.intersects(ImageUsage::TRANSFER_SRC) {
+        ImageUsage::COLOR_ATTACHMENT | ImageUsage::TRANSFER_SRC
+    } else {
+        ImageUsage::COLOR_ATTACHMENT
+    };
+
+    let (swapchain, images) = Swapchain::new(
+        context.device.clone(),
+        surface,
+        SwapchainCreateInfo {
+            min_image_count: caps.min_image_count + 1, // How many buffers to use in the swapchain
+            image_format: Some(context.image_format),
+            image_extent: window.inner_size().into(),
+            image_usage, // What the images are going to be used for
+            composite_alpha,
+            ..Default::default()
+        },
+    ).unwrap();
+
+    (swapchain, images, image_usage.intersects(ImageUsage::TRANSFER_SRC))
+}
@@ -1,337 +1,77 @@
+use std::path::Path;
 use std::sync::Arc;
-use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
+use cgmath::Matrix4;
+use vulkano::buffer::{BufferUsage, Subbuffer};
+use vulkano::buffer::allocator::{SubbufferAllocator, SubbufferAllocatorCreateInfo};
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::command_buffer::{
     AutoCommandBufferBuilder, CommandBufferUsage, PrimaryAutoCommandBuffer, RenderPassBeginInfo,
     SubpassContents,
 };
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::physical::{PhysicalDevice, PhysicalDeviceType};
-use vulkano::device::{
-    Device, DeviceCreateInfo, DeviceExtensions, Queue, QueueCreateInfo, QueueFlags,
-};
+use vulkano::device::{Device, DeviceExtensions, Queue, QueueFlags};
+use vulkano::format::Format;
 use vulkano::image::view::ImageView;
-use vulkano::image::{ImageUsage, SwapchainImage};
-use vulkano::instance::{Instance, InstanceCreateInfo};
-use vulkano::memory::allocator::{AllocationCreateInfo, MemoryUsage, StandardMemoryAllocator};
-use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
-use vulkano::pipeline::graphics::vertex_input::{BuffersDefinition, Vertex};
-use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
-use vulkano::pipeline::GraphicsPipeline;
-use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
-use vulkano::shader::ShaderModule;
-use vulkano::swapchain::{self, AcquireError, PresentInfo, Surface, Swapchain, SwapchainCreateInfo, SwapchainCreationError, SwapchainPresentInfo};
-use vulkano::sync::future::FenceSignalFuture;
-use vulkano::sync::{self, FlushError, GpuFuture};
-use vulkano::VulkanLibrary;
-use vulkano_win::VkSurfaceBuild;
-use winit::event::{Event, WindowEvent};
-use winit::event_loop::{ControlFlow, EventLoop};
-use winit::window::{Window, WindowBuilder};
-use crate::example::buffer::create_memory_allocator;
-use crate::example::graphics_pipeline::MyVertex;
-use crate::example::pipeline_glsl::{fs, vs};
-
+use vulkano::image::{AttachmentImage, ImageAccess};
+use vulkano::instance::Instance;
+use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
+use vulkano::pipeline::graphics::viewport::Viewport;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+use vulkano::swapchain::Surface;
+use winit::event_loop::EventLoop;
+use crate::example::debug_draw::{get_screen_projection, DebugDraw, DebugDrawRenderer};
+use crate::example::input::InputBindings;
+use crate::example::mesh::{triangle_material, triangle_mesh};
+use crate::example::mesh_glsl::vs;
+use crate::example::pipeline_desc::MeshPipelines;
+use crate::example::profiler::GpuProfiler;
+use crate::example::render_mesh::{draw_scene_with_options, GpuScene, RenderOptions};
+use crate::example::scene::Scene;
+use crate::example::screenshot::ScreenCapture;
+use crate::example::text::{TextLabel, TextRenderer};
+use crate::example::window_config::WindowConfig;
+use crate::example::window_manager::WindowManager;
+
+//在窗口中显示之前的三角形，它随 time uniform 旋转，颜色随时间闪烁
 pub fn create_window() {
-    let library = VulkanLibrary::new().expect("no local Vulkan library/DLL");
-    let required_extensions = vulkano_win::required_extensions(&library);
-
-    let instance = Instance::new(library, InstanceCreateInfo {
-        enabled_extensions: required_extensions,
-        ..Default::default()
-    }).expect("failed to create instance");
-
-    let event_loop = EventLoop::new();
-    let surface = WindowBuilder::new()
-        .build_vk_surface(&event_loop, instance.clone()).unwrap();
-
-    let device_extensions = DeviceExtensions {
-        khr_swapchain: true,
-        ..DeviceExtensions::empty()
-    };
-
-    let (physical_device, queue_family_index) = select_physical_device(
-        &instance,
-        &surface,
-        &device_extensions,
-    );
-
-    let (device, mut queues) = Device::new(
-        physical_device.clone(),
-        DeviceCreateInfo {
-            queue_create_infos: vec![QueueCreateInfo {
-                queue_family_index,
-                ..Default::default()
-            }],
-            enabled_extensions: device_extensions,
+    create_scene_window(
+        Scene::from_mesh(triangle_mesh(), vec![triangle_material()]),
+        RenderOptions {
+            animate: true,
             ..Default::default()
         },
-    ).expect("failed to create device");
-
-    let queue = queues.next().unwrap();
-
-    //创建交换链
-    let caps = physical_device
-        .surface_capabilities(&surface, Default::default())
-        .expect("failed to get surface capabilities");
-
-    //在所有这些属性中，我们只关心其中的一些，主要是图像的尺寸
-    let window = surface
-        .object()
-        .unwrap()
-        .clone()
-        .downcast::<Window>()
-        .unwrap();
-    let dimensions = window.inner_size();
-    let composite_alpha = caps.supported_composite_alpha.into_iter().next().unwrap();
-    let image_format = Some(
-        physical_device
-            .surface_formats(&surface, Default::default())
-            .unwrap()[0]
-            .0,
+        InputBindings::default(),
+        WindowConfig::default(),
+        None,
+        None,
     );
+}
 
-    //结合所有内容，我们可以创建交换链
-    let (mut swapchain, images) = Swapchain::new(
-        device.clone(),
-        surface.clone(),
-        SwapchainCreateInfo {
-            min_image_count: caps.min_image_count + 1, // How many buffers to use in the swapchain
-            image_format,
-            image_extent: dimensions.into(),
-            image_usage: ImageUsage::COLOR_ATTACHMENT, // What the images are going to be used for
-            composite_alpha,
-            ..Default::default()
-        },
-    ).unwrap();
-
-    let render_pass = get_render_pass(device.clone(), &swapchain);
-    let framebuffers = get_framebuffers(&images, &render_pass);
-
-    let vertex1 = MyVertex {
-        position: [-0.5, -0.5],
-    };
-    let vertex2 = MyVertex {
-        position: [0.0, 0.5],
-    };
-    let vertex3 = MyVertex {
-        position: [0.5, -0.25],
-    };
-
-    let command_buffer_allocator =
-        StandardCommandBufferAllocator::new(device.clone(), Default::default());
-
-    let memory_allocator = create_memory_allocator(device.clone());
-    let vertex_buffer = Buffer::from_iter(
-        &memory_allocator,
-        BufferCreateInfo {
-            usage: BufferUsage::VERTEX_BUFFER,
-            ..Default::default()
-        },
-        AllocationCreateInfo {
-            usage: MemoryUsage::Upload,
-            ..Default::default()
-        },
-        vec![vertex1, vertex2, vertex3],
-    )
-        .unwrap();
-
-    let vs = vs::load(device.clone()).expect("failed to create shader module");
-    let fs = fs::load(device.clone()).expect("failed to create shader module");
-
-    let mut viewport = Viewport {
-        origin: [0.0, 0.0],
-        dimensions: window.inner_size().into(),
-        depth_range: 0.0..1.0,
-    };
-
-    let pipeline = get_pipeline(
-        device.clone(),
-        vs.clone(),
-        fs.clone(),
-        render_pass.clone(),
-        viewport.clone(),
-    );
-
-    let mut command_buffers = get_command_buffers(
-        &command_buffer_allocator,
-        &queue,
-        &pipeline,
-        &framebuffers,
-        &vertex_buffer);
-
-    let mut window_resized = false;
-    let mut recreate_swapchain = false;
-
-    let frames_in_flight = images.len();
-    let mut fences: Vec<Option<Arc<FenceSignalFuture<_>>>> = vec![None; frames_in_flight];
-    let mut previous_fence_i = 0;
-
-    event_loop.run(|event, _, control_flow| {
-        match event {
-            Event::WindowEvent {
-                event: WindowEvent::CloseRequested,
-                ..
-            } => {
-                *control_flow = ControlFlow::Exit;
-            }
-            Event::WindowEvent {
-                event: WindowEvent::Resized(_),
-                ..
-            } => {
-                window_resized = true;
-            }
-            Event::MainEventsCleared => {}
-            //处理无效的交换链和窗口大小调整
-            Event::RedrawEventsCleared => {
-                if recreate_swapchain {
-                    recreate_swapchain = false;
-
-                    let new_dimensions = window.inner_size();
-
-                    let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
-                        image_extent: new_dimensions.into(), // here, "image_extend" will correspond to the window dimensions
-                        ..swapchain.create_info()
-                    }) {
-                        Ok(r) => r,
-                        // This error tends to happen when the user is manually resizing the window.
-                        // Simply restarting the loop is the easiest way to fix this issue.
-                        Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-                        Err(e) => panic!("failed to recreate swapchain: {e}"),
-                    };
-                    swapchain = new_swapchain;
-                    let new_framebuffers = get_framebuffers(&new_images, &render_pass);
-                }
-            }
-            _ => (),
-        }
-
-        if window_resized || recreate_swapchain {
-            recreate_swapchain = false;
-
-            let new_dimensions = window.inner_size();
-
-            let (new_swapchain, new_images) = match swapchain.recreate(SwapchainCreateInfo {
-                image_extent: new_dimensions.into(),
-                ..swapchain.create_info()
-            }) {
-                Ok(r) => r,
-                Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-                Err(e) => panic!("failed to recreate swapchain: {e}"),
-            };
-            swapchain = new_swapchain;
-            let new_framebuffers = get_framebuffers(&new_images, &render_pass);
-
-            if window_resized {
-                window_resized = false;
-
-                viewport.dimensions = new_dimensions.into();
-                let new_pipeline = get_pipeline(
-                    device.clone(),
-                    vs.clone(),
-                    fs.clone(),
-                    render_pass.clone(),
-                    viewport.clone(),
-                );
-                command_buffers = get_command_buffers(
-                    &command_buffer_allocator,
-                    &queue,
-                    &new_pipeline,
-                    &new_framebuffers,
-                    &vertex_buffer,
-                );
-            }
-        }
-
-        //获取和呈现
-        let (image_i, suboptimal, acquire_future) =
-            match swapchain::acquire_next_image(swapchain.clone(), None) {
-                Ok(r) => r,
-                Err(AcquireError::OutOfDate) => {
-                    recreate_swapchain = true;
-                    return;
-                }
-                Err(e) => panic!("failed to acquire next image: {e}"),
-            };
-        if suboptimal {
-            recreate_swapchain = true;
-        }
-
-        let previous_future = match fences[previous_fence_i as usize].clone() {
-                // Create a NowFuture
-                None => {
-                    let mut now = sync::now(device.clone());
-                    now.cleanup_finished();
-
-                    now.boxed()
-                }
-                // Use the existing FenceSignalFuture
-                Some(fence) => fence.boxed(),
-            };
-
-            let future = previous_future
-                .join(&acquire_future)
-                .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
-                .unwrap()
-                .then_swapchain_present(
-                    queue.clone(),
-                    SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i),
-                )
-                .then_signal_fence_and_flush();
-
-        /*match execution {
-            Ok(future) => {
-                future.wait(None).unwrap();  // wait for the GPU to finish
-            }
-            Err(FlushError::OutOfDate) => {
-                recreate_swapchain = true;
-            }
-            Err(e) => {
-                println!("Failed to flush future: {e}");
-            }
-        }*/
-
-
-        // wait for the fence related to this image to finish
-        // normally this would be the oldest fence, that most likely have already finished
-        if let Some(image_fence) = &fences[image_i as usize] {
-            image_fence.wait(None).unwrap();
-        }
-
-        let previous_future = match fences[previous_fence_i].clone() {
-            // Create a NowFuture
-            None => {
-                let mut now = sync::now(device.clone());
-                now.cleanup_finished();
-
-                now.boxed()
-            }
-            // Use the existing FenceSignalFuture
-            Some(fence) => fence.boxed(),
-        };
-
-        let future = previous_future
-            .join(acquire_future)
-            .then_execute(queue.clone(), command_buffers[image_i as usize].clone())
-            .unwrap()
-            .then_swapchain_present(
-                queue.clone(),
-                SwapchainPresentInfo::swapchain_image_index(swapchain.clone(), image_i)
-            )
-            .then_signal_fence_and_flush();
-
-        fences[image_i as usize] = match future {
-            Ok(value) => Some(Arc::new(value)),
-            Err(FlushError::OutOfDate) => {
-                recreate_swapchain = true;
-                None
-            }
-            Err(e) => {
-                println!("Failed to flush future: {e}");
-                None
-            }
-        };
-
-        previous_fence_i = image_i as usize;
+//在窗口中显示一个场景，可以用轨道相机或自由相机查看，按 N 打开更多窗口查看同一个场景
+//record 不为 None 时把主窗口收到的输入事件录制到这个文件，可以用 replay 回放
+//profile 不为 None 时记录主窗口每一帧的 GPU 时间，关闭时写成 Chrome trace
+pub fn create_scene_window(
+    scene: Scene,
+    options: RenderOptions,
+    bindings: InputBindings,
+    window: WindowConfig,
+    record: Option<&Path>,
+    profile: Option<&Path>,
+) {
+    let event_loop = EventLoop::new();
+    let mut manager = WindowManager::new(&event_loop, window, &scene, options, bindings);
+    if let Some(path) = record {
+        manager.start_recording(path);
+    }
+    if let Some(path) = profile {
+        manager.start_profiling(path);
+    }
+
+    event_loop.run(move |event, event_loop, control_flow| {
+        manager.handle_event(event, event_loop, control_flow);
     });
 }
 
@@ -373,37 +113,52 @@ pub fn select_physical_device(
         }).expect("no device available")
 }
 
-pub fn get_render_pass(device: Arc<Device>, swapchain: &Arc<Swapchain>) -> Arc<RenderPass> {
+//渲染通道包含颜色附件和深度附件，窗口和离屏渲染共用
+pub fn get_render_pass(device: Arc<Device>, color_format: Format) -> Arc<RenderPass> {
     vulkano::single_pass_renderpass!(
         device,
         attachments: {
             color: {
                 load: Clear,
                 store: Store,
-                format: swapchain.image_format(), // set the format the same as the swapchain
+                format: color_format, // set the format the same as the swapchain
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: Format::D16_UNORM,
                 samples: 1,
             },
         },
         pass: {
             color: [color],
-            depth_stencil: {},
+            depth_stencil: {depth},
         },
     )
         .unwrap()
 }
 
-pub fn get_framebuffers(
-    images: &[Arc<SwapchainImage>],
+//每个颜色图像对应一个帧缓冲，深度图像只在渲染过程中使用，所以创建为临时附件
+pub fn get_framebuffers<I>(
+    images: &[Arc<I>],
     render_pass: &Arc<RenderPass>,
-) -> Vec<Arc<Framebuffer>> {
+    memory_allocator: &StandardMemoryAllocator,
+) -> Vec<Arc<Framebuffer>>
+    where I: ImageAccess + 'static
+{
     images
         .iter()
         .map(|image| {
             let view = ImageView::new_default(image.clone()).unwrap();
+            let depth_buffer = ImageView::new_default(
+                AttachmentImage::transient(memory_allocator, image.dimensions().width_height(), Format::D16_UNORM).unwrap(),
+            )
+                .unwrap();
             Framebuffer::new(
                 render_pass.clone(),
                 FramebufferCreateInfo {
-                    attachments: vec![view],
+                    attachments: vec![view, depth_buffer],
                     ..Default::default()
                 },
             )
@@ -412,58 +167,127 @@ pub fn get_framebuffers(
         .collect::<Vec<_>>()
 }
 
-fn get_pipeline(
-    device: Arc<Device>,
-    vs: Arc<ShaderModule>,
-    fs: Arc<ShaderModule>,
-    render_pass: Arc<RenderPass>,
-    viewport: Viewport,
-) -> Arc<GraphicsPipeline> {
-    GraphicsPipeline::start()
-        .vertex_input_state(MyVertex::per_vertex())
-        .vertex_shader(vs.entry_point("main").unwrap(), ())
-        .input_assembly_state(InputAssemblyState::new())
-        .viewport_state(ViewportState::viewport_fixed_scissor_irrelevant([viewport]))
-        .fragment_shader(fs.entry_point("main").unwrap(), ())
-        .render_pass(Subpass::from(render_pass, 0).unwrap())
-        .build(device)
+//uniform缓冲区分配器
+pub fn create_uniform_buffer_allocator(memory_allocator: Arc<StandardMemoryAllocator>) -> SubbufferAllocator {
+    SubbufferAllocator::new(
+        memory_allocator,
+        SubbufferAllocatorCreateInfo {
+            buffer_usage: BufferUsage::UNIFORM_BUFFER,
+            memory_usage: MemoryUsage::Upload,
+            ..Default::default()
+        },
+    )
+}
+
+//从环形分配器中分配本帧的uniform缓冲区并写入数据
+pub fn get_uniform_subbuffer(
+    uniform_buffer_allocator: &SubbufferAllocator,
+    uniform_data: vs::Data,
+) -> Subbuffer<vs::Data> {
+    let subbuffer = uniform_buffer_allocator.allocate_sized().unwrap();
+    *subbuffer.write().unwrap() = uniform_data;
+
+    subbuffer
+}
+
+//为本帧的uniform缓冲区创建描述符集，描述符集本身也由分配器按帧分配
+pub fn get_descriptor_set(
+    descriptor_set_allocator: &StandardDescriptorSetAllocator,
+    pipeline: &Arc<GraphicsPipeline>,
+    uniform_subbuffer: Subbuffer<vs::Data>,
+) -> Arc<PersistentDescriptorSet> {
+    let layout = pipeline.layout().set_layouts().get(0).unwrap();
+    PersistentDescriptorSet::new(
+        descriptor_set_allocator,
+        layout.clone(),
+        [WriteDescriptorSet::buffer(0, uniform_subbuffer)],
+    )
         .unwrap()
 }
 
-fn get_command_buffers(
+//画在场景上面的调试线条和文字
+pub struct Overlays<'a> {
+    pub debug_renderer: &'a DebugDrawRenderer,
+    pub debug_draw: &'a DebugDraw,
+    //调试线条使用的投影矩阵 * 观察矩阵 * 世界矩阵
+    pub view_proj: Matrix4<f32>,
+    //没有可用的字体时为 None
+    pub text_renderer: Option<&'a mut TextRenderer>,
+    pub labels: &'a [TextLabel],
+}
+
+//uniform数据每帧都会变化，所以命令缓冲区也需要每帧重新录制
+#[allow(clippy::too_many_arguments)]
+pub fn get_command_buffer(
     command_buffer_allocator: &StandardCommandBufferAllocator,
     queue: &Arc<Queue>,
-    pipeline: &Arc<GraphicsPipeline>,
-    framebuffers: &[Arc<Framebuffer>],
-    vertex_buffer: &Subbuffer<[MyVertex]>,
-) -> Vec<Arc<PrimaryAutoCommandBuffer>> {
-    framebuffers
-        .iter()
-        .map(|framebuffer| {
-            let mut builder = AutoCommandBufferBuilder::primary(
-                command_buffer_allocator,
-                queue.queue_family_index(),
-                CommandBufferUsage::MultipleSubmit,
-            )
-            .unwrap();
-
-            builder
-                .begin_render_pass(
-                    RenderPassBeginInfo {
-                        clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into())],
-                        ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                    },
-                    SubpassContents::Inline,
-                )
-                .unwrap()
-                .bind_pipeline_graphics(pipeline.clone())
-                .bind_vertex_buffers(0, vertex_buffer.clone())
-                .draw(vertex_buffer.len() as u32, 1, 0, 0)
-                .unwrap()
-                .end_render_pass()
-                .unwrap();
+    pipelines: &mut MeshPipelines,
+    framebuffer: &Arc<Framebuffer>,
+    viewport: &Viewport,
+    gpu_scene: &GpuScene,
+    descriptor_set: Arc<PersistentDescriptorSet>,
+    view: &Matrix4<f32>,
+    options: &RenderOptions,
+    overlays: Overlays,
+    screen_capture: &mut ScreenCapture,
+    profiler: &mut GpuProfiler,
+    frame_slot: usize,
+) -> PrimaryAutoCommandBuffer {
+    let mut builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+    .unwrap();
+    //frame 区间包括整个命令缓冲区，HUD 显示它的时间
+    profiler.begin_frame(&mut builder, frame_slot);
+    profiler.begin_scope(&mut builder, "frame");
+
+    //文字图集需要在渲染通道开始之前上传
+    profiler.begin_scope(&mut builder, "text_upload");
+    let text = match overlays.text_renderer {
+        Some(renderer) => {
+            let prepared = renderer.prepare(&mut builder, overlays.labels, options.scale_factor);
+            let renderer: &TextRenderer = renderer;
+            prepared.map(|text| (renderer, text))
+        }
+        None => None,
+    };
+    profiler.end_scope(&mut builder);
 
-            Arc::new(builder.build().unwrap())
-        })
-        .collect()
+    builder
+        .begin_render_pass(
+            RenderPassBeginInfo {
+                clear_values: vec![Some([0.0, 0.0, 1.0, 1.0].into()), Some(1f32.into())],
+                ..RenderPassBeginInfo::framebuffer(framebuffer.clone())
+            },
+            SubpassContents::Inline,
+        )
+        .unwrap()
+        .set_viewport(0, [viewport.clone()]);
+    profiler.begin_scope_with_statistics(&mut builder, "scene");
+    draw_scene_with_options(&mut builder, pipelines, gpu_scene, descriptor_set, view, options);
+    profiler.end_scope(&mut builder);
+    profiler.begin_scope_with_statistics(&mut builder, "debug_draw");
+    overlays.debug_renderer.draw(
+        &mut builder,
+        overlays.debug_draw,
+        overlays.view_proj,
+        get_screen_projection(viewport.dimensions, options.scale_factor),
+    );
+    profiler.end_scope(&mut builder);
+    if let Some((renderer, text)) = text {
+        profiler.begin_scope_with_statistics(&mut builder, "text");
+        renderer.draw(&mut builder, text, viewport.dimensions);
+        profiler.end_scope(&mut builder);
+    }
+    builder.end_render_pass().unwrap();
+    //有截图请求时把这一帧的画面复制出来
+    profiler.begin_scope(&mut builder, "screenshot");
+    screen_capture.record(&mut builder, framebuffer.attachments()[0].image());
+    profiler.end_scope(&mut builder);
+    profiler.end_scope(&mut builder);
+    profiler.end_frame();
+
+    builder.build().unwrap()
 }
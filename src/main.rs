@@ -1,7 +1,24 @@
 
+use std::env;
+use std::path::Path;
+use my_winit::example::export::{export_fractal, export_scene, ExportSettings};
+use my_winit::example::fractal::DEFAULT_ZOOM_CENTER;
+use my_winit::example::golden::{run_golden_tests, GoldenSettings};
+use my_winit::example::hdr::{render_hdr, HdrFormat, HdrSettings};
+use my_winit::example::image_diff::{diff_files, DiffSettings, HeatmapMetric};
+use my_winit::example::image_filter::{filter_file, Filter};
+use my_winit::example::image_stats::{analyze_file, HistogramSettings, MAX_BINS};
+use my_winit::example::input::InputBindings;
+use my_winit::example::pipeline_cache::save_pipeline_caches;
+use my_winit::example::recording::replay_recording;
+use my_winit::example::render_mesh::{render_mesh, RenderOptions};
+use my_winit::example::text::TextLabel;
+use my_winit::example::window_config::WindowConfig;
 use my_winit::example::windows::create_window;
 
 fn main() {
+    let args: Vec<String> = env::args().collect();
+
     /*let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     println!("{:?}", now);
 
@@ -14,7 +31,117 @@ fn main() {
     let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
     println!("{:?}", now);
 */
-    create_window();
+    match args.get(1).map(|arg| arg.as_str()) {
+        //render-mesh <model.obj/.gltf/.glb/.stl/.ply> [output.png] [--point-size N] [--scale-factor N] [--wireframe] [--label text] [--font file.ttf] [--bindings input.cfg] [--window window.cfg] [--record input.rec] [--profile trace.json]
+        Some("render-mesh") => {
+            let args = parse_render_args(&args[2..]);
+            match args.positional.first() {
+                Some(path) => render_mesh(
+                    Path::new(path),
+                    args.positional.get(1).map(Path::new),
+                    args.options,
+                    args.bindings,
+                    args.window,
+                    args.record.as_deref().map(Path::new),
+                    args.profile.as_deref().map(Path::new),
+                ),
+                None => println!("usage: my_winit render-mesh <model.obj/.gltf/.glb/.stl/.ply> [output.png] [--point-size N] [--scale-factor N] [--wireframe] [--label text] [--font file.ttf] [--bindings input.cfg] [--window window.cfg] [--record input.rec] [--profile trace.json]"),
+            }
+        }
+        //replay <model> <input.rec> <output.png> [--point-size N] [--wireframe] [--bindings input.cfg]
+        Some("replay") => {
+            let args = parse_render_args(&args[2..]);
+            match args.positional.as_slice() {
+                [model, recording, output] => replay_recording(
+                    Path::new(model),
+                    Path::new(recording),
+                    Path::new(output),
+                    args.options,
+                    args.bindings,
+                ),
+                _ => println!("usage: my_winit replay <model> <input.rec> <output.png> [--point-size N] [--wireframe] [--bindings input.cfg]"),
+            }
+        }
+        //export <model|mandelbrot> <frames-dir|out.y4m|out.gif|-> [--start S] [--end S] [--fps N] [--size WxH] [--center X,Y] [--zoom N]
+        Some("export") => {
+            let args = parse_render_args(&args[2..]);
+            let result = match args.positional.as_slice() {
+                [source, output] if source == "mandelbrot" => export_fractal(Path::new(output), &args.export, args.center, args.zoom),
+                [model, output] => export_scene(Path::new(model), Path::new(output), &args.export, &args.options),
+                _ => Err("usage: my_winit export <model|mandelbrot> <frames-dir|out.y4m|out.gif|-> [--start S] [--end S] [--fps N] [--size WxH] [--center X,Y] [--zoom N] [--point-size N] [--wireframe]".to_string()),
+            };
+            if let Err(e) = result {
+                println!("{e}");
+            }
+        }
+        //golden [case...] [--update] [--golden-dir DIR] [--output DIR] [--tolerance N] [--max-percent P]
+        Some("golden") => {
+            //有用例失败时以非零状态退出，方便在 CI 中使用
+            if !run_golden_tests(&parse_golden_args(&args[2..])) {
+                std::process::exit(1);
+            }
+        }
+        //diff <a.png> <b.png> [heatmap.png] [--metric abs|ssim|flip] [--gpu]
+        Some("diff") => {
+            let (positional, settings) = parse_diff_args(&args[2..]);
+            match positional.as_slice() {
+                [a, b, rest @ ..] if rest.len() <= 1 => {
+                    let heatmap = rest.first().map(Path::new);
+                    match diff_files(Path::new(a), Path::new(b), heatmap, &settings) {
+                        Ok(diff) => {
+                            println!("{}", diff.summary());
+                            if let Some(path) = heatmap {
+                                println!("saved {}", path.display());
+                            }
+                        }
+                        Err(e) => println!("{e}"),
+                    }
+                }
+                _ => println!("usage: my_winit diff <a.png> <b.png> [heatmap.png] [--metric abs|ssim|flip] [--gpu]"),
+            }
+        }
+        //filter <in.png> <out.png> <filter>...，例如 filter in.png out.png blur:5 sobel
+        Some("filter") => match &args[2..] {
+            [input, output, specs @ ..] => match specs.iter().map(|spec| Filter::parse(spec)).collect::<Result<Vec<_>, _>>() {
+                Ok(filters) => match filter_file(Path::new(input), Path::new(output), &filters) {
+                    Ok(()) => println!("saved {output}"),
+                    Err(e) => println!("{e}"),
+                },
+                Err(e) => println!("{e}"),
+            },
+            _ => println!("usage: my_winit filter <in.png> <out.png> [blur:R] [box:R] [sobel[:scale]] [sharpen[:amount]] [brightness:B] [contrast:C] [gamma:G] [grayscale] [threshold[:level]] [lut:file.cube]..."),
+        },
+        //stats <image.png> [--bins N] [--luminance-range MIN,MAX] [--plot plot.png]
+        Some("stats") => {
+            let (positional, settings, plot) = parse_stats_args(&args[2..]);
+            match positional.as_slice() {
+                [path] => match analyze_file(Path::new(path), &settings, plot.as_deref().map(Path::new)) {
+                    Ok(stats) => {
+                        println!("{}", stats.summary());
+                        if let Some(plot) = plot {
+                            println!("saved {plot}");
+                        }
+                    }
+                    Err(e) => println!("{e}"),
+                },
+                _ => println!("usage: my_winit stats <image.png> [--bins N] [--luminance-range MIN,MAX] [--plot plot.png]"),
+            }
+        }
+        //render-hdr <model|mandelbrot> <out.exr|out.hdr|out.png> [--size WxH] [--format f16|f32] [--exposure E] [--center X,Y] [--scale S]
+        Some("render-hdr") => {
+            let (positional, settings) = parse_hdr_args(&args[2..]);
+            match positional.as_slice() {
+                [source, output] => match render_hdr(source, Path::new(output), &settings, &RenderOptions::default()) {
+                    Ok(()) => println!("saved {output}"),
+                    Err(e) => println!("{e}"),
+                },
+                _ => println!("usage: my_winit render-hdr <model|mandelbrot> <out.exr|out.hdr|out.png> [--size WxH] [--format f16|f32] [--exposure E] [--center X,Y] [--scale S]"),
+            }
+        }
+        _ => create_window(),
+    }
+    //各个命令创建管线时留在内存里的编译结果，退出前写回一次
+    save_pipeline_caches();
 
     //winit
     /*let event_loop = EventLoop::new();
@@ -32,6 +159,222 @@ fn main() {
         }
     })*/
 }
+//render-mesh、replay 和 export 共用的命令行参数
+struct RenderArgs {
+    positional: Vec<String>,
+    options: RenderOptions,
+    bindings: InputBindings,
+    window: WindowConfig,
+    record: Option<String>,
+    //GPU 区间的 Chrome trace 输出文件
+    profile: Option<String>,
+    export: ExportSettings,
+    //mandelbrot 缩放的中心和每秒放大的倍数
+    center: [f32; 2],
+    zoom: f32,
+}
+
+fn parse_render_args(args: &[String]) -> RenderArgs {
+    let mut parsed = RenderArgs {
+        positional: Vec::new(),
+        options: RenderOptions::default(),
+        bindings: InputBindings::default(),
+        window: WindowConfig::default(),
+        record: None,
+        profile: None,
+        export: ExportSettings::default(),
+        center: DEFAULT_ZOOM_CENTER,
+        zoom: 2.0,
+    };
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--point-size" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(point_size) => parsed.options.point_size = point_size,
+                None => println!("--point-size expects a number"),
+            },
+            "--wireframe" => parsed.options.wireframe = true,
+            "--scale-factor" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(scale_factor) if scale_factor > 0.0 => parsed.options.scale_factor = scale_factor,
+                _ => println!("--scale-factor expects a positive number"),
+            },
+            "--bindings" => match rest.next().map(|path| InputBindings::load(Path::new(path))) {
+                Some(Ok(bindings)) => parsed.bindings = bindings,
+                Some(Err(e)) => println!("{e}"),
+                None => println!("--bindings expects a file"),
+            },
+            "--window" => match rest.next().map(|path| WindowConfig::load(Path::new(path))) {
+                Some(Ok(window)) => parsed.window = window,
+                Some(Err(e)) => println!("{e}"),
+                None => println!("--window expects a file"),
+            },
+            "--font" => match rest.next() {
+                Some(path) => parsed.options.font = Some(path.into()),
+                None => println!("--font expects a TTF/OTF file"),
+            },
+            //每个 --label 一行，从左上角往下排
+            "--label" => match rest.next() {
+                Some(text) => {
+                    let y = 8.0 + 24.0 * parsed.options.labels.len() as f32;
+                    parsed.options.labels.push(TextLabel {
+                        size: 20.0,
+                        ..TextLabel::new(text.clone(), [8.0, y])
+                    });
+                }
+                None => println!("--label expects some text"),
+            },
+            "--record" => match rest.next() {
+                Some(path) => parsed.record = Some(path.clone()),
+                None => println!("--record expects a file"),
+            },
+            "--profile" => match rest.next() {
+                Some(path) => parsed.profile = Some(path.clone()),
+                None => println!("--profile expects a file"),
+            },
+            "--start" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(start) => parsed.export.start = start,
+                None => println!("--start expects a number of seconds"),
+            },
+            "--end" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(end) => parsed.export.end = end,
+                None => println!("--end expects a number of seconds"),
+            },
+            "--fps" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(fps) if fps > 0 => parsed.export.fps = fps,
+                _ => println!("--fps expects a positive number"),
+            },
+            "--size" => match rest.next().and_then(|value| parse_pair::<u32>(value, 'x')) {
+                Some([width, height]) if width > 0 && height > 0 => {
+                    parsed.export.width = width;
+                    parsed.export.height = height;
+                }
+                _ => println!("--size expects WIDTHxHEIGHT"),
+            },
+            "--center" => match rest.next().and_then(|value| parse_pair::<f32>(value, ',')) {
+                Some(center) => parsed.center = center,
+                None => println!("--center expects X,Y"),
+            },
+            "--zoom" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(zoom) if zoom > 0.0 => parsed.zoom = zoom,
+                _ => println!("--zoom expects a positive number"),
+            },
+            _ => parsed.positional.push(arg.clone()),
+        }
+    }
+    parsed
+}
+
+fn parse_golden_args(args: &[String]) -> GoldenSettings {
+    let mut settings = GoldenSettings::default();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--update" => settings.update = true,
+            "--golden-dir" => match rest.next() {
+                Some(path) => settings.golden_dir = path.into(),
+                None => println!("--golden-dir expects a directory"),
+            },
+            "--output" => match rest.next() {
+                Some(path) => settings.output_dir = path.into(),
+                None => println!("--output expects a directory"),
+            },
+            "--tolerance" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(tolerance) => settings.tolerance = tolerance,
+                None => println!("--tolerance expects a number from 0 to 255"),
+            },
+            "--max-percent" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(max_percent) if max_percent >= 0.0 => settings.max_percent = max_percent,
+                _ => println!("--max-percent expects a non-negative number"),
+            },
+            _ => settings.only.push(arg.clone()),
+        }
+    }
+    settings
+}
+
+fn parse_diff_args(args: &[String]) -> (Vec<String>, DiffSettings) {
+    let mut positional = Vec::new();
+    let mut settings = DiffSettings::default();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--metric" => match rest.next().and_then(|value| HeatmapMetric::from_name(value)) {
+                Some(metric) => settings.metric = metric,
+                None => println!("--metric expects abs, ssim or flip"),
+            },
+            "--gpu" => settings.gpu = true,
+            _ => positional.push(arg.clone()),
+        }
+    }
+    (positional, settings)
+}
+
+fn parse_stats_args(args: &[String]) -> (Vec<String>, HistogramSettings, Option<String>) {
+    let mut positional = Vec::new();
+    let mut settings = HistogramSettings::default();
+    let mut plot = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--bins" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(bins) if (2..=MAX_BINS).contains(&bins) => settings.bins = bins,
+                _ => println!("--bins expects a number from 2 to {MAX_BINS}"),
+            },
+            "--luminance-range" => match rest.next().and_then(|value| parse_pair::<f32>(value, ',')) {
+                Some([min, max]) if min < max => settings.log_luminance_range = [min, max],
+                _ => println!("--luminance-range expects MIN,MAX in log2 units"),
+            },
+            "--plot" => match rest.next() {
+                Some(path) => plot = Some(path.clone()),
+                None => println!("--plot expects a file"),
+            },
+            _ => positional.push(arg.clone()),
+        }
+    }
+    (positional, settings, plot)
+}
+
+fn parse_hdr_args(args: &[String]) -> (Vec<String>, HdrSettings) {
+    let mut positional = Vec::new();
+    let mut settings = HdrSettings::default();
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--size" => match rest.next().and_then(|value| parse_pair::<u32>(value, 'x')) {
+                Some([width, height]) if width > 0 && height > 0 => {
+                    settings.width = width;
+                    settings.height = height;
+                }
+                _ => println!("--size expects WIDTHxHEIGHT"),
+            },
+            "--format" => match rest.next().and_then(|value| HdrFormat::from_name(value)) {
+                Some(format) => settings.format = format,
+                None => println!("--format expects f16 or f32"),
+            },
+            "--exposure" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(exposure) if exposure > 0.0 => settings.exposure = exposure,
+                _ => println!("--exposure expects a positive number"),
+            },
+            "--center" => match rest.next().and_then(|value| parse_pair::<f32>(value, ',')) {
+                Some(center) => settings.center = center,
+                None => println!("--center expects X,Y"),
+            },
+            "--scale" => match rest.next().and_then(|value| value.parse().ok()) {
+                Some(scale) if scale > 0.0 => settings.scale = scale,
+                _ => println!("--scale expects a positive number"),
+            },
+            _ => positional.push(arg.clone()),
+        }
+    }
+    (positional, settings)
+}
+
+//"640x480"、"-0.5,0.1" 这样用分隔符连接的两个数
+fn parse_pair<T: std::str::FromStr>(value: &str, separator: char) -> Option<[T; 2]> {
+    let (first, second) = value.split_once(separator)?;
+    Some([first.trim().parse().ok()?, second.trim().parse().ok()?])
+}
+
 /*#[allow(unused)]
 fn main() {
 mod cs {
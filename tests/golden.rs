@@ -0,0 +1,7 @@
+//用默认设置运行所有 golden 图像用例，没有 Vulkan 驱动时 run_golden_tests 打印原因并跳过
+use my_winit::example::golden::{run_golden_tests, GoldenSettings};
+
+#[test]
+fn golden_images_match() {
+    assert!(run_golden_tests(&GoldenSettings::default()), "golden image tests failed, see the output above");
+}